@@ -13,10 +13,10 @@ DRIVING_ANALYSIS_SCHEMA = {
     "lane_centering": {"following_lane_discipline": True, "score": 20},
     "following_distance": {"safe_distance": "safe", "score": 15},
     "signal_compliance": {
-        "traffic_light": {"status": "green", "compliance": True, "score": 15},
+        "traffic_light": {"status": "none", "compliance": True, "score": 15},
         "stop_sign": {"present": False, "compliance": "N/A", "score": 5},
     },
-    "merging_lane_change": {"safe_merging": True, "score": 10},
+    "merging_lane_change": {"in_progress": False, "safe_merging": True, "score": 10},
     "pedestrian_yielding": {"pedestrian_present": False, "score": 10},
     "intersection_behavior": {"stop_line_observance": True, "score": 10},
     "road_sign_awareness": {
@@ -75,7 +75,7 @@ def analyze_frame(client, image_path):
         },
         "signal_compliance": {
             "traffic_light": {
-                "status": "red" | "yellow" | "green",
+                "status": "red" | "yellow" | "green" | "none",
                 "compliance": boolean,
                 "score": number (0-15)
             },
@@ -86,6 +86,7 @@ def analyze_frame(client, image_path):
             }
         },
         "merging_lane_change": {
+            "in_progress": boolean,
             "safe_merging": boolean,
             "score": number (0-10)
         },
@@ -0,0 +1,56 @@
+"""Write the frame and feature row vglnt-server's `test_frame_features_parity` checks.
+
+`extract_features` is taken from main.py without running the training script
+around it, so the row is exactly what the Keras model is trained on.
+
+    python feature_fixture.py ../vglnt-server/fixtures/features.json
+"""
+import ast
+import json
+import os
+import sys
+
+# Every sub-score differs from its category total, and every one-hot picks a
+# different slot than the all-compliant frame the other tests use
+FRAME = {
+    "lane_centering": {"following_lane_discipline": True, "score": 18},
+    "following_distance": {"safe_distance": "approximate", "score": 9},
+    "signal_compliance": {
+        "traffic_light": {"status": "red", "compliance": True, "score": 12},
+        "stop_sign": {"present": True, "compliance": False, "score": 2},
+    },
+    "merging_lane_change": {"in_progress": True, "safe_merging": False, "score": 4},
+    "pedestrian_yielding": {"pedestrian_present": True, "score": 7},
+    "intersection_behavior": {"stop_line_observance": False, "score": 3},
+    "road_sign_awareness": {
+        "speed_limit_sign": {
+            "visible": True,
+            "observing_limit": "exceeding",
+            "score": 6,
+        },
+        "yield_sign": {"visible": True, "score": 4},
+    },
+    "shoulder_use": {"using_shoulder": True, "score": 1},
+}
+
+
+def load_extract_features():
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "main.py")
+    with open(path) as f:
+        tree = ast.parse(f.read(), path)
+    (function,) = [
+        node
+        for node in tree.body
+        if isinstance(node, ast.FunctionDef) and node.name == "extract_features"
+    ]
+    namespace = {}
+    exec(compile(ast.Module([function], type_ignores=[]), path, "exec"), namespace)
+    return namespace["extract_features"]
+
+
+if __name__ == "__main__":
+    extract_features = load_extract_features()
+    features = [float(value) for value in extract_features(FRAME)]
+    with open(sys.argv[1], "w") as f:
+        json.dump({"frame": FRAME, "features": features}, f, indent=2)
+        f.write("\n")
@@ -7,6 +7,9 @@ import json
 import glob
 import os
 
+# Fills padded timesteps; far outside the range of standardised features.
+PAD_VALUE = -1e4
+
 
 def load_driving_data(annotations_dir, max_frames=200):
     data = []
@@ -36,6 +39,29 @@ def load_driving_data(annotations_dir, max_frames=200):
     return data, labels
 
 
+def export_npz(model, scaler, path):
+    """Write weights and scaler in the layout vglnt-server's `load_weights` reads."""
+    arrays = {}
+    lstm_layers = [l for l in model.layers if isinstance(l, keras.layers.LSTM)]
+    dense_layers = [l for l in model.layers if isinstance(l, keras.layers.Dense)]
+    for i, layer in enumerate(lstm_layers):
+        kernel, recurrent_kernel, bias = layer.get_weights()
+        arrays[f"lstm_{i}.kernel"] = kernel
+        arrays[f"lstm_{i}.recurrent_kernel"] = recurrent_kernel
+        arrays[f"lstm_{i}.bias"] = bias
+    for i, layer in enumerate(dense_layers):
+        kernel, bias = layer.get_weights()
+        arrays[f"dense_{i}.kernel"] = kernel
+        arrays[f"dense_{i}.bias"] = bias
+    np.savez(path, **arrays)
+
+    scaler_path = os.path.splitext(path)[0] + ".scaler.json"
+    with open(scaler_path, "w") as f:
+        json.dump(
+            {"mean": scaler.mean_.tolist(), "scale": scaler.scale_.tolist()}, f
+        )
+
+
 def extract_features(frame_data):
     features = []
     features.append(float(frame_data["lane_centering"]["following_lane_discipline"]))
@@ -100,17 +126,23 @@ for video in driving_data:
 
 max_frames = max(len(video_features) for video_features in X)
 
+# Scale real frames only, as vglnt-server's FeatureScaler does, then pad with
+# a value the Masking layer skips, so padding never reaches the LSTMs
+scaler = StandardScaler()
+scaler.fit(np.concatenate([np.asarray(video, dtype="float32") for video in X]))
+X = [scaler.transform(np.asarray(video, dtype="float32")) for video in X]
+
 X = keras.preprocessing.sequence.pad_sequences(
-    X, maxlen=max_frames, dtype="float32", padding="post", truncating="post"
+    X,
+    maxlen=max_frames,
+    dtype="float32",
+    padding="post",
+    truncating="post",
+    value=PAD_VALUE,
 )
 
 y = np.array(labels)
 
-scaler = StandardScaler()
-X_reshaped = X.reshape(-1, X.shape[-1])
-X_scaled = scaler.fit_transform(X_reshaped)
-X = X_scaled.reshape(X.shape)
-
 X_train, X_temp, y_train, y_temp = train_test_split(
     X, y, test_size=0.3, random_state=42
 )
@@ -120,12 +152,12 @@ X_val, X_test, y_val, y_test = train_test_split(
 
 model = keras.Sequential()
 model.add(
-    keras.layers.LSTM(
-        units=512,
-        return_sequences=True,
+    keras.layers.Masking(
+        mask_value=PAD_VALUE,
         input_shape=(X_train.shape[1], X_train.shape[2]),
     )
 )
+model.add(keras.layers.LSTM(units=512, return_sequences=True))
 model.add(keras.layers.Dropout(0.5))
 model.add(keras.layers.LSTM(units=256, return_sequences=True))
 model.add(keras.layers.Dropout(0.5))
@@ -161,3 +193,4 @@ print(f"Test Loss: {loss}, Test MAE: {mae}")
 
 
 model.save("../../models/lstm_model.pt")
+export_npz(model, scaler, "../../models/lstm_model.npz")
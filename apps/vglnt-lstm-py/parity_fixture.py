@@ -0,0 +1,151 @@
+"""Write the ONNX model vglnt-server's `test_onnx_parity` compares with tch.
+
+No weights are stored: every tensor is computed in the graph as
+SCALE * sin(FREQ * j + k), where j is the flat index in the Keras layout
+`export_npz` writes and k is the tensor's position in `weights()`. The test
+builds the same tensors for the tch backend, so the fixture matches the full
+architecture, per-timestep heads included, in a few kilobytes.
+
+    python parity_fixture.py ../vglnt-server/fixtures/parity.onnx
+"""
+import json
+import os
+import sys
+
+import onnx
+from onnx import TensorProto, helper
+
+INPUT_SIZE = 30
+LSTM_SIZES = [512, 256, 128]
+DENSE_SIZES = [128, 64]
+HEAD_HIDDEN = 64
+HEAD_WIDTH = 12
+FREQ = 0.7
+SCALE = 0.1
+# Keras packs the gates as i, f, c, o; ONNX expects i, o, f, c
+GATE_ORDER = [0, 3, 1, 2]
+
+
+def weights():
+    """(name, Keras shape) of every tensor, in the order that sets k."""
+    shapes = []
+    input_size = INPUT_SIZE
+    for i, hidden in enumerate(LSTM_SIZES):
+        shapes.append((f"lstm_{i}.kernel", [input_size, 4 * hidden]))
+        shapes.append((f"lstm_{i}.recurrent_kernel", [hidden, 4 * hidden]))
+        shapes.append((f"lstm_{i}.bias", [4 * hidden]))
+        input_size = hidden
+    for i, size in enumerate(DENSE_SIZES + [1]):
+        shapes.append((f"dense_{i}.kernel", [input_size, size]))
+        shapes.append((f"dense_{i}.bias", [size]))
+        input_size = size
+    shapes.append(("fc1.kernel", [LSTM_SIZES[-1], HEAD_HIDDEN]))
+    shapes.append(("fc1.bias", [HEAD_HIDDEN]))
+    shapes.append(("fc2.kernel", [HEAD_HIDDEN, HEAD_WIDTH]))
+    shapes.append(("fc2.bias", [HEAD_WIDTH]))
+    return shapes
+
+
+class Graph:
+    def __init__(self):
+        self.nodes = []
+        self.initializers = []
+
+    def const(self, values, data_type=TensorProto.FLOAT, dims=None):
+        name = f"const_{len(self.initializers)}"
+        dims = [len(values)] if dims is None else dims
+        self.initializers.append(helper.make_tensor(name, data_type, dims, values))
+        return name
+
+    def ints(self, values):
+        return self.const(values, TensorProto.INT64)
+
+    def scalar(self, value):
+        return self.const([value], dims=[])
+
+    def op(self, op_type, *inputs, outputs=1, **attrs):
+        names = [f"{op_type.lower()}_{len(self.nodes)}_{i}" for i in range(outputs)]
+        self.nodes.append(helper.make_node(op_type, list(inputs), names, **attrs))
+        return names[0] if outputs == 1 else names
+
+    def weight(self, k, shape):
+        size = 1
+        for dim in shape:
+            size *= dim
+        index = self.op("Range", self.scalar(0.0), self.scalar(float(size)), self.scalar(1.0))
+        phase = self.op("Add", self.op("Mul", index, self.scalar(FREQ)), self.scalar(float(k)))
+        values = self.op("Mul", self.op("Sin", phase), self.scalar(SCALE))
+        return self.op("Reshape", values, self.ints(shape))
+
+    def reorder_gates(self, tensor, hidden, rest):
+        """Reorders the leading 4 * hidden axis from Keras to ONNX gate order."""
+        gates = self.op("Reshape", tensor, self.ints([4, hidden] + rest))
+        gates = self.op("Gather", gates, self.ints(GATE_ORDER), axis=0)
+        return self.op("Reshape", gates, self.ints([1, 4 * hidden] + rest))
+
+
+def build():
+    graph = Graph()
+    tensors = {name: (k, shape) for k, (name, shape) in enumerate(weights())}
+    weight = lambda name: graph.weight(*tensors[name])
+
+    # Batch-major [1, seq, INPUT_SIZE] in, time-major for the LSTMs
+    sequence = graph.op("Transpose", "features", perm=[1, 0, 2])
+    input_size = INPUT_SIZE
+    for i, hidden in enumerate(LSTM_SIZES):
+        kernel = graph.op("Transpose", weight(f"lstm_{i}.kernel"))
+        recurrent = graph.op("Transpose", weight(f"lstm_{i}.recurrent_kernel"))
+        bias = graph.reorder_gates(weight(f"lstm_{i}.bias"), hidden, [])
+        # Keras has a single bias; the recurrent half stays zero
+        zeros = graph.op("Mul", bias, graph.scalar(0.0))
+        outputs = graph.op(
+            "LSTM",
+            sequence,
+            graph.reorder_gates(kernel, hidden, [input_size]),
+            graph.reorder_gates(recurrent, hidden, [hidden]),
+            graph.op("Concat", bias, zeros, axis=1),
+            outputs=2,
+            hidden_size=hidden,
+        )
+        # [seq, 1, 1, hidden] -> [seq, 1, hidden]
+        sequence = graph.op("Reshape", outputs[0], graph.ints([-1, 1, hidden]))
+        final_state = outputs[1]
+        input_size = hidden
+
+    dense = lambda x, name: graph.op(
+        "Add", graph.op("MatMul", x, weight(f"{name}.kernel")), weight(f"{name}.bias")
+    )
+    hidden = graph.op("Reshape", final_state, graph.ints([1, LSTM_SIZES[-1]]))
+    for i in range(len(DENSE_SIZES)):
+        hidden = graph.op("Relu", dense(hidden, f"dense_{i}"))
+    graph.nodes.append(
+        helper.make_node("Identity", [dense(hidden, f"dense_{len(DENSE_SIZES)}")], ["drive_score"])
+    )
+
+    heads = dense(graph.op("Relu", dense(sequence, "fc1")), "fc2")
+    graph.nodes.append(helper.make_node("Transpose", [heads], ["head_logits"], perm=[1, 0, 2]))
+
+    return helper.make_model(
+        helper.make_graph(
+            graph.nodes,
+            "vglnt_parity",
+            [helper.make_tensor_value_info("features", TensorProto.FLOAT, [1, "seq", INPUT_SIZE])],
+            # Left to inference, so they follow whatever the loader names seq
+            [
+                helper.make_tensor_value_info("drive_score", TensorProto.FLOAT, None),
+                helper.make_tensor_value_info("head_logits", TensorProto.FLOAT, None),
+            ],
+            initializer=graph.initializers,
+        ),
+        producer_name="vglnt-lstm-py",
+        opset_imports=[helper.make_opsetid("", 13)],
+    )
+
+
+if __name__ == "__main__":
+    path = sys.argv[1]
+    onnx.save(build(), path)
+    # Brings the raw 0-20 scores into the range the formula weights expect
+    scaler_path = os.path.splitext(path)[0] + ".scaler.json"
+    with open(scaler_path, "w") as f:
+        json.dump({"mean": [0.0] * INPUT_SIZE, "scale": [10.0] * INPUT_SIZE}, f)
@@ -0,0 +1,334 @@
+//! Caller authentication: API keys from a TOML file and, optionally, HS256
+//! JWT bearer tokens. Every caller belongs to a tenant and sees only that
+//! tenant's analyses unless it has the admin role.
+
+use super::quota::Limits;
+use super::AppState;
+use crate::error::AppError;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::middleware::Next;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, ResponseError};
+use anyhow::{Context, Result};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Tenant of every caller when authentication is disabled.
+const DEFAULT_TENANT: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Member,
+    /// Sees and manages the analyses of every tenant.
+    Admin,
+}
+
+/// The authenticated caller, available to handlers as an extractor. Queued
+/// jobs keep it so a restarted job runs under its uploader's limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Principal {
+    pub tenant: String,
+    pub role: Role,
+    pub limits: Limits,
+}
+
+impl Principal {
+    /// The tenant to restrict store lookups to; `None` for admins.
+    pub fn scope(&self) -> Option<&str> {
+        match self.role {
+            Role::Admin => None,
+            Role::Member => Some(&self.tenant),
+        }
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Principal>()
+                .cloned()
+                .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string())),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyFile {
+    #[serde(default)]
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyEntry {
+    key: String,
+    tenant: String,
+    #[serde(default)]
+    role: Role,
+    /// Overrides of the default limits for this key.
+    #[serde(default)]
+    limits: Limits,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    tenant: String,
+    #[serde(default)]
+    role: Role,
+}
+
+pub struct Authenticator {
+    /// Principals by the SHA-256 of their key, so lookups do not compare
+    /// secrets byte by byte and the plaintext keys are not kept around.
+    keys: HashMap<[u8; 32], Principal>,
+    jwt: Option<(DecodingKey, Validation)>,
+    /// Limits of bearer tokens and of keys that do not set their own.
+    default_limits: Limits,
+    disabled: bool,
+}
+
+impl Authenticator {
+    /// Reads keys from the TOML file at `VGLNT_API_KEYS_FILE` and the JWT
+    /// secret from `VGLNT_JWT_SECRET`. Fails when neither is set, unless
+    /// `VGLNT_AUTH_DISABLED=1` makes every caller an admin.
+    pub fn from_env() -> Result<Self> {
+        let default_limits = Limits::from_env();
+        let keys = match std::env::var("VGLNT_API_KEYS_FILE") {
+            Ok(path) => load_keys(Path::new(&path), default_limits)?,
+            Err(_) => HashMap::new(),
+        };
+        let jwt = std::env::var("VGLNT_JWT_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| {
+                (
+                    DecodingKey::from_secret(secret.as_bytes()),
+                    Validation::new(Algorithm::HS256),
+                )
+            });
+        let disabled = std::env::var("VGLNT_AUTH_DISABLED").is_ok_and(|v| v == "1");
+
+        if disabled {
+            warn!("Authentication is disabled; every caller is an admin");
+        } else if keys.is_empty() && jwt.is_none() {
+            anyhow::bail!(
+                "No credentials configured; set VGLNT_API_KEYS_FILE or VGLNT_JWT_SECRET, \
+                 or VGLNT_AUTH_DISABLED=1 for local use"
+            );
+        }
+        Ok(Self {
+            keys,
+            jwt,
+            default_limits,
+            disabled,
+        })
+    }
+
+    /// Identifies the caller from `X-API-Key` or `Authorization: Bearer`.
+    pub fn authenticate(&self, req: &ServiceRequest) -> Result<Principal, AppError> {
+        if self.disabled {
+            return Ok(Principal {
+                tenant: DEFAULT_TENANT.to_string(),
+                role: Role::Admin,
+                limits: self.default_limits,
+            });
+        }
+        let headers = req.headers();
+
+        if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+            return self
+                .keys
+                .get(&digest(key))
+                .cloned()
+                .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()));
+        }
+        if let Some(authorization) = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+            let token = authorization
+                .strip_prefix("Bearer ")
+                .ok_or_else(|| AppError::Unauthorized("Expected a bearer token".to_string()))?;
+            // An API key is also accepted as a bearer token
+            if let Some(principal) = self.keys.get(&digest(token)) {
+                return Ok(principal.clone());
+            }
+            let (key, validation) = self
+                .jwt
+                .as_ref()
+                .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))?;
+            let claims = jsonwebtoken::decode::<Claims>(token, key, validation)
+                .map_err(|e| AppError::Unauthorized(format!("Invalid token: {}", e)))?
+                .claims;
+            return Ok(Principal {
+                tenant: claims.tenant,
+                role: claims.role,
+                limits: self.default_limits,
+            });
+        }
+        Err(AppError::Unauthorized(
+            "Missing X-API-Key or Authorization header".to_string(),
+        ))
+    }
+}
+
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+fn load_keys(path: &Path, default_limits: Limits) -> Result<HashMap<[u8; 32], Principal>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read API keys from {}", path.display()))?;
+    parse_keys(&contents, default_limits)
+        .with_context(|| format!("Invalid API key file {}", path.display()))
+}
+
+/// Parses `[[keys]]` tables with `key`, `tenant`, an optional `role` and an
+/// optional `[keys.limits]` table.
+fn parse_keys(contents: &str, default_limits: Limits) -> Result<HashMap<[u8; 32], Principal>> {
+    let file: KeyFile = toml::from_str(contents)?;
+    let mut keys = HashMap::new();
+    for entry in file.keys {
+        if entry.key.is_empty() || entry.tenant.is_empty() {
+            anyhow::bail!("API keys need a non-empty key and tenant");
+        }
+        let principal = Principal {
+            tenant: entry.tenant,
+            role: entry.role,
+            limits: entry.limits.or(default_limits),
+        };
+        if keys.insert(digest(&entry.key), principal).is_some() {
+            anyhow::bail!("Duplicate API key");
+        }
+    }
+    Ok(keys)
+}
+
+/// Middleware that rejects unauthenticated and rate-limited requests and
+/// stores the [`Principal`] of the rest in the request extensions.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let state = req
+        .app_data::<web::Data<Arc<AppState>>>()
+        .cloned()
+        .ok_or_else(|| AppError::Internal("Application state missing".to_string()))?;
+    let principal = state
+        .authenticator
+        .authenticate(&req)
+        .and_then(|principal| state.quotas.check_request(&principal).map(|()| principal));
+    match principal {
+        Ok(principal) => {
+            req.extensions_mut().insert(principal);
+            Ok(next.call(req).await?.map_into_left_body())
+        }
+        // A response rather than an error, so the outer middleware still
+        // tags, logs and counts refused requests
+        Err(e) => Ok(req.into_response(e.error_response()).map_into_right_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    fn authenticator() -> Authenticator {
+        let keys = parse_keys(
+            r#"
+            [[keys]]
+            key = "acme-key"
+            tenant = "acme"
+
+            [keys.limits]
+            concurrent_jobs = 4
+
+            [[keys]]
+            key = "ops-key"
+            tenant = "ops"
+            role = "admin"
+            "#,
+            Limits {
+                concurrent_jobs: Some(1),
+                frames_per_month: Some(10_000),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        Authenticator {
+            keys,
+            jwt: Some((
+                DecodingKey::from_secret(b"secret"),
+                Validation::new(Algorithm::HS256),
+            )),
+            default_limits: Limits::default(),
+            disabled: false,
+        }
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let auth = authenticator();
+
+        let req = TestRequest::default()
+            .insert_header((API_KEY_HEADER, "acme-key"))
+            .to_srv_request();
+        let principal = auth.authenticate(&req).unwrap();
+        assert_eq!(principal.scope(), Some("acme"));
+        assert_eq!(principal.limits.concurrent_jobs, Some(4));
+        assert_eq!(principal.limits.frames_per_month, Some(10_000));
+
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, "Bearer ops-key"))
+            .to_srv_request();
+        assert_eq!(auth.authenticate(&req).unwrap().scope(), None);
+
+        let req = TestRequest::default()
+            .insert_header((API_KEY_HEADER, "wrong"))
+            .to_srv_request();
+        assert_eq!(auth.authenticate(&req).unwrap_err().code(), "unauthorized");
+        let req = TestRequest::default().to_srv_request();
+        assert!(auth.authenticate(&req).is_err());
+
+        assert!(parse_keys("[[keys]]\nkey = \"k\"\ntenant = \"\"", Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_jwt_bearer() {
+        let auth = authenticator();
+        let token = |secret: &[u8]| {
+            let claims = json!({ "tenant": "fleet-7", "exp": u32::MAX });
+            jsonwebtoken::encode(
+                &Header::default(),
+                &claims,
+                &EncodingKey::from_secret(secret),
+            )
+            .unwrap()
+        };
+
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, format!("Bearer {}", token(b"secret"))))
+            .to_srv_request();
+        let principal = auth.authenticate(&req).unwrap();
+        assert_eq!(principal.tenant, "fleet-7");
+        assert_eq!(principal.role, Role::Member);
+
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, format!("Bearer {}", token(b"other"))))
+            .to_srv_request();
+        assert!(auth.authenticate(&req).is_err());
+    }
+}
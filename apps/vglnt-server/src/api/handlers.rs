@@ -1,107 +1,601 @@
-use actix_multipart::Multipart;
-use actix_web::{web, HttpResponse};
+use actix_files::NamedFile;
+use actix_multipart::{Field, Multipart};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::DateTime;
+use dashmap::mapref::entry::Entry;
 use futures::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use serde_json::json;
 use uuid::Uuid;
 use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
 use tempfile::NamedTempFile;
+use tracing::warn;
 use crate::error::AppError;
+use crate::metrics::METRICS;
+use crate::telemetry;
+use crate::types::{AnalysisStatus, DrivingAnalysis};
+use crate::video::clip::{ClipOptions, MAX_ROLL};
+use crate::video::render::{RenderOptions, RenderStatus};
+use super::auth::Principal;
+use super::health;
+use super::quota::JobPermit;
+use super::jobs;
+use super::queue::QueuedJob;
+use super::store::{ListQuery, SortKey, STATUSES};
 use super::AppState;
 
+const MAX_PER_PAGE: usize = 100;
+/// Largest telemetry file accepted, well above an hour of 10 Hz NMEA.
+const MAX_TELEMETRY_BYTES: usize = 32 * 1024 * 1024;
+/// Largest value accepted for the other non-video form fields.
+const MAX_TEXT_FIELD_BYTES: usize = 4 * 1024;
+
 pub async fn upload_video(
     mut payload: Multipart,
     state: web::Data<Arc<AppState>>,
+    principal: Principal,
 ) -> Result<HttpResponse, AppError> {
+    if state.is_shutting_down() {
+        return Err(AppError::Unavailable("Server is shutting down".to_string()));
+    }
+    let permit = state.quotas.start_job(&principal)?;
     let analysis_id = Uuid::new_v4();
-    let mut temp_file = NamedTempFile::new()
+    let mut temp_file = NamedTempFile::new_in(state.videos.dir())
         .map_err(|e| AppError::Internal(e.to_string()))?;
-    
-    while let Ok(Some(mut field)) = payload.try_next().await {
-        while let Some(chunk) = field.next().await {
-            let data = chunk.map_err(|e| AppError::InvalidInput(e.to_string()))?;
-            temp_file.write_all(&data)
-                .map_err(|e| AppError::Internal(e.to_string()))?;
-        }
-    }
-    
-    let path = temp_file.path().to_str()
-        .ok_or_else(|| AppError::Internal("Invalid path".to_string()))?;
-    
-    state.analysis_cache.insert(analysis_id, AnalysisStatus::Processing);
-    
-    // Spawn analysis task
-    let state = Arc::clone(&state);
-    let path = path.to_string();
-    tokio::spawn(async move {
-        match state.video_analyzer.process_video(&path).await {
-            Ok(result) => {
-                state.analysis_cache.insert(analysis_id, AnalysisStatus::Complete(result));
+    let mut filename = String::from("upload.mp4");
+    let mut telemetry_file: Option<(String, Vec<u8>)> = None;
+    let mut telemetry_offset = 0.0;
+    let mut callback_url = None;
+    let mut budget = UploadBudget { permit: &permit, size_limit: state.max_upload_bytes, used: 0 };
+
+    while let Some(mut field) = payload.try_next().await? {
+        let field_name = field.name().to_string();
+        let field_filename = field.content_disposition().get_filename().map(str::to_string);
+        match field_name.as_str() {
+            "telemetry" => {
+                let data = read_field(&mut field, &mut budget, MAX_TELEMETRY_BYTES).await?;
+                let name = field_filename.unwrap_or_else(|| "telemetry".to_string());
+                telemetry_file = Some((name, data));
+            }
+            "telemetry_offset" => {
+                let data = read_field(&mut field, &mut budget, MAX_TEXT_FIELD_BYTES).await?;
+                telemetry_offset = String::from_utf8_lossy(&data).trim().parse()
+                    .map_err(|_| AppError::InvalidInput(
+                        "telemetry_offset must be a number of seconds".to_string(),
+                    ))?;
             }
-            Err(e) => {
-                state.analysis_cache.insert(
-                    analysis_id,
-                    AnalysisStatus::Failed(e.to_string())
-                );
+            "callback_url" => {
+                let data = read_field(&mut field, &mut budget, MAX_TEXT_FIELD_BYTES).await?;
+                let url = String::from_utf8_lossy(&data);
+                callback_url = Some(state.webhooks.validate_url(url.trim()).await?);
             }
+            _ => {
+                if let Some(content_type) = field.content_type() {
+                    let essence = content_type.essence_str();
+                    if !essence.starts_with("video/") && essence != "application/octet-stream" {
+                        return Err(AppError::UnsupportedMediaType(format!(
+                            "Expected a video upload, got {}", essence
+                        )));
+                    }
+                }
+                if let Some(name) = field_filename {
+                    filename = name;
+                }
+                while let Some(chunk) = field.next().await {
+                    let data = chunk?;
+                    budget.take(data.len())?;
+                    temp_file.write_all(&data)
+                        .map_err(|e| AppError::Internal(e.to_string()))?;
+                }
+            }
+        }
+    }
+
+    let telemetry = match telemetry_file {
+        Some((name, data)) => {
+            let contents = String::from_utf8(data).map_err(|_| {
+                AppError::InvalidInput("Telemetry file is not valid UTF-8".to_string())
+            })?;
+            let telemetry = telemetry::parse(&name, &contents, telemetry_offset)
+                .map_err(|e| AppError::InvalidInput(format!("{:#}", e)))?;
+            Some(telemetry)
         }
-    });
-    
+        None => None,
+    };
+    permit.record_upload(budget.used)?;
+    METRICS.uploads.inc();
+    METRICS.upload_bytes.inc_by(budget.used);
+
+    // Keep the video and a queue entry on disk, so the job survives a restart
+    temp_file.persist(state.videos.path(analysis_id))
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let job = QueuedJob {
+        analysis_id,
+        principal,
+        filename,
+        upload_time: SystemTime::now(),
+        telemetry,
+        callback_url,
+        attempts: 1,
+    };
+    if let Err(e) = state.queue.push(&job) {
+        let _ = state.videos.remove(analysis_id);
+        return Err(AppError::Internal(format!("{:#}", e)));
+    }
+
+    state.analyses.insert(
+        analysis_id, job.principal.tenant.clone(), job.filename.clone(), job.upload_time,
+    );
+    if let Some(url) = &job.callback_url {
+        state.webhooks.set_callback(analysis_id, url.clone());
+    }
+    jobs::spawn(state.get_ref(), job, Some(permit));
+
     Ok(HttpResponse::Ok().json(json!({
         "analysis_id": analysis_id,
         "status": "processing"
     })))
 }
 
+/// Reads a non-video field into memory, refusing it past `max_len` bytes.
+async fn read_field(
+    field: &mut Field,
+    budget: &mut UploadBudget<'_>,
+    max_len: usize,
+) -> Result<Vec<u8>, AppError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        budget.take(chunk.len())?;
+        if data.len() + chunk.len() > max_len {
+            return Err(AppError::PayloadTooLarge(format!(
+                "The {} field exceeds {} bytes", field.name(), max_len
+            )));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// Bytes of an upload so far, across all of its multipart fields.
+struct UploadBudget<'a> {
+    permit: &'a JobPermit,
+    size_limit: u64,
+    used: u64,
+}
+
+impl UploadBudget<'_> {
+    /// Counts `len` more bytes against the upload size limit and the daily quota.
+    fn take(&mut self, len: usize) -> Result<(), AppError> {
+        self.used += len as u64;
+        if self.used > self.size_limit {
+            return Err(AppError::PayloadTooLarge("Upload exceeds the size limit".to_string()));
+        }
+        self.permit.check_upload(self.used)
+    }
+}
+
 pub async fn get_analysis_status(
     analysis_id: web::Path<Uuid>,
     state: web::Data<Arc<AppState>>,
+    principal: Principal,
 ) -> Result<HttpResponse, AppError> {
-    let status = state.analysis_cache
-        .get(&analysis_id)
+    let status = state.analyses
+        .get(&analysis_id, principal.scope())
         .ok_or_else(|| AppError::NotFound("Analysis not found".to_string()))?;
-        
+
     Ok(HttpResponse::Ok().json(&*status))
 }
 
 pub async fn get_analysis_result(
     analysis_id: web::Path<Uuid>,
     state: web::Data<Arc<AppState>>,
+    principal: Principal,
 ) -> Result<HttpResponse, AppError> {
-    let status = state.analysis_cache
-        .get(&analysis_id)
+    let status = state.analyses
+        .get(&analysis_id, principal.scope())
         .ok_or_else(|| AppError::NotFound("Analysis not found".to_string()))?;
-        
+
     match &*status {
-        AnalysisStatus::Complete(result) => Ok(HttpResponse::Ok().json(result)),
-        AnalysisStatus::Failed(error) => Err(AppError::ProcessingError(error.clone())),
-        AnalysisStatus::Processing => Ok(HttpResponse::Ok().json(json!({
-            "status": "processing"
-        }))),
+        AnalysisStatus::Complete { analysis, .. } => Ok(HttpResponse::Ok().json(analysis)),
+        AnalysisStatus::Failed { error, code, .. } => {
+            Err(AppError::from_code(code.as_deref(), error.clone()))
+        }
+        AnalysisStatus::Queued | AnalysisStatus::Processing { .. } => {
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "processing"
+            })))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListParams {
+    page: Option<usize>,
+    per_page: Option<usize>,
+    /// One of `queued`, `processing`, `complete` or `failed`.
+    status: Option<String>,
+    /// RFC 3339 timestamps bounding the upload time, inclusive.
+    uploaded_after: Option<String>,
+    uploaded_before: Option<String>,
+    min_score: Option<f32>,
+    max_score: Option<f32>,
+    /// Case-insensitive filename search.
+    q: Option<String>,
+    /// `upload_time` (the default), `score` or `filename`.
+    sort: Option<String>,
+    /// `asc` or `desc` (the default).
+    order: Option<String>,
+}
+
+impl ListParams {
+    fn into_query(self) -> Result<ListQuery, AppError> {
+        let invalid = |message: String| AppError::InvalidInput(message);
+        let time = |value: Option<String>, name: &str| -> Result<Option<SystemTime>, AppError> {
+            value.map(|v| DateTime::parse_from_rfc3339(&v)
+                .map(SystemTime::from)
+                .map_err(|_| invalid(format!("{} must be an RFC 3339 timestamp", name))))
+                .transpose()
+        };
+
+        let defaults = ListQuery::default();
+        let per_page = self.per_page.unwrap_or(defaults.per_page);
+        if per_page == 0 || per_page > MAX_PER_PAGE {
+            return Err(invalid(format!("per_page must be between 1 and {}", MAX_PER_PAGE)));
+        }
+        let status = match self.status {
+            Some(status) => Some(*STATUSES.iter().find(|s| **s == status).ok_or_else(|| {
+                invalid(format!("status must be one of {}", STATUSES.join(", ")))
+            })?),
+            None => None,
+        };
+        let sort = match self.sort.as_deref() {
+            None | Some("upload_time") => SortKey::UploadTime,
+            Some("score") => SortKey::Score,
+            Some("filename") => SortKey::Filename,
+            Some(_) => return Err(invalid(
+                "sort must be one of upload_time, score, filename".to_string(),
+            )),
+        };
+        let descending = match self.order.as_deref() {
+            None | Some("desc") => true,
+            Some("asc") => false,
+            Some(_) => return Err(invalid("order must be asc or desc".to_string())),
+        };
+
+        Ok(ListQuery {
+            page: self.page.unwrap_or(defaults.page).max(1),
+            per_page,
+            status,
+            uploaded_after: time(self.uploaded_after, "uploaded_after")?,
+            uploaded_before: time(self.uploaded_before, "uploaded_before")?,
+            min_score: self.min_score,
+            max_score: self.max_score,
+            filename: self.q.filter(|q| !q.is_empty()),
+            sort,
+            descending,
+        })
     }
 }
 
 pub async fn list_analyses(
+    params: web::Query<ListParams>,
     state: web::Data<Arc<AppState>>,
+    principal: Principal,
 ) -> Result<HttpResponse, AppError> {
-    let analyses: Vec<_> = state.analysis_cache
-        .iter()
-        .map(|entry| json!({
-            "id": entry.key(),
-            "status": entry.value()
-        }))
-        .collect();
-        
-    Ok(HttpResponse::Ok().json(analyses))
+    let mut query = params.into_inner().into_query()?;
+    query.tenant = principal.scope().map(str::to_string);
+    Ok(HttpResponse::Ok().json(state.analyses.list(&query)))
 }
 
 pub async fn delete_analysis(
     analysis_id: web::Path<Uuid>,
     state: web::Data<Arc<AppState>>,
+    principal: Principal,
 ) -> Result<HttpResponse, AppError> {
-    state.analysis_cache
-        .remove(&analysis_id)
+    let analysis_id = analysis_id.into_inner();
+    let entry = state.analyses
+        .remove(&analysis_id, principal.scope())
         .ok_or_else(|| AppError::NotFound("Analysis not found".to_string()))?;
-        
+    // Still queued or running, so the delete cancels it
+    if let Some((_, job)) = state.jobs.remove(&analysis_id) {
+        job.abort();
+        state.webhooks.cancelled(&entry.tenant, analysis_id);
+    } else {
+        state.webhooks.remove_callback(analysis_id);
+    }
+    if let Err(e) = state.queue.remove(analysis_id) {
+        warn!("Failed to dequeue {}: {}", analysis_id, e);
+    }
+    state.renders.retain(|(id, _), _| *id != analysis_id);
+    let removed = state.videos.remove(analysis_id)
+        .and(state.renderer.remove(analysis_id))
+        .and(state.clipper.remove(analysis_id));
+    if let Err(e) = removed {
+        warn!("Failed to delete files of {}: {}", analysis_id, e);
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "deleted"
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenderQuery {
+    /// Comma-separated overlays to draw; all of them when omitted.
+    overlays: Option<String>,
+}
+
+/// Serves the annotated video, starting a background render on first request.
+///
+/// Responds 202 while the render is in progress; poll until it returns the MP4.
+pub async fn render_video(
+    req: HttpRequest,
+    analysis_id: web::Path<Uuid>,
+    query: web::Query<RenderQuery>,
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    let analysis_id = analysis_id.into_inner();
+    let options = match &query.overlays {
+        Some(overlays) => RenderOptions::parse(overlays)
+            .map_err(|e| AppError::InvalidInput(e.to_string()))?,
+        None => RenderOptions::default(),
+    };
+
+    let (analysis, source) = completed_with_source(&state, &principal, analysis_id)?;
+
+    let key = (analysis_id, options);
+    let status = match state.renders.entry(key) {
+        Entry::Occupied(entry) => entry.get().clone(),
+        Entry::Vacant(entry) => {
+            entry.insert(RenderStatus::Rendering);
+            let state = Arc::clone(state.get_ref());
+            tokio::spawn(async move {
+                let renderer = Arc::clone(&state.renderer);
+                let rendered = tokio::task::spawn_blocking(move || {
+                    renderer.render(&source, &analysis, options)
+                })
+                .await;
+                let status = match rendered {
+                    Ok(Ok(path)) => RenderStatus::Ready(path),
+                    Ok(Err(e)) => RenderStatus::Failed(format!("{:#}", e)),
+                    Err(e) => RenderStatus::Failed(e.to_string()),
+                };
+                // Deleted meanwhile: keep neither the status nor the file
+                if state.analyses.get(&analysis_id, None).is_none() {
+                    if let RenderStatus::Ready(path) = &status {
+                        if let Err(e) = std::fs::remove_file(path) {
+                            warn!("Failed to delete render of {}: {}", analysis_id, e);
+                        }
+                    }
+                    state.renders.remove(&key);
+                    return;
+                }
+                state.renders.insert(key, status);
+            });
+            RenderStatus::Rendering
+        }
+    };
+
+    match status {
+        RenderStatus::Ready(path) => serve_file(&req, path).await,
+        RenderStatus::Rendering => Ok(HttpResponse::Accepted().json(json!({
+            "analysis_id": analysis_id,
+            "status": "rendering"
+        }))),
+        RenderStatus::Failed(error) => {
+            // Let the next request try again
+            state.renders.remove(&key);
+            Err(AppError::ProcessingError(error))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClipQuery {
+    /// Seconds before the event; the server default when omitted.
+    pre_roll: Option<f64>,
+    /// Seconds after the event; the server default when omitted.
+    post_roll: Option<f64>,
+}
+
+pub async fn event_clip(
+    req: HttpRequest,
+    path: web::Path<(Uuid, usize)>,
+    query: web::Query<ClipQuery>,
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    let (analysis_id, event_idx) = path.into_inner();
+    let defaults = state.clipper.defaults();
+    let options = ClipOptions {
+        pre_roll: query.pre_roll.unwrap_or(defaults.pre_roll),
+        post_roll: query.post_roll.unwrap_or(defaults.post_roll),
+    };
+    for roll in [options.pre_roll, options.post_roll] {
+        if !(0.0..=MAX_ROLL).contains(&roll) {
+            return Err(AppError::InvalidInput(format!(
+                "pre_roll and post_roll must be between 0 and {} seconds", MAX_ROLL
+            )));
+        }
+    }
+
+    let (analysis, source) = completed_with_source(&state, &principal, analysis_id)?;
+    let event = analysis.summary.critical_events
+        .get(event_idx)
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("Event {} not found", event_idx)))?;
+
+    let clipper = Arc::clone(&state.clipper);
+    let clip = tokio::task::spawn_blocking(move || {
+        clipper.clip(&source, analysis_id, event_idx, &event, options)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(|e| AppError::ProcessingError(format!("{:#}", e)))?;
+
+    serve_file(&req, clip).await
+}
+
+pub async fn frame_thumbnail(
+    req: HttpRequest,
+    path: web::Path<(Uuid, u32)>,
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    let (analysis_id, frame_number) = path.into_inner();
+    let (analysis, source) = completed_with_source(&state, &principal, analysis_id)?;
+    if frame_number >= analysis.metadata.frame_count {
+        return Err(AppError::NotFound(format!("Frame {} not found", frame_number)));
+    }
+
+    let clipper = Arc::clone(&state.clipper);
+    let thumbnail = tokio::task::spawn_blocking(move || {
+        clipper.thumbnail(&source, analysis_id, frame_number)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(|e| AppError::ProcessingError(format!("{:#}", e)))?;
+
+    serve_file(&req, thumbnail).await
+}
+
+/// A completed analysis and the path of its retained source video.
+fn completed_with_source(
+    state: &AppState,
+    principal: &Principal,
+    analysis_id: Uuid,
+) -> Result<(DrivingAnalysis, PathBuf), AppError> {
+    let analysis = match &*state.analyses
+        .get(&analysis_id, principal.scope())
+        .ok_or_else(|| AppError::NotFound("Analysis not found".to_string()))?
+    {
+        AnalysisStatus::Complete { analysis, .. } => analysis.clone(),
+        _ => return Err(AppError::InvalidInput("Analysis is not complete".to_string())),
+    };
+    let source = state.videos.path(analysis_id);
+    if !source.exists() {
+        return Err(AppError::NotFound("Source video is no longer available".to_string()));
+    }
+    Ok((analysis, source))
+}
+
+/// Serves a cached file with its content type and `Range` request support.
+async fn serve_file(req: &HttpRequest, path: PathBuf) -> Result<HttpResponse, AppError> {
+    let file = NamedFile::open_async(&path).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(file.into_response(req))
+}
+
+/// Fallback for unknown routes, so they get the error envelope too.
+pub async fn route_not_found(req: HttpRequest) -> Result<HttpResponse, AppError> {
+    Err(AppError::NotFound(format!("No route for {} {}", req.method(), req.path())))
+}
+
+/// The caller's usage and limits; admins may pass `tenant` to see another tenant.
+pub async fn get_usage(
+    query: web::Query<UsageQuery>,
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    let principal = match query.into_inner().tenant {
+        Some(tenant) if principal.scope().is_none() => Principal { tenant, ..principal },
+        _ => principal,
+    };
+    Ok(HttpResponse::Ok().json(state.quotas.usage(&principal)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    tenant: Option<String>,
+}
+
+/// The caller's webhook endpoint and signing secret.
+pub async fn get_webhook(
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(state.webhooks.endpoint(&principal.tenant)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookRegistration {
+    url: String,
+}
+
+pub async fn register_webhook(
+    body: web::Json<WebhookRegistration>,
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    let url = state.webhooks.validate_url(&body.url).await?;
+    Ok(HttpResponse::Ok().json(state.webhooks.register(&principal.tenant, url)))
+}
+
+pub async fn unregister_webhook(
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    if !state.webhooks.unregister(&principal.tenant) {
+        return Err(AppError::NotFound("No webhook registered".to_string()));
+    }
     Ok(HttpResponse::Ok().json(json!({
         "status": "deleted"
     })))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryQuery {
+    analysis_id: Option<Uuid>,
+}
+
+pub async fn list_deliveries(
+    query: web::Query<DeliveryQuery>,
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(json!({
+        "deliveries": state.webhooks.deliveries(principal.scope(), query.analysis_id)
+    })))
+}
+
+/// Sends a logged delivery again, signed with the current secret.
+pub async fn redeliver_webhook(
+    delivery_id: web::Path<Uuid>,
+    state: web::Data<Arc<AppState>>,
+    principal: Principal,
+) -> Result<HttpResponse, AppError> {
+    let delivery = state.webhooks
+        .redeliver(principal.scope(), delivery_id.into_inner())
+        .ok_or_else(|| AppError::NotFound("Delivery not found".to_string()))?;
+    Ok(HttpResponse::Accepted().json(delivery))
+}
+
+/// Prometheus metrics in the text format, left unauthenticated for scrapers.
+pub async fn metrics(state: web::Data<Arc<AppState>>) -> HttpResponse {
+    METRICS.queue_depth.set(state.jobs.len() as i64);
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(METRICS.render())
+}
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "status": "ok"
+    }))
+}
+
+/// Readiness: 200 when every dependency check passes, 503 otherwise.
+pub async fn readyz(state: web::Data<Arc<AppState>>) -> HttpResponse {
+    let readiness = health::check(&state).await;
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
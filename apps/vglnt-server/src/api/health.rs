@@ -0,0 +1,167 @@
+//! Readiness checks of the dependencies an analysis needs, reported one by
+//! one so an orchestrator (and whoever is paged) can see which one failed.
+
+use super::AppState;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Free space the spool directory needs to accept uploads, unless overridden.
+const DEFAULT_MIN_FREE_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    /// Why the check failed, or what it found when it passed.
+    pub detail: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Reads the free space threshold from `VGLNT_MIN_FREE_BYTES`.
+pub fn min_free_bytes_from_env() -> u64 {
+    std::env::var("VGLNT_MIN_FREE_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MIN_FREE_BYTES)
+}
+
+pub async fn check(state: &AppState) -> Readiness {
+    let started = Instant::now();
+    let model_server = match state.video_analyzer.llm().health().await {
+        Ok(()) => (true, "answering".to_string()),
+        Err(e) => (false, format!("{:#}", e)),
+    };
+    let model_server = timed("model_server", started, model_server);
+
+    let started = Instant::now();
+    let sequence_model = state.video_analyzer.sequence_model();
+    let sequence_model = timed(
+        "sequence_model",
+        started,
+        if !sequence_model.weights_loaded() {
+            (
+                false,
+                format!("{} is running on untrained weights", sequence_model.name()),
+            )
+        } else if !sequence_model.heads_loaded() {
+            (
+                true,
+                format!(
+                    "{} weights loaded; no trained risk and pattern heads, reporting the drive \
+                     score only",
+                    sequence_model.name()
+                ),
+            )
+        } else {
+            (true, format!("{} weights loaded", sequence_model.name()))
+        },
+    );
+
+    let dir = state.videos.dir().to_path_buf();
+    let min_free_bytes = state.min_free_bytes;
+    let started = Instant::now();
+    let (store, spool) =
+        tokio::task::spawn_blocking(move || (writable(&dir), free_space(&dir, min_free_bytes)))
+            .await
+            .unwrap_or_else(|e| {
+                let failed = (false, e.to_string());
+                (failed.clone(), failed)
+            });
+    let store = timed("store", started, store);
+    let spool = timed("spool", started, spool);
+
+    let mut dirs = vec![
+        ("queue", state.queue.dir().to_path_buf()),
+        ("renders", state.renderer.dir().to_path_buf()),
+        ("clips", state.clipper.dir().to_path_buf()),
+    ];
+    if let Some(dir) = state.analyses.dir() {
+        dirs.push(("results", dir.to_path_buf()));
+    }
+    let mut dir_checks = Vec::with_capacity(dirs.len());
+    for (name, dir) in dirs {
+        let started = Instant::now();
+        let result = tokio::task::spawn_blocking(move || writable(&dir))
+            .await
+            .unwrap_or_else(|e| (false, e.to_string()));
+        dir_checks.push(timed(name, started, result));
+    }
+
+    let accepting_uploads = timed(
+        "accepting_uploads",
+        Instant::now(),
+        if state.is_shutting_down() {
+            (false, "Shutting down".to_string())
+        } else {
+            (true, "Accepting uploads".to_string())
+        },
+    );
+
+    let mut checks = vec![
+        accepting_uploads,
+        model_server,
+        sequence_model,
+        store,
+        spool,
+    ];
+    checks.extend(dir_checks);
+    Readiness {
+        ready: checks.iter().all(|check| check.ok),
+        checks,
+    }
+}
+
+fn timed(name: &'static str, started: Instant, (ok, detail): (bool, String)) -> Check {
+    Check {
+        name,
+        ok,
+        detail,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Creates and deletes a probe file in `dir`.
+fn writable(dir: &Path) -> (bool, String) {
+    let probe = dir.join(format!(".readyz-{}", Uuid::new_v4()));
+    let result = std::fs::write(&probe, b"ok").and_then(|_| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => (true, format!("{} is writable", dir.display())),
+        Err(e) => (false, format!("Cannot write to {}: {}", dir.display(), e)),
+    }
+}
+
+fn free_space(dir: &Path, min_free_bytes: u64) -> (bool, String) {
+    match fs2::available_space(dir) {
+        Ok(free) => (
+            free >= min_free_bytes,
+            format!("{} bytes free of the {} required", free, min_free_bytes),
+        ),
+        Err(e) => (
+            false,
+            format!("Cannot read free space of {}: {}", dir.display(), e),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_checks() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(writable(dir.path()).0);
+        assert!(!writable(&dir.path().join("missing")).0);
+
+        assert!(free_space(dir.path(), 0).0);
+        assert!(!free_space(dir.path(), u64::MAX).0);
+    }
+}
@@ -0,0 +1,472 @@
+//! The lifecycle of an analysis job: started on upload or recovered from the
+//! queue at startup, finished, aborted by a delete, or drained on shutdown.
+
+use super::auth::Principal;
+use super::queue::{JobQueue, QueuedJob};
+use super::quota::{JobPermit, QuotaTracker};
+use super::request_id;
+use super::store::{status_name, AnalysisStore};
+use super::webhook::Webhooks;
+use super::AppState;
+use crate::error::AppError;
+use crate::metrics::METRICS;
+use crate::types::AnalysisStatus;
+use crate::video::store::VideoStore;
+use dashmap::DashMap;
+use futures::future::{self, AbortHandle};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+/// How often a draining shutdown checks whether the jobs are done.
+const DRAIN_POLL: Duration = Duration::from_millis(250);
+/// How often a recovered job checks for a free slot of its tenant.
+const RESUME_POLL: Duration = Duration::from_secs(1);
+/// Starts a job gets before it is failed, unless overridden.
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Runs a queued analysis in the background. Without a permit, as for jobs
+/// recovered at startup, it first waits for a job slot of its tenant.
+///
+/// Jobs run on the main runtime rather than an HTTP worker's, so they keep
+/// going while the server stops and shutdown can wait for them.
+pub fn spawn(state: &Arc<AppState>, job: QueuedJob, permit: Option<JobPermit>) {
+    let analysis_id = job.analysis_id;
+    // A child of the request span, if any, so an upload and its analysis share a trace
+    let span = info_span!(
+        "analysis",
+        %analysis_id,
+        tenant = %job.principal.tenant,
+        request_id = %request_id::current().unwrap_or_default(),
+    );
+    let (task, abort) = future::abortable(run(Arc::clone(state), job, permit).instrument(span));
+    state.jobs.insert(analysis_id, abort);
+    state.runtime.spawn(task);
+}
+
+async fn run(state: Arc<AppState>, job: QueuedJob, permit: Option<JobPermit>) {
+    let permit = match permit {
+        Some(permit) => permit,
+        None => resume(&state.quotas, &job.principal).await,
+    };
+    let analysis_id = job.analysis_id;
+    let start_time = SystemTime::now();
+    let processing = |frames_processed, total_frames| AnalysisStatus::Processing {
+        start_time,
+        frames_processed,
+        total_frames,
+    };
+    // Frames are only counted once decoding is done
+    state.analyses.set_status(analysis_id, processing(0, 0));
+    let source = state.videos.path(analysis_id);
+    let result = match source.to_str() {
+        Some(path) => {
+            state
+                .video_analyzer
+                .process_video(
+                    analysis_id,
+                    &job.filename,
+                    path,
+                    job.telemetry,
+                    |frames_processed, total_frames| {
+                        permit.record_frames(1);
+                        state
+                            .analyses
+                            .set_status(analysis_id, processing(frames_processed, total_frames));
+                    },
+                )
+                .await
+        }
+        None => Err(anyhow::anyhow!("Invalid source video path")),
+    };
+    // Deleted while running; there is no outcome left to record
+    if state.jobs.remove(&analysis_id).is_none() {
+        return;
+    }
+
+    let status = match result {
+        Ok(analysis) => AnalysisStatus::Complete {
+            analysis,
+            completion_time: SystemTime::now(),
+        },
+        Err(e) => {
+            let error = AppError::from(e);
+            AnalysisStatus::Failed {
+                error: error.message().to_string(),
+                code: Some(error.code().to_string()),
+                timestamp: SystemTime::now(),
+            }
+        }
+    };
+    finish(&state, &job.principal.tenant, analysis_id, status);
+}
+
+/// Waits until the tenant has a free job slot.
+async fn resume(quotas: &Arc<QuotaTracker>, principal: &Principal) -> JobPermit {
+    loop {
+        if let Some(permit) = quotas.resume_job(principal) {
+            return permit;
+        }
+        tokio::time::sleep(RESUME_POLL).await;
+    }
+}
+
+/// Records the final status of an analysis, sends its webhooks and takes it
+/// off the queue. Only completed analyses keep their source video.
+pub fn finish(state: &AppState, tenant: &str, analysis_id: Uuid, status: AnalysisStatus) {
+    METRICS
+        .jobs
+        .with_label_values(&[status_name(&status)])
+        .inc();
+    info!(%analysis_id, status = status_name(&status), "Analysis finished");
+    if !matches!(status, AnalysisStatus::Complete { .. }) {
+        if let Err(e) = state.videos.remove(analysis_id) {
+            warn!("Failed to delete source video of {}: {}", analysis_id, e);
+        }
+    }
+    settle(
+        &state.analyses,
+        &state.webhooks,
+        &state.queue,
+        tenant,
+        analysis_id,
+        status,
+    );
+}
+
+/// Stores the final status, then sends the webhooks for what was stored, then
+/// dequeues the job. A receiver fetching the result sees it, and a crash
+/// after the status is stored never runs the job, or sends its event, again.
+fn settle(
+    analyses: &AnalysisStore,
+    webhooks: &Arc<Webhooks>,
+    queue: &JobQueue,
+    tenant: &str,
+    analysis_id: Uuid,
+    status: AnalysisStatus,
+) {
+    analyses.set_status(analysis_id, status);
+    if let Some(stored) = analyses.get(&analysis_id, None) {
+        webhooks.notify(tenant, analysis_id, &stored);
+    }
+    if let Err(e) = queue.remove(analysis_id) {
+        warn!("Failed to dequeue {}: {}", analysis_id, e);
+    }
+}
+
+/// The jobs a previous process left queued, sorted by what to do with them.
+#[derive(Debug, Default)]
+struct Recovery {
+    /// To run again, with the new attempt already counted in the queue.
+    resume: Vec<QueuedJob>,
+    /// Started `max_attempts` times without finishing, so to be failed.
+    exhausted: Vec<QueuedJob>,
+}
+
+/// Sorts the queued jobs, dequeuing those already finished and those whose
+/// video is gone.
+fn claim(
+    queue: &JobQueue,
+    videos: &VideoStore,
+    analyses: &AnalysisStore,
+    max_attempts: u32,
+) -> anyhow::Result<Recovery> {
+    let mut recovery = Recovery::default();
+    for mut job in queue.pending()? {
+        let analysis_id = job.analysis_id;
+        if analyses.get(&analysis_id, None).is_some() {
+            queue.remove(analysis_id)?;
+        } else if !videos.path(analysis_id).exists() {
+            warn!(
+                "Dropping queued analysis {}: its video is gone",
+                analysis_id
+            );
+            queue.remove(analysis_id)?;
+        } else if job.attempts >= max_attempts {
+            recovery.exhausted.push(job);
+        } else {
+            job.attempts += 1;
+            queue.push(&job)?;
+            recovery.resume.push(job);
+        }
+    }
+    queue.sync()?;
+    Ok(recovery)
+}
+
+/// Restarts the analyses a previous process left queued, returning how many,
+/// and deletes the videos no analysis refers to any more. Jobs that have
+/// already been started `VGLNT_JOB_MAX_ATTEMPTS` times are failed instead.
+pub fn recover(state: &Arc<AppState>) -> anyhow::Result<usize> {
+    let max_attempts = std::env::var("VGLNT_JOB_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    let recovery = claim(&state.queue, &state.videos, &state.analyses, max_attempts)?;
+
+    for job in recovery.exhausted {
+        let analysis_id = job.analysis_id;
+        warn!(
+            "Failing queued analysis {} after {} attempts",
+            analysis_id, job.attempts
+        );
+        state.analyses.insert(
+            analysis_id,
+            job.principal.tenant.clone(),
+            job.filename.clone(),
+            job.upload_time,
+        );
+        if let Some(url) = &job.callback_url {
+            state.webhooks.set_callback(analysis_id, url.clone());
+        }
+        let error = AppError::ProcessingError(format!(
+            "Analysis did not finish after {} attempts",
+            job.attempts
+        ));
+        let status = AnalysisStatus::Failed {
+            error: error.message().to_string(),
+            code: Some(error.code().to_string()),
+            timestamp: SystemTime::now(),
+        };
+        finish(state, &job.principal.tenant, analysis_id, status);
+    }
+
+    let recovered = recovery.resume.len();
+    for job in recovery.resume {
+        state.analyses.insert(
+            job.analysis_id,
+            job.principal.tenant.clone(),
+            job.filename.clone(),
+            job.upload_time,
+        );
+        if let Some(url) = &job.callback_url {
+            state.webhooks.set_callback(job.analysis_id, url.clone());
+        }
+        spawn(state, job, None);
+    }
+
+    let orphaned = state
+        .videos
+        .retain(|analysis_id| state.analyses.get(&analysis_id, None).is_some())?;
+    if orphaned > 0 {
+        info!("Deleted {} videos of no analysis", orphaned);
+    }
+    Ok(recovered)
+}
+
+/// Waits for running analyses to finish until the shutdown deadline, then
+/// stops the rest, which stay queued and run again on the next start. The
+/// deadline counts from when shutdown began, so it covers the HTTP
+/// server's own wait too.
+pub async fn drain(state: &AppState, deadline: Duration) {
+    let until = state.shutdown_started().unwrap_or_else(Instant::now) + deadline;
+    if !state.jobs.is_empty() {
+        info!(
+            "Waiting up to {:?} for {} running analyses",
+            until.saturating_duration_since(Instant::now()),
+            state.jobs.len()
+        );
+    }
+    let stopped = wait_or_abort(&state.jobs, until).await;
+    if !stopped.is_empty() {
+        warn!(
+            "Stopped {} unfinished analyses; they will resume on restart",
+            stopped.len()
+        );
+    }
+    release_attempts(&state.queue, &stopped);
+    if let Err(e) = state.queue.sync() {
+        warn!("Failed to sync the job queue: {}", e);
+    }
+    if let Err(e) = state.analyses.sync() {
+        warn!("Failed to sync the stored analyses: {}", e);
+    }
+}
+
+/// Waits until `jobs` is empty or `until` passes, then aborts and returns
+/// the jobs still running.
+async fn wait_or_abort(jobs: &DashMap<Uuid, AbortHandle>, until: Instant) -> Vec<Uuid> {
+    while !jobs.is_empty() && Instant::now() < until {
+        tokio::time::sleep(DRAIN_POLL.min(until.saturating_duration_since(Instant::now()))).await;
+    }
+    let unfinished: Vec<Uuid> = jobs.iter().map(|job| *job.key()).collect();
+    let mut stopped = Vec::new();
+    for analysis_id in unfinished {
+        // A job finishing meanwhile removes itself first
+        if let Some((_, job)) = jobs.remove(&analysis_id) {
+            job.abort();
+            stopped.push(analysis_id);
+        }
+    }
+    stopped
+}
+
+/// Gives back the attempts of jobs stopped at shutdown, which did not fail.
+fn release_attempts(queue: &JobQueue, stopped: &[Uuid]) {
+    for analysis_id in stopped {
+        let released = queue.get(*analysis_id).and_then(|job| match job {
+            Some(mut job) if job.attempts > 0 => {
+                job.attempts -= 1;
+                queue.push(&job)
+            }
+            _ => Ok(()),
+        });
+        if let Err(e) = released {
+            warn!("Failed to requeue {}: {:#}", analysis_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::queue::fixtures::job;
+
+    struct Dirs {
+        _dir: tempfile::TempDir,
+        queue: JobQueue,
+        videos: VideoStore,
+    }
+
+    fn dirs() -> Dirs {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = JobQueue::new(dir.path().join("queue")).unwrap();
+        let videos = VideoStore::new(dir.path().join("videos")).unwrap();
+        Dirs {
+            _dir: dir,
+            queue,
+            videos,
+        }
+    }
+
+    /// Queues `job` with its video on disk.
+    fn queued(dirs: &Dirs, job: &QueuedJob) {
+        dirs.queue.push(job).unwrap();
+        std::fs::write(dirs.videos.path(job.analysis_id), b"").unwrap();
+    }
+
+    #[test]
+    fn test_claim() {
+        let dirs = dirs();
+        let analyses = AnalysisStore::new();
+        let (fresh, mut spent, finished, gone) = (job(0), job(1), job(2), job(3));
+        spent.attempts = 3;
+        for job in [&fresh, &spent, &finished] {
+            queued(&dirs, job);
+        }
+        dirs.queue.push(&gone).unwrap();
+        analyses.insert(
+            finished.analysis_id,
+            "acme".to_string(),
+            finished.filename.clone(),
+            finished.upload_time,
+        );
+
+        let recovery = claim(&dirs.queue, &dirs.videos, &analyses, 3).unwrap();
+        let ids = |jobs: &[QueuedJob]| jobs.iter().map(|job| job.analysis_id).collect::<Vec<_>>();
+        assert_eq!(ids(&recovery.resume), [fresh.analysis_id]);
+        assert_eq!(ids(&recovery.exhausted), [spent.analysis_id]);
+
+        // The new attempt is counted before the job runs, so a crash counts too
+        let requeued = dirs.queue.get(fresh.analysis_id).unwrap().unwrap();
+        assert_eq!(requeued.attempts, 2);
+        assert!(dirs.queue.get(finished.analysis_id).unwrap().is_none());
+        assert!(dirs.queue.get(gone.analysis_id).unwrap().is_none());
+        assert_eq!(
+            dirs.queue.get(spent.analysis_id).unwrap().unwrap().attempts,
+            3
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_settle_stores_before_notifying() {
+        let dirs = dirs();
+        let analyses = AnalysisStore::new();
+        let webhooks = Arc::new(Webhooks::new(1, true).unwrap());
+        let job = job(0);
+        queued(&dirs, &job);
+        analyses.insert(
+            job.analysis_id,
+            "acme".to_string(),
+            job.filename.clone(),
+            job.upload_time,
+        );
+        webhooks.set_callback(job.analysis_id, "http://127.0.0.1:9/hook".to_string());
+
+        let status = AnalysisStatus::Failed {
+            error: "Model unavailable".to_string(),
+            code: Some("model_unavailable".to_string()),
+            timestamp: SystemTime::now(),
+        };
+        settle(
+            &analyses,
+            &webhooks,
+            &dirs.queue,
+            "acme",
+            job.analysis_id,
+            status,
+        );
+
+        // The event is built from the stored status, so it is final by then
+        let deliveries = webhooks.deliveries(Some("acme"), None);
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].payload["status"], "failed");
+        let stored = analyses.get(&job.analysis_id, None).unwrap();
+        assert_eq!(status_name(&stored), "failed");
+        assert!(dirs.queue.get(job.analysis_id).unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_wait_or_abort() {
+        let jobs = Arc::new(DashMap::new());
+        let (quick, slow) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut tasks = Vec::new();
+        for (analysis_id, runs_for) in [(quick, 10), (slow, 60_000)] {
+            let finished = {
+                let jobs = Arc::clone(&jobs);
+                async move {
+                    tokio::time::sleep(Duration::from_millis(runs_for)).await;
+                    jobs.remove(&analysis_id);
+                }
+            };
+            let (task, abort) = future::abortable(finished);
+            jobs.insert(analysis_id, abort);
+            tasks.push(tokio::spawn(task));
+        }
+
+        let started = Instant::now();
+        let stopped = wait_or_abort(&jobs, started + Duration::from_millis(500)).await;
+        assert_eq!(stopped, [slow]);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(jobs.is_empty());
+        let results: Vec<bool> = future::join_all(tasks)
+            .await
+            .into_iter()
+            .map(|result| result.unwrap().is_ok())
+            .collect();
+        assert_eq!(results, [true, false]);
+
+        // Nothing running returns at once, even with time left
+        let stopped = wait_or_abort(&jobs, Instant::now() + Duration::from_secs(60)).await;
+        assert!(stopped.is_empty());
+    }
+
+    #[test]
+    fn test_release_attempts() {
+        let dirs = dirs();
+        let (stopped, dequeued) = (job(0), job(1));
+        queued(&dirs, &stopped);
+
+        release_attempts(&dirs.queue, &[stopped.analysis_id, dequeued.analysis_id]);
+        assert_eq!(
+            dirs.queue
+                .get(stopped.analysis_id)
+                .unwrap()
+                .unwrap()
+                .attempts,
+            0
+        );
+        assert!(dirs.queue.get(dequeued.analysis_id).unwrap().is_none());
+    }
+}
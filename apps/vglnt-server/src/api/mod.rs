@@ -1,22 +1,82 @@
-use crate::types::AnalysisStatus;
+use crate::video::clip::Clipper;
+use crate::video::render::{RenderOptions, RenderStatus, Renderer};
+use crate::video::store::VideoStore;
 use crate::{llm, lstm, video};
 use dashmap::DashMap;
-use std::sync::Arc;
+use futures::future::AbortHandle;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 use uuid::Uuid;
 
+pub mod auth;
 pub mod handlers;
+pub mod health;
+pub mod jobs;
+pub mod queue;
+pub mod quota;
+pub mod request_id;
 pub mod routes;
+pub mod store;
+pub mod webhook;
+
+/// Largest upload accepted, across all multipart fields, unless overridden.
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 2 * 1024 * 1024 * 1024;
 
 pub struct AppState {
     video_analyzer: Arc<video::VideoAnalyzer>,
-    analysis_cache: Arc<DashMap<Uuid, AnalysisStatus>>,
+    analyses: Arc<store::AnalysisStore>,
+    videos: Arc<VideoStore>,
+    renderer: Arc<Renderer>,
+    renders: Arc<DashMap<(Uuid, RenderOptions), RenderStatus>>,
+    clipper: Arc<Clipper>,
+    max_upload_bytes: u64,
+    authenticator: auth::Authenticator,
+    quotas: Arc<quota::QuotaTracker>,
+    webhooks: Arc<webhook::Webhooks>,
+    /// Running analyses, removed by whichever of the task and a delete gets there first.
+    jobs: Arc<DashMap<Uuid, AbortHandle>>,
+    min_free_bytes: u64,
+    queue: queue::JobQueue,
+    /// The main runtime, which outlives the HTTP workers, for analysis jobs.
+    runtime: tokio::runtime::Handle,
+    /// When shutdown began, if it has.
+    shutdown_started: OnceLock<Instant>,
 }
 
 impl AppState {
     pub fn new() -> anyhow::Result<Self> {
         Ok(Self {
             video_analyzer: Arc::new(video::VideoAnalyzer::new()?),
-            analysis_cache: Arc::new(DashMap::new()),
+            analyses: Arc::new(store::AnalysisStore::from_env()?),
+            videos: Arc::new(VideoStore::from_env()?),
+            renderer: Arc::new(Renderer::from_env()?),
+            renders: Arc::new(DashMap::new()),
+            clipper: Arc::new(Clipper::from_env()?),
+            max_upload_bytes: std::env::var("VGLNT_MAX_UPLOAD_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES),
+            authenticator: auth::Authenticator::from_env()?,
+            quotas: Arc::new(quota::QuotaTracker::new()),
+            webhooks: Arc::new(webhook::Webhooks::from_env()?),
+            jobs: Arc::new(DashMap::new()),
+            min_free_bytes: health::min_free_bytes_from_env(),
+            queue: queue::JobQueue::from_env()?,
+            runtime: tokio::runtime::Handle::current(),
+            shutdown_started: OnceLock::new(),
         })
     }
+
+    /// Stops accepting uploads; running analyses carry on.
+    pub fn begin_shutdown(&self) {
+        self.shutdown_started.get_or_init(Instant::now);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown_started.get().is_some()
+    }
+
+    pub fn shutdown_started(&self) -> Option<Instant> {
+        self.shutdown_started.get().copied()
+    }
 }
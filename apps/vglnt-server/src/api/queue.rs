@@ -0,0 +1,176 @@
+//! Accepted analyses that have not finished, one JSON manifest per job on
+//! disk, so a restart or an interrupted shutdown re-runs them instead of
+//! losing them. The uploaded video itself stays in the `VideoStore`.
+
+use super::auth::Principal;
+use crate::types::Telemetry;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::warn;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedJob {
+    pub analysis_id: Uuid,
+    /// The uploader, whose limits apply when the job is resumed.
+    pub principal: Principal,
+    pub filename: String,
+    pub upload_time: SystemTime,
+    pub telemetry: Option<Telemetry>,
+    pub callback_url: Option<String>,
+    /// Times the job has been started without finishing or being stopped
+    /// at shutdown, so one that crashes the process is eventually dropped.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+pub struct JobQueue {
+    dir: PathBuf,
+}
+
+impl JobQueue {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create queue directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Reads `VGLNT_QUEUE_DIR`, defaulting to a directory under the system temp dir.
+    pub fn from_env() -> Result<Self> {
+        let dir = std::env::var("VGLNT_QUEUE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("vglnt").join("queue"));
+        Self::new(dir)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, analysis_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", analysis_id))
+    }
+
+    /// Writes the manifest durably, replacing any earlier one of the job.
+    pub fn push(&self, job: &QueuedJob) -> Result<()> {
+        let path = self.path(job.analysis_id);
+        write_durably(&path, &serde_json::to_vec(job)?)
+            .with_context(|| format!("Failed to queue {}", path.display()))
+    }
+
+    pub fn get(&self, analysis_id: Uuid) -> Result<Option<QueuedJob>> {
+        match std::fs::read(self.path(analysis_id)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn remove(&self, analysis_id: Uuid) -> std::io::Result<()> {
+        match std::fs::remove_file(self.path(analysis_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Every queued job, oldest upload first. Unreadable manifests are
+    /// skipped with a warning rather than blocking the rest.
+    pub fn pending(&self) -> Result<Vec<QueuedJob>> {
+        let mut jobs = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            let job = std::fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| Ok(serde_json::from_slice::<QueuedJob>(&data)?));
+            match job {
+                Ok(job) => jobs.push(job),
+                Err(e) => warn!("Skipping queued job {}: {:#}", path.display(), e),
+            }
+        }
+        jobs.sort_by_key(|job| job.upload_time);
+        Ok(jobs)
+    }
+
+    /// Makes the queue's renames and removals durable.
+    pub fn sync(&self) -> std::io::Result<()> {
+        File::open(&self.dir)?.sync_all()
+    }
+}
+
+/// Writes `data` to a partial file, syncs it, then renames it over `path`,
+/// so readers see either the old contents or all of the new.
+pub(super) fn write_durably(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let partial = path.with_extension("partial");
+    let mut file = File::create(&partial)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&partial, path)
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+    use crate::api::auth::Role;
+    use crate::api::quota::Limits;
+    use std::time::Duration;
+
+    /// A first attempt by tenant `acme`, uploaded `seconds` after the epoch.
+    pub fn job(seconds: u64) -> QueuedJob {
+        QueuedJob {
+            analysis_id: Uuid::new_v4(),
+            principal: Principal {
+                tenant: "acme".to_string(),
+                role: Role::Member,
+                limits: Limits {
+                    concurrent_jobs: Some(2),
+                    ..Default::default()
+                },
+            },
+            filename: "drive.mp4".to_string(),
+            upload_time: SystemTime::UNIX_EPOCH + Duration::from_secs(seconds),
+            telemetry: None,
+            callback_url: Some("https://example.com/hook".to_string()),
+            attempts: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::job;
+    use super::*;
+
+    #[test]
+    fn test_queue_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = JobQueue::new(dir.path().to_path_buf()).unwrap();
+        let (late, early, done) = (job(120), job(60), job(0));
+        for job in [&late, &early, &done] {
+            queue.push(job).unwrap();
+        }
+        queue.remove(done.analysis_id).unwrap();
+        queue.remove(done.analysis_id).unwrap();
+        std::fs::write(dir.path().join("broken.json"), b"{").unwrap();
+        std::fs::write(dir.path().join("stray.partial"), b"{}").unwrap();
+        queue.sync().unwrap();
+
+        let pending = queue.pending().unwrap();
+        let ids: Vec<Uuid> = pending.iter().map(|job| job.analysis_id).collect();
+        assert_eq!(ids, [early.analysis_id, late.analysis_id]);
+        assert_eq!(pending[0].callback_url, early.callback_url);
+        assert_eq!(pending[0].principal, early.principal);
+
+        let mut retried = late.clone();
+        retried.attempts = 2;
+        queue.push(&retried).unwrap();
+        assert_eq!(queue.get(late.analysis_id).unwrap().unwrap().attempts, 2);
+        assert!(queue.get(done.analysis_id).unwrap().is_none());
+    }
+}
@@ -0,0 +1,388 @@
+//! Per-tenant usage limits: concurrent analyses, uploaded bytes per UTC day,
+//! model frames per UTC month and requests per minute. Limits are set per API
+//! key, but usage is counted per tenant: every key of a tenant draws on the
+//! same counters, each checked against its own limits. Counters live in
+//! memory and reset when their window rolls over.
+
+use super::auth::Principal;
+use crate::error::AppError;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long to suggest waiting when every job slot is taken.
+const JOB_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Limits of one API key, applied to its tenant's usage; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    pub concurrent_jobs: Option<u32>,
+    pub upload_bytes_per_day: Option<u64>,
+    pub frames_per_month: Option<u64>,
+    pub requests_per_minute: Option<u64>,
+}
+
+impl Limits {
+    /// Reads the defaults from `VGLNT_QUOTA_CONCURRENT_JOBS`,
+    /// `VGLNT_QUOTA_UPLOAD_BYTES_PER_DAY`, `VGLNT_QUOTA_FRAMES_PER_MONTH` and
+    /// `VGLNT_QUOTA_REQUESTS_PER_MINUTE`.
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+        Self {
+            concurrent_jobs: var("VGLNT_QUOTA_CONCURRENT_JOBS"),
+            upload_bytes_per_day: var("VGLNT_QUOTA_UPLOAD_BYTES_PER_DAY"),
+            frames_per_month: var("VGLNT_QUOTA_FRAMES_PER_MONTH"),
+            requests_per_minute: var("VGLNT_QUOTA_REQUESTS_PER_MINUTE"),
+        }
+    }
+
+    /// These limits, with unset ones taken from `defaults`.
+    pub fn or(self, defaults: Limits) -> Self {
+        Self {
+            concurrent_jobs: self.concurrent_jobs.or(defaults.concurrent_jobs),
+            upload_bytes_per_day: self.upload_bytes_per_day.or(defaults.upload_bytes_per_day),
+            frames_per_month: self.frames_per_month.or(defaults.frames_per_month),
+            requests_per_minute: self.requests_per_minute.or(defaults.requests_per_minute),
+        }
+    }
+}
+
+/// A fixed window of time: its index and how long until the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Window {
+    index: i64,
+    resets_in: Duration,
+}
+
+fn minute(now: DateTime<Utc>) -> Window {
+    let seconds = now.timestamp();
+    Window {
+        index: seconds.div_euclid(60),
+        resets_in: Duration::from_secs(60 - seconds.rem_euclid(60) as u64),
+    }
+}
+
+fn day(now: DateTime<Utc>) -> Window {
+    let seconds = now.timestamp();
+    Window {
+        index: seconds.div_euclid(86_400),
+        resets_in: Duration::from_secs(86_400 - seconds.rem_euclid(86_400) as u64),
+    }
+}
+
+fn month(now: DateTime<Utc>) -> Window {
+    let (year, month) = match now.month() {
+        12 => (now.year() + 1, 1),
+        month => (now.year(), month + 1),
+    };
+    let next = NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map_or(now, |start| start.and_utc());
+    Window {
+        index: now.year() as i64 * 12 + now.month0() as i64,
+        resets_in: (next - now).to_std().unwrap_or_default(),
+    }
+}
+
+/// A count that restarts at zero in each new window.
+#[derive(Debug, Default)]
+struct Counter {
+    window: i64,
+    count: u64,
+}
+
+impl Counter {
+    fn current(&mut self, window: Window) -> &mut u64 {
+        if self.window != window.index {
+            self.window = window.index;
+            self.count = 0;
+        }
+        &mut self.count
+    }
+}
+
+#[derive(Debug, Default)]
+struct TenantUsage {
+    active_jobs: u32,
+    uploaded_bytes: Counter,
+    frames: Counter,
+    requests: Counter,
+}
+
+/// What a tenant has used in the current windows, with the caller's limits.
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub tenant: String,
+    pub active_jobs: u32,
+    pub uploaded_bytes_today: u64,
+    pub frames_this_month: u64,
+    pub requests_this_minute: u64,
+    pub limits: Limits,
+}
+
+fn exceeded(limit: &str, message: String, retry_after: Duration) -> AppError {
+    AppError::QuotaExceeded {
+        message,
+        retry_after,
+    }
+    .with_details(json!({ "limit": limit }))
+}
+
+#[derive(Default)]
+pub struct QuotaTracker {
+    usage: DashMap<String, TenantUsage>,
+}
+
+impl QuotaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts one request against the caller's per-minute limit.
+    pub fn check_request(&self, principal: &Principal) -> Result<(), AppError> {
+        self.check_request_at(principal, Utc::now())
+    }
+
+    fn check_request_at(&self, principal: &Principal, now: DateTime<Utc>) -> Result<(), AppError> {
+        let window = minute(now);
+        let mut usage = self.usage.entry(principal.tenant.clone()).or_default();
+        let requests = usage.requests.current(window);
+        if let Some(limit) = principal.limits.requests_per_minute {
+            if *requests >= limit {
+                return Err(exceeded(
+                    "requests_per_minute",
+                    format!("Rate limit of {} requests per minute reached", limit),
+                    window.resets_in,
+                ));
+            }
+        }
+        *requests += 1;
+        Ok(())
+    }
+
+    /// Takes a job slot for a new upload, refusing it while the tenant is at
+    /// its concurrency limit or has used up its daily bytes or monthly frames.
+    pub fn start_job(self: &Arc<Self>, principal: &Principal) -> Result<JobPermit, AppError> {
+        let now = Utc::now();
+        let limits = principal.limits;
+        let mut usage = self.usage.entry(principal.tenant.clone()).or_default();
+
+        if let Some(limit) = limits.concurrent_jobs {
+            if usage.active_jobs >= limit {
+                return Err(exceeded(
+                    "concurrent_jobs",
+                    format!("At most {} analyses may run at once", limit),
+                    JOB_RETRY_AFTER,
+                ));
+            }
+        }
+        let today = day(now);
+        if let Some(limit) = limits.upload_bytes_per_day {
+            if *usage.uploaded_bytes.current(today) >= limit {
+                return Err(exceeded(
+                    "upload_bytes_per_day",
+                    format!("Daily upload quota of {} bytes used up", limit),
+                    today.resets_in,
+                ));
+            }
+        }
+        let this_month = month(now);
+        if let Some(limit) = limits.frames_per_month {
+            if *usage.frames.current(this_month) >= limit {
+                return Err(exceeded(
+                    "frames_per_month",
+                    format!("Monthly quota of {} analyzed frames used up", limit),
+                    this_month.resets_in,
+                ));
+            }
+        }
+
+        usage.active_jobs += 1;
+        Ok(JobPermit {
+            tracker: Arc::clone(self),
+            principal: principal.clone(),
+        })
+    }
+
+    /// Takes a job slot for an analysis accepted before a restart, unless the
+    /// tenant is at its concurrency limit. Only that limit applies: the
+    /// upload was counted when it was accepted.
+    pub fn resume_job(self: &Arc<Self>, principal: &Principal) -> Option<JobPermit> {
+        let mut usage = self.usage.entry(principal.tenant.clone()).or_default();
+        if principal
+            .limits
+            .concurrent_jobs
+            .is_some_and(|limit| usage.active_jobs >= limit)
+        {
+            return None;
+        }
+        usage.active_jobs += 1;
+        Some(JobPermit {
+            tracker: Arc::clone(self),
+            principal: principal.clone(),
+        })
+    }
+
+    pub fn usage(&self, principal: &Principal) -> Usage {
+        let now = Utc::now();
+        let mut usage = self.usage.entry(principal.tenant.clone()).or_default();
+        Usage {
+            tenant: principal.tenant.clone(),
+            active_jobs: usage.active_jobs,
+            uploaded_bytes_today: *usage.uploaded_bytes.current(day(now)),
+            frames_this_month: *usage.frames.current(month(now)),
+            requests_this_minute: *usage.requests.current(minute(now)),
+            limits: principal.limits,
+        }
+    }
+}
+
+/// A running job slot of a tenant, released when dropped.
+pub struct JobPermit {
+    tracker: Arc<QuotaTracker>,
+    principal: Principal,
+}
+
+impl JobPermit {
+    /// Fails once an upload of `bytes` so far would exceed the daily quota,
+    /// so oversized uploads are cut off early. Counts nothing.
+    pub fn check_upload(&self, bytes: u64) -> Result<(), AppError> {
+        self.add_upload(bytes, false)
+    }
+
+    /// Counts a finished upload of `bytes` against the daily quota.
+    pub fn record_upload(&self, bytes: u64) -> Result<(), AppError> {
+        self.add_upload(bytes, true)
+    }
+
+    fn add_upload(&self, bytes: u64, record: bool) -> Result<(), AppError> {
+        let today = day(Utc::now());
+        let mut usage = self
+            .tracker
+            .usage
+            .entry(self.principal.tenant.clone())
+            .or_default();
+        let uploaded = usage.uploaded_bytes.current(today);
+        if let Some(limit) = self.principal.limits.upload_bytes_per_day {
+            if uploaded.saturating_add(bytes) > limit {
+                return Err(exceeded(
+                    "upload_bytes_per_day",
+                    format!(
+                        "Upload would exceed the daily quota of {} bytes ({} left)",
+                        limit,
+                        limit.saturating_sub(*uploaded)
+                    ),
+                    today.resets_in,
+                ));
+            }
+        }
+        if record {
+            *uploaded += bytes;
+        }
+        Ok(())
+    }
+
+    /// Counts frames sent to the vision model against the monthly quota,
+    /// whether or not the model could analyse them.
+    pub fn record_frames(&self, frames: u64) {
+        let this_month = month(Utc::now());
+        let mut usage = self
+            .tracker
+            .usage
+            .entry(self.principal.tenant.clone())
+            .or_default();
+        *usage.frames.current(this_month) += frames;
+    }
+}
+
+impl Drop for JobPermit {
+    fn drop(&mut self) {
+        if let Some(mut usage) = self.tracker.usage.get_mut(&self.principal.tenant) {
+            usage.active_jobs = usage.active_jobs.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::auth::Role;
+    use chrono::TimeZone;
+
+    fn principal(limits: Limits) -> Principal {
+        Principal {
+            tenant: "acme".to_string(),
+            role: Role::Member,
+            limits,
+        }
+    }
+
+    #[test]
+    fn test_windows() {
+        let now = Utc.with_ymd_and_hms(2024, 12, 31, 23, 59, 30).unwrap();
+        assert_eq!(minute(now).resets_in, Duration::from_secs(30));
+        assert_eq!(day(now).resets_in, Duration::from_secs(30));
+        assert_eq!(month(now).resets_in, Duration::from_secs(30));
+
+        let next = now + chrono::Duration::seconds(30);
+        assert_ne!(month(next).index, month(now).index);
+        assert_eq!(month(next).resets_in, Duration::from_secs(31 * 86_400));
+    }
+
+    #[test]
+    fn test_job_and_upload_limits() {
+        let tracker = Arc::new(QuotaTracker::new());
+        let principal = principal(Limits {
+            concurrent_jobs: Some(1),
+            upload_bytes_per_day: Some(1_000),
+            ..Default::default()
+        });
+
+        let permit = tracker.start_job(&principal).unwrap();
+        let error = tracker.start_job(&principal).err().unwrap();
+        assert_eq!(error.code(), "quota_exceeded");
+
+        assert!(permit.check_upload(1_000).is_ok());
+        assert!(permit.check_upload(1_001).is_err());
+        permit.record_upload(600).unwrap();
+        assert!(permit.record_upload(600).is_err());
+        assert_eq!(tracker.usage(&principal).uploaded_bytes_today, 600);
+
+        drop(permit);
+        assert_eq!(tracker.usage(&principal).active_jobs, 0);
+        let permit = tracker.start_job(&principal).unwrap();
+        permit.record_upload(400).unwrap();
+        drop(permit);
+        let error = tracker.start_job(&principal).err().unwrap();
+        assert_eq!(error.code(), "quota_exceeded");
+
+        // Resumed jobs skip the spent upload quota but not the job limit
+        let resumed = tracker.resume_job(&principal).unwrap();
+        assert!(tracker.resume_job(&principal).is_none());
+        drop(resumed);
+        assert!(tracker.resume_job(&principal).is_some());
+    }
+
+    #[test]
+    fn test_request_rate() {
+        let tracker = QuotaTracker::new();
+        let principal = principal(Limits {
+            requests_per_minute: Some(2),
+            ..Default::default()
+        });
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 15).unwrap();
+        assert!(tracker.check_request_at(&principal, now).is_ok());
+        assert!(tracker.check_request_at(&principal, now).is_ok());
+        let error = tracker.check_request_at(&principal, now).err().unwrap();
+        assert_eq!(error.code(), "quota_exceeded");
+
+        // The next minute starts afresh
+        let later = now + chrono::Duration::seconds(45);
+        assert!(tracker.check_request_at(&principal, later).is_ok());
+    }
+}
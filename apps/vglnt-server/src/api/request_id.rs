@@ -0,0 +1,82 @@
+//! Request ids, taken from the `X-Request-Id` header or generated, echoed on
+//! the response and readable by error bodies while the request is handled.
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::HttpRequest;
+use std::future::Future;
+use uuid::Uuid;
+
+pub const HEADER: &str = "x-request-id";
+/// Longer client-supplied ids are replaced rather than echoed.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request being handled on this task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+pub fn from_request(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Runs the rest of the service chain for `request` with `request_id` set,
+/// then adds it to the response. Errors are turned into their responses
+/// here, so those carry the id too.
+pub async fn scope<B>(
+    request: HttpRequest,
+    request_id: String,
+    response: impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let mut response = match REQUEST_ID.scope(request_id.clone(), response).await {
+        Ok(response) => response.map_into_left_body(),
+        Err(e) => ServiceResponse::new(request, e.error_response()).map_into_right_body(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(HEADER), value);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use actix_web::HttpResponse;
+
+    #[actix_web::test]
+    async fn test_scope() {
+        let request = TestRequest::default().to_http_request();
+        let handled = async {
+            assert_eq!(current().as_deref(), Some("req-1"));
+            Ok::<_, actix_web::Error>(ServiceResponse::new(
+                TestRequest::default().to_http_request(),
+                HttpResponse::Ok().finish(),
+            ))
+        };
+        let response = scope(request.clone(), "req-1".to_string(), handled)
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get(HEADER).unwrap(), "req-1");
+
+        let failed = async { Err(actix_web::error::ErrorUnauthorized("No key")) };
+        let response = scope::<()>(request, "req-2".to_string(), failed)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers().get(HEADER).unwrap(), "req-2");
+        assert!(current().is_none());
+    }
+}
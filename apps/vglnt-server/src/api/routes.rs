@@ -1,15 +1,49 @@
-use super::handlers;
+use super::{auth, handlers};
+use actix_web::middleware::from_fn;
 use actix_web::web;
 
 pub fn video_routes() -> actix_web::Scope {
     web::scope("/api/v1/video")
+        .wrap(from_fn(auth::authenticate))
         .route("/upload", web::post().to(handlers::upload_video))
         .route("/{id}/status", web::get().to(handlers::get_analysis_status))
         .route("/{id}/result", web::get().to(handlers::get_analysis_result))
+        .route("/{id}/render", web::get().to(handlers::render_video))
+        .route("/{id}/events/{event_idx}/clip", web::get().to(handlers::event_clip))
+        .route("/{id}/frames/{frame_number}/thumbnail", web::get().to(handlers::frame_thumbnail))
 }
 
 pub fn analysis_routes() -> actix_web::Scope {
     web::scope("/api/v1/analysis")
+        .wrap(from_fn(auth::authenticate))
         .route("/list", web::get().to(handlers::list_analyses))
         .route("/{id}", web::delete().to(handlers::delete_analysis))
 }
+
+pub fn usage_routes() -> actix_web::Scope {
+    web::scope("/api/v1/usage")
+        .wrap(from_fn(auth::authenticate))
+        .route("", web::get().to(handlers::get_usage))
+}
+
+pub fn webhook_routes() -> actix_web::Scope {
+    web::scope("/api/v1/webhooks")
+        .wrap(from_fn(auth::authenticate))
+        .route("", web::get().to(handlers::get_webhook))
+        .route("", web::put().to(handlers::register_webhook))
+        .route("", web::delete().to(handlers::unregister_webhook))
+        .route("/deliveries", web::get().to(handlers::list_deliveries))
+        .route("/deliveries/{id}/redeliver", web::post().to(handlers::redeliver_webhook))
+}
+
+pub fn metrics_route() -> actix_web::Resource {
+    web::resource("/metrics").route(web::get().to(handlers::metrics))
+}
+
+pub fn healthz_route() -> actix_web::Resource {
+    web::resource("/healthz").route(web::get().to(handlers::healthz))
+}
+
+pub fn readyz_route() -> actix_web::Resource {
+    web::resource("/readyz").route(web::get().to(handlers::readyz))
+}
@@ -0,0 +1,643 @@
+//! Analyses by id, with ordered indexes on upload time and score, overall and
+//! per tenant, so listings scan only the requested tenant's analyses in the
+//! requested range and order; the other filters apply while scanning.
+//! Finished analyses are also written to disk, one JSON file each, and
+//! reloaded on start.
+
+use super::queue::write_durably;
+use crate::types::{AnalysisListResponse, AnalysisStatus, AnalysisSummaryItem};
+use anyhow::{Context, Result};
+use dashmap::mapref::one::MappedRef;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+use tracing::warn;
+use uuid::Uuid;
+
+pub const STATUSES: [&str; 4] = ["queued", "processing", "complete", "failed"];
+
+#[derive(Serialize, Deserialize)]
+pub struct StoredAnalysis {
+    pub tenant: String,
+    pub filename: String,
+    pub upload_time: SystemTime,
+    pub status: AnalysisStatus,
+}
+
+impl StoredAnalysis {
+    fn score(&self) -> Option<f32> {
+        score_of(&self.status)
+    }
+}
+
+fn score_of(status: &AnalysisStatus) -> Option<f32> {
+    match status {
+        AnalysisStatus::Complete { analysis, .. } => Some(analysis.summary.overall_score),
+        _ => None,
+    }
+}
+
+pub fn status_name(status: &AnalysisStatus) -> &'static str {
+    match status {
+        AnalysisStatus::Queued => STATUSES[0],
+        AnalysisStatus::Processing { .. } => STATUSES[1],
+        AnalysisStatus::Complete { .. } => STATUSES[2],
+        AnalysisStatus::Failed { .. } => STATUSES[3],
+    }
+}
+
+/// Whether an analysis has stopped for good, so its status is worth keeping.
+fn is_final(status: &AnalysisStatus) -> bool {
+    !matches!(
+        status,
+        AnalysisStatus::Queued | AnalysisStatus::Processing { .. }
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    UploadTime,
+    Score,
+    Filename,
+}
+
+/// A validated listing request; every filter is optional.
+#[derive(Debug, Clone)]
+pub struct ListQuery {
+    /// Only analyses of this tenant; every tenant when `None`.
+    pub tenant: Option<String>,
+    /// 1-based.
+    pub page: usize,
+    pub per_page: usize,
+    pub status: Option<&'static str>,
+    pub uploaded_after: Option<SystemTime>,
+    pub uploaded_before: Option<SystemTime>,
+    /// Score filters match only completed analyses.
+    pub min_score: Option<f32>,
+    pub max_score: Option<f32>,
+    /// Case-insensitive substring of the filename.
+    pub filename: Option<String>,
+    pub sort: SortKey,
+    pub descending: bool,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        Self {
+            tenant: None,
+            page: 1,
+            per_page: 20,
+            status: None,
+            uploaded_after: None,
+            uploaded_before: None,
+            min_score: None,
+            max_score: None,
+            filename: None,
+            sort: SortKey::UploadTime,
+            descending: true,
+        }
+    }
+}
+
+impl ListQuery {
+    fn matches(&self, entry: &StoredAnalysis) -> bool {
+        // NaN fails every comparison, so unscored analyses never match a score filter
+        let score = entry.score().unwrap_or(f32::NAN);
+        let filename = entry.filename.to_lowercase();
+        let within = |bound: Option<SystemTime>, keep: fn(&SystemTime, &SystemTime) -> bool| {
+            bound.map_or(true, |t| keep(&entry.upload_time, &t))
+        };
+
+        owned_by(entry, self.tenant.as_deref())
+            && self
+                .status
+                .map_or(true, |s| status_name(&entry.status) == s)
+            && within(self.uploaded_after, SystemTime::ge)
+            && within(self.uploaded_before, SystemTime::le)
+            && self.min_score.map_or(true, |min| score >= min)
+            && self.max_score.map_or(true, |max| score <= max)
+            && self
+                .filename
+                .as_ref()
+                .map_or(true, |needle| filename.contains(&needle.to_lowercase()))
+    }
+}
+
+fn owned_by(entry: &StoredAnalysis, tenant: Option<&str>) -> bool {
+    tenant.map_or(true, |tenant| entry.tenant == tenant)
+}
+
+/// `f32` ordered by `total_cmp`, for the score index.
+#[derive(Debug, Clone, Copy)]
+struct Score(f32);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+#[derive(Default)]
+struct Index {
+    by_upload_time: BTreeSet<(SystemTime, Uuid)>,
+    /// Completed analyses only.
+    by_score: BTreeSet<(Score, Uuid)>,
+}
+
+#[derive(Default)]
+struct Indexes {
+    all: Index,
+    by_tenant: HashMap<String, Index>,
+}
+
+impl Indexes {
+    /// The index over every analysis and the one over `tenant`'s.
+    fn both(&mut self, tenant: &str) -> [&mut Index; 2] {
+        let own = self.by_tenant.entry(tenant.to_string()).or_default();
+        [&mut self.all, own]
+    }
+
+    /// The index to list from: every tenant's when `None`, and `None` for a
+    /// tenant without analyses.
+    fn scope(&self, tenant: Option<&str>) -> Option<&Index> {
+        match tenant {
+            Some(tenant) => self.by_tenant.get(tenant),
+            None => Some(&self.all),
+        }
+    }
+}
+
+/// Writers take the index lock before touching an entry and readers do the
+/// same, so the indexes always agree with the entries.
+#[derive(Default)]
+pub struct AnalysisStore {
+    entries: DashMap<Uuid, StoredAnalysis>,
+    indexes: RwLock<Indexes>,
+    /// Where finished analyses are kept; memory only when `None`.
+    dir: Option<PathBuf>,
+}
+
+impl AnalysisStore {
+    /// A store that keeps nothing on disk.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store persisted in `dir`, loaded with the analyses already there.
+    /// Unreadable files are skipped with a warning.
+    pub fn open(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create results directory {}", dir.display()))?;
+        let store = Self {
+            dir: Some(dir.clone()),
+            ..Self::default()
+        };
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| Uuid::parse_str(stem).ok());
+            let Some(id) = id.filter(|_| path.extension().is_some_and(|ext| ext == "json")) else {
+                continue;
+            };
+            let stored = std::fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| Ok(serde_json::from_slice::<StoredAnalysis>(&data)?));
+            match stored {
+                Ok(stored) => store.index(id, stored),
+                Err(e) => warn!("Skipping stored analysis {}: {:#}", path.display(), e),
+            }
+        }
+        Ok(store)
+    }
+
+    /// Reads `VGLNT_RESULTS_DIR`, defaulting to a directory under the system temp dir.
+    pub fn from_env() -> Result<Self> {
+        let dir = std::env::var("VGLNT_RESULTS_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("vglnt").join("results"));
+        Self::open(dir)
+    }
+
+    /// Where finished analyses are kept, if anywhere.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    fn path(&self, id: Uuid) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{}.json", id)))
+    }
+
+    fn index(&self, id: Uuid, entry: StoredAnalysis) {
+        let mut indexes = self.indexes.write().unwrap();
+        for index in indexes.both(&entry.tenant) {
+            index.by_upload_time.insert((entry.upload_time, id));
+            if let Some(score) = entry.score() {
+                index.by_score.insert((Score(score), id));
+            }
+        }
+        self.entries.insert(id, entry);
+    }
+
+    /// Records a new upload by `tenant` as queued.
+    pub fn insert(&self, id: Uuid, tenant: String, filename: String, upload_time: SystemTime) {
+        self.index(
+            id,
+            StoredAnalysis {
+                tenant,
+                filename,
+                upload_time,
+                status: AnalysisStatus::Queued,
+            },
+        );
+    }
+
+    /// Updates the status of an analysis; ignored if it has been removed.
+    /// Final statuses are written to disk before this returns.
+    pub fn set_status(&self, id: Uuid, status: AnalysisStatus) {
+        let mut indexes = self.indexes.write().unwrap();
+        let Some(mut entry) = self.entries.get_mut(&id) else {
+            return;
+        };
+        let (old, new) = (entry.score(), score_of(&status));
+        entry.status = status;
+        for index in indexes.both(&entry.tenant) {
+            if let Some(score) = old {
+                index.by_score.remove(&(Score(score), id));
+            }
+            if let Some(score) = new {
+                index.by_score.insert((Score(score), id));
+            }
+        }
+        if !is_final(&entry.status) {
+            return;
+        }
+        if let Some(path) = self.path(id) {
+            let written = serde_json::to_vec(&*entry)
+                .map_err(std::io::Error::from)
+                .and_then(|data| write_durably(&path, &data));
+            if let Err(e) = written {
+                warn!("Failed to store analysis {}: {}", id, e);
+            }
+        }
+    }
+
+    /// The status of an analysis, if it exists and belongs to `tenant`
+    /// (any tenant when `None`).
+    pub fn get(
+        &self,
+        id: &Uuid,
+        tenant: Option<&str>,
+    ) -> Option<MappedRef<'_, Uuid, StoredAnalysis, AnalysisStatus>> {
+        let entry = self.entries.get(id)?;
+        owned_by(&entry, tenant).then(|| entry.map(|e| &e.status))
+    }
+
+    pub fn remove(&self, id: &Uuid, tenant: Option<&str>) -> Option<StoredAnalysis> {
+        let mut indexes = self.indexes.write().unwrap();
+        let (_, entry) = self
+            .entries
+            .remove_if(id, |_, entry| owned_by(entry, tenant))?;
+        for index in indexes.both(&entry.tenant) {
+            index.by_upload_time.remove(&(entry.upload_time, *id));
+            if let Some(score) = entry.score() {
+                index.by_score.remove(&(Score(score), *id));
+            }
+        }
+        if indexes.by_tenant[&entry.tenant].by_upload_time.is_empty() {
+            indexes.by_tenant.remove(&entry.tenant);
+        }
+        if let Some(path) = self.path(*id) {
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    warn!("Failed to delete stored analysis {}: {}", id, e)
+                }
+                _ => {}
+            }
+        }
+        Some(entry)
+    }
+
+    /// Makes the stored analyses' renames and removals durable.
+    pub fn sync(&self) -> std::io::Result<()> {
+        match &self.dir {
+            Some(dir) => File::open(dir)?.sync_all(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn list(&self, query: &ListQuery) -> AnalysisListResponse {
+        let indexes = self.indexes.read().unwrap();
+        let empty = Index::default();
+        let index = indexes.scope(query.tenant.as_deref()).unwrap_or(&empty);
+        let ids = match query.sort {
+            SortKey::Score => {
+                let range = index.by_score.range((
+                    query.min_score.map_or(Bound::Unbounded, |s| {
+                        Bound::Included((Score(s), Uuid::nil()))
+                    }),
+                    query.max_score.map_or(Bound::Unbounded, |s| {
+                        Bound::Included((Score(s), Uuid::max()))
+                    }),
+                ));
+                let mut ids: Vec<Uuid> = ordered(range, query.descending).collect();
+                // Unscored analyses follow the scored ones in either direction
+                if query.min_score.is_none() && query.max_score.is_none() {
+                    let unscored = time_range(index, query, query.descending);
+                    ids.extend(
+                        unscored
+                            .filter(|id| self.entries.get(id).is_some_and(|e| e.score().is_none())),
+                    );
+                }
+                ids
+            }
+            SortKey::UploadTime => time_range(index, query, query.descending).collect(),
+            SortKey::Filename => time_range(index, query, false).collect(),
+        };
+
+        let mut matching: Vec<(Uuid, String)> = ids
+            .into_iter()
+            .filter_map(|id| {
+                let entry = self.entries.get(&id)?;
+                query
+                    .matches(&entry)
+                    .then(|| (id, entry.filename.to_lowercase()))
+            })
+            .collect();
+        if query.sort == SortKey::Filename {
+            matching.sort_by(|a, b| a.1.cmp(&b.1));
+            if query.descending {
+                matching.reverse();
+            }
+        }
+
+        let total_count = matching.len();
+        let analyses = matching
+            .into_iter()
+            .skip(query.page.saturating_sub(1) * query.per_page)
+            .take(query.per_page)
+            .filter_map(|(id, _)| {
+                let entry = self.entries.get(&id)?;
+                Some(AnalysisSummaryItem {
+                    id,
+                    tenant: entry.tenant.clone(),
+                    filename: entry.filename.clone(),
+                    status: status_name(&entry.status).to_string(),
+                    upload_time: entry.upload_time,
+                    overall_score: entry.score(),
+                })
+            })
+            .collect();
+
+        AnalysisListResponse {
+            analyses,
+            total_count,
+            page: query.page,
+            per_page: query.per_page,
+        }
+    }
+}
+
+/// Ids uploaded within the query's time range, in upload order.
+fn time_range<'a>(
+    index: &'a Index,
+    query: &ListQuery,
+    descending: bool,
+) -> Box<dyn Iterator<Item = Uuid> + 'a> {
+    let range = index.by_upload_time.range((
+        query
+            .uploaded_after
+            .map_or(Bound::Unbounded, |t| Bound::Included((t, Uuid::nil()))),
+        query
+            .uploaded_before
+            .map_or(Bound::Unbounded, |t| Bound::Included((t, Uuid::max()))),
+    ));
+    ordered(range, descending)
+}
+
+fn ordered<'a, K: 'a>(
+    range: impl DoubleEndedIterator<Item = &'a (K, Uuid)> + 'a,
+    descending: bool,
+) -> Box<dyn Iterator<Item = Uuid> + 'a> {
+    if descending {
+        Box::new(range.rev().map(|(_, id)| *id))
+    } else {
+        Box::new(range.map(|(_, id)| *id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::analysis;
+    use std::time::Duration;
+
+    /// Five uploads a minute apart, alternating between tenants `a` and `b`;
+    /// the first three complete with rising scores.
+    fn store() -> (AnalysisStore, Vec<Uuid>) {
+        let store = AnalysisStore::new();
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        for (idx, id) in ids.iter().enumerate() {
+            let upload_time = SystemTime::UNIX_EPOCH + Duration::from_secs(60 * idx as u64);
+            let tenant = if idx % 2 == 0 { "a" } else { "b" };
+            store.insert(
+                *id,
+                tenant.to_string(),
+                format!("drive-{}.mp4", idx),
+                upload_time,
+            );
+        }
+        for (idx, id) in ids.iter().take(3).enumerate() {
+            store.set_status(
+                *id,
+                AnalysisStatus::Complete {
+                    analysis: analysis(*id, 50.0 + 10.0 * idx as f32),
+                    completion_time: SystemTime::now(),
+                },
+            );
+        }
+        (store, ids)
+    }
+
+    fn listed(response: &AnalysisListResponse) -> Vec<Uuid> {
+        response.analyses.iter().map(|a| a.id).collect()
+    }
+
+    #[test]
+    fn test_newest_first_with_pagination() {
+        let (store, ids) = store();
+        let query = ListQuery {
+            page: 2,
+            per_page: 2,
+            ..Default::default()
+        };
+        let response = store.list(&query);
+        assert_eq!(response.total_count, 5);
+        assert_eq!(listed(&response), [ids[2], ids[1]]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let (store, ids) = store();
+        let query = ListQuery {
+            status: Some("complete"),
+            min_score: Some(55.0),
+            ..Default::default()
+        };
+        assert_eq!(listed(&store.list(&query)), [ids[2], ids[1]]);
+
+        let query = ListQuery {
+            uploaded_after: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(60)),
+            uploaded_before: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(180)),
+            filename: Some("DRIVE-3".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(listed(&store.list(&query)), [ids[3]]);
+    }
+
+    #[test]
+    fn test_filter_by_processing() {
+        let (store, ids) = store();
+        store.set_status(
+            ids[3],
+            AnalysisStatus::Processing {
+                start_time: SystemTime::now(),
+                frames_processed: 4,
+                total_frames: 10,
+            },
+        );
+
+        let processing = ListQuery {
+            status: Some("processing"),
+            ..Default::default()
+        };
+        let response = store.list(&processing);
+        assert_eq!(listed(&response), [ids[3]]);
+        assert_eq!(response.analyses[0].status, "processing");
+        let queued = ListQuery {
+            status: Some("queued"),
+            ..Default::default()
+        };
+        assert_eq!(listed(&store.list(&queued)), [ids[4]]);
+    }
+
+    #[test]
+    fn test_sort_by_score_keeps_indexes_current() {
+        let (store, ids) = store();
+        store.set_status(
+            ids[0],
+            AnalysisStatus::Complete {
+                analysis: analysis(ids[0], 90.0),
+                completion_time: SystemTime::now(),
+            },
+        );
+        store.remove(&ids[4], None);
+
+        let query = ListQuery {
+            sort: SortKey::Score,
+            ..Default::default()
+        };
+        let response = store.list(&query);
+        assert_eq!(listed(&response), [ids[0], ids[2], ids[1], ids[3]]);
+        assert_eq!(response.analyses[0].overall_score, Some(90.0));
+    }
+
+    #[test]
+    fn test_tenant_scoping() {
+        let (store, ids) = store();
+        let query = ListQuery {
+            tenant: Some("b".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(listed(&store.list(&query)), [ids[3], ids[1]]);
+
+        assert!(store.get(&ids[0], Some("b")).is_none());
+        assert!(store.get(&ids[0], Some("a")).is_some());
+        assert!(store.remove(&ids[0], Some("b")).is_none());
+        assert!(store.get(&ids[0], None).is_some());
+
+        // A tenant's index follows its removals, down to none at all
+        store.remove(&ids[1], None);
+        store.remove(&ids[3], None);
+        assert!(store.list(&query).analyses.is_empty());
+        let query = ListQuery {
+            tenant: Some("a".to_string()),
+            sort: SortKey::Score,
+            ..Default::default()
+        };
+        let response = store.list(&query);
+        assert_eq!(listed(&response), [ids[2], ids[0], ids[4]]);
+        assert_eq!(response.analyses[0].status, "complete");
+        assert_eq!(response.analyses[2].status, "queued");
+    }
+
+    #[test]
+    fn test_persists_finished_analyses() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AnalysisStore::open(dir.path().to_path_buf()).unwrap();
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        for (idx, id) in ids.iter().enumerate() {
+            let upload_time = SystemTime::UNIX_EPOCH + Duration::from_secs(idx as u64);
+            store.insert(*id, "a".to_string(), "drive.mp4".to_string(), upload_time);
+        }
+        store.set_status(
+            ids[0],
+            AnalysisStatus::Complete {
+                analysis: analysis(ids[0], 70.0),
+                completion_time: SystemTime::now(),
+            },
+        );
+        store.set_status(
+            ids[1],
+            AnalysisStatus::Failed {
+                error: "Model unavailable".to_string(),
+                code: Some("model_unavailable".to_string()),
+                timestamp: SystemTime::now(),
+            },
+        );
+        store.set_status(
+            ids[2],
+            AnalysisStatus::Processing {
+                start_time: SystemTime::now(),
+                frames_processed: 0,
+                total_frames: 10,
+            },
+        );
+        store.remove(&ids[1], None);
+        std::fs::write(dir.path().join(format!("{}.json", Uuid::new_v4())), b"{").unwrap();
+        store.sync().unwrap();
+
+        // Only finished analyses outlive the process, with their indexes rebuilt
+        let reopened = AnalysisStore::open(dir.path().to_path_buf()).unwrap();
+        let query = ListQuery {
+            sort: SortKey::Score,
+            ..Default::default()
+        };
+        assert_eq!(listed(&reopened.list(&query)), [ids[0]]);
+        assert!(reopened.get(&ids[0], Some("a")).is_some());
+        assert!(reopened.get(&ids[0], Some("b")).is_none());
+        assert!(reopened.get(&ids[1], None).is_none());
+        assert!(reopened.get(&ids[2], None).is_none());
+    }
+}
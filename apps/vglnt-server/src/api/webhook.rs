@@ -0,0 +1,810 @@
+//! Signed HTTP callbacks when an analysis completes, fails or is cancelled by
+//! a delete, sent to a URL given with the upload and to the tenant's
+//! registered endpoint, retried with exponential backoff and kept in a
+//! delivery log.
+//!
+//! Each POST carries `X-Vglnt-Signature: sha256=<hex>`, the HMAC-SHA256 of
+//! `<X-Vglnt-Timestamp>.<body>` under the tenant's webhook secret.
+//!
+//! Webhook hosts must resolve to public addresses, both when the URL is
+//! given and on every connection, so tenants cannot make the server call
+//! its model server, cloud metadata or other internal services. Set
+//! `VGLNT_WEBHOOK_ALLOW_PRIVATE=1` to allow internal receivers.
+
+use super::store::status_name;
+use crate::error::AppError;
+use crate::types::AnalysisStatus;
+use dashmap::mapref::one::RefMut;
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub const SIGNATURE_HEADER: &str = "x-vglnt-signature";
+pub const TIMESTAMP_HEADER: &str = "x-vglnt-timestamp";
+pub const EVENT_HEADER: &str = "x-vglnt-event";
+pub const DELIVERY_HEADER: &str = "x-vglnt-delivery";
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 6;
+const BASE_DELAY: Duration = Duration::from_secs(2);
+const MAX_DELAY: Duration = Duration::from_secs(300);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Deliveries kept per tenant; older ones are dropped from the log.
+const LOG_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Complete,
+    Failed,
+    /// Deleted while queued or running; the analysis has no status left.
+    Cancelled,
+}
+
+impl Event {
+    /// The event for a final status; `None` while the analysis is running.
+    pub fn for_status(status: &AnalysisStatus) -> Option<Self> {
+        match status {
+            AnalysisStatus::Complete { .. } => Some(Event::Complete),
+            AnalysisStatus::Failed { .. } => Some(Event::Failed),
+            AnalysisStatus::Queued | AnalysisStatus::Processing { .. } => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Complete => "analysis.complete",
+            Event::Failed => "analysis.failed",
+            Event::Cancelled => "analysis.cancelled",
+        }
+    }
+}
+
+/// A tenant's registered endpoint and the secret its deliveries are signed with.
+#[derive(Debug, Clone, Serialize)]
+pub struct Endpoint {
+    pub url: Option<String>,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attempt {
+    pub timestamp: SystemTime,
+    /// The response status, if the endpoint answered.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub analysis_id: Uuid,
+    pub event: &'static str,
+    pub url: String,
+    pub state: DeliveryState,
+    pub attempts: Vec<Attempt>,
+    pub created: SystemTime,
+    pub payload: Value,
+}
+
+pub struct Webhooks {
+    client: Client,
+    /// By tenant.
+    endpoints: DashMap<String, Endpoint>,
+    /// Per-upload callback URLs, until the analysis finishes.
+    callbacks: DashMap<Uuid, String>,
+    /// Newest first, by tenant.
+    deliveries: DashMap<String, VecDeque<Delivery>>,
+    max_attempts: u32,
+    /// Wait before the first retry, doubling after each.
+    retry_delay: Duration,
+    /// Whether webhooks may target loopback, private and link-local hosts.
+    allow_private: bool,
+}
+
+impl Webhooks {
+    /// Reads the attempts per delivery from `VGLNT_WEBHOOK_MAX_ATTEMPTS` and
+    /// whether internal hosts are allowed from `VGLNT_WEBHOOK_ALLOW_PRIVATE`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let max_attempts = std::env::var("VGLNT_WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|attempts| *attempts > 0)
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        let allow_private = std::env::var("VGLNT_WEBHOOK_ALLOW_PRIVATE").is_ok_and(|v| v == "1");
+        Self::new(max_attempts, allow_private)
+    }
+
+    pub fn new(max_attempts: u32, allow_private: bool) -> anyhow::Result<Self> {
+        // A redirect could point anywhere, so it counts as a failed attempt
+        let mut client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(Policy::none());
+        if !allow_private {
+            client = client.dns_resolver(Arc::new(PublicResolver));
+        }
+        Ok(Self {
+            client: client.build()?,
+            endpoints: DashMap::new(),
+            callbacks: DashMap::new(),
+            deliveries: DashMap::new(),
+            max_attempts,
+            retry_delay: BASE_DELAY,
+            allow_private,
+        })
+    }
+
+    /// Checks that `url` is an absolute HTTP(S) URL.
+    pub fn parse_url(url: &str) -> Result<String, AppError> {
+        match Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(parsed.into()),
+            _ => Err(AppError::InvalidInput(
+                "Webhook URL must be an absolute http or https URL".to_string(),
+            )),
+        }
+    }
+
+    /// Checks that `url` is an absolute HTTP(S) URL whose host resolves to
+    /// public addresses only.
+    pub async fn validate_url(&self, url: &str) -> Result<String, AppError> {
+        let url = Self::parse_url(url)?;
+        if self.allow_private {
+            return Ok(url);
+        }
+        let parsed = Url::parse(&url).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+        let host = parsed.host_str().unwrap_or_default();
+        let addrs: Vec<IpAddr> = match literal_ip(&parsed) {
+            Some(ip) => vec![ip],
+            None => tokio::net::lookup_host((host, parsed.port_or_known_default().unwrap_or(80)))
+                .await
+                .map_err(|_| {
+                    AppError::InvalidInput(format!("Cannot resolve webhook host {}", host))
+                })?
+                .map(|addr| addr.ip())
+                .collect(),
+        };
+        if addrs.is_empty() || !addrs.into_iter().all(is_public) {
+            return Err(AppError::InvalidInput(format!(
+                "Webhook host {} is not a public address",
+                host
+            )));
+        }
+        Ok(url)
+    }
+
+    /// The tenant's endpoint, creating its signing secret on first use.
+    pub fn endpoint(&self, tenant: &str) -> Endpoint {
+        self.endpoint_mut(tenant).clone()
+    }
+
+    pub fn register(&self, tenant: &str, url: String) -> Endpoint {
+        let mut endpoint = self.endpoint_mut(tenant);
+        endpoint.url = Some(url);
+        endpoint.clone()
+    }
+
+    fn endpoint_mut(&self, tenant: &str) -> RefMut<'_, String, Endpoint> {
+        self.endpoints
+            .entry(tenant.to_string())
+            .or_insert_with(|| Endpoint {
+                url: None,
+                secret: format!(
+                    "whsec_{}{}",
+                    Uuid::new_v4().simple(),
+                    Uuid::new_v4().simple()
+                ),
+            })
+    }
+
+    /// Stops sending to the tenant's endpoint; per-upload callbacks still work.
+    pub fn unregister(&self, tenant: &str) -> bool {
+        self.endpoints
+            .get_mut(tenant)
+            .and_then(|mut endpoint| endpoint.url.take())
+            .is_some()
+    }
+
+    pub fn set_callback(&self, analysis_id: Uuid, url: String) {
+        self.callbacks.insert(analysis_id, url);
+    }
+
+    /// Drops the upload's callback, for an analysis deleted after it finished.
+    pub fn remove_callback(&self, analysis_id: Uuid) {
+        self.callbacks.remove(&analysis_id);
+    }
+
+    /// Sends the event for `status` to the upload's callback and the
+    /// tenant's endpoint, if the status is final.
+    pub fn notify(self: &Arc<Self>, tenant: &str, analysis_id: Uuid, status: &AnalysisStatus) {
+        if let Some(event) = Event::for_status(status) {
+            self.send_event(tenant, analysis_id, event, Some(status));
+        }
+    }
+
+    /// Sends `analysis.cancelled` for an analysis deleted while queued or running.
+    pub fn cancelled(self: &Arc<Self>, tenant: &str, analysis_id: Uuid) {
+        self.send_event(tenant, analysis_id, Event::Cancelled, None);
+    }
+
+    fn send_event(
+        self: &Arc<Self>,
+        tenant: &str,
+        analysis_id: Uuid,
+        event: Event,
+        status: Option<&AnalysisStatus>,
+    ) {
+        let callback = self.callbacks.remove(&analysis_id).map(|(_, url)| url);
+        let registered = self.endpoints.get(tenant).and_then(|e| e.url.clone());
+        let mut urls: Vec<String> = callback.into_iter().chain(registered).collect();
+        urls.dedup();
+
+        let payload = payload(event, tenant, analysis_id, status);
+        for url in urls {
+            self.start(
+                tenant,
+                Delivery {
+                    id: Uuid::new_v4(),
+                    analysis_id,
+                    event: event.name(),
+                    url,
+                    state: DeliveryState::Pending,
+                    attempts: Vec::new(),
+                    created: SystemTime::now(),
+                    payload: payload.clone(),
+                },
+            );
+        }
+    }
+
+    /// Deliveries newest first, of one tenant or of all when `tenant` is `None`.
+    pub fn deliveries(&self, tenant: Option<&str>, analysis_id: Option<Uuid>) -> Vec<Delivery> {
+        let mut deliveries: Vec<Delivery> = self
+            .deliveries
+            .iter()
+            .filter(|log| tenant.map_or(true, |tenant| log.key() == tenant))
+            .flat_map(|log| log.value().iter().cloned().collect::<Vec<_>>())
+            .filter(|d| analysis_id.map_or(true, |id| d.analysis_id == id))
+            .collect();
+        deliveries.sort_by(|a, b| b.created.cmp(&a.created));
+        deliveries
+    }
+
+    /// Sends a logged delivery again as a new delivery with the same payload.
+    pub fn redeliver(self: &Arc<Self>, tenant: Option<&str>, id: Uuid) -> Option<Delivery> {
+        let (owner, original) = self.deliveries.iter().find_map(|log| {
+            if tenant.map_or(false, |tenant| log.key() != tenant) {
+                return None;
+            }
+            let delivery = log.value().iter().find(|d| d.id == id)?;
+            Some((log.key().clone(), delivery.clone()))
+        })?;
+        let delivery = Delivery {
+            id: Uuid::new_v4(),
+            state: DeliveryState::Pending,
+            attempts: Vec::new(),
+            created: SystemTime::now(),
+            ..original
+        };
+        self.start(&owner, delivery.clone());
+        Some(delivery)
+    }
+
+    fn start(self: &Arc<Self>, tenant: &str, delivery: Delivery) {
+        let id = delivery.id;
+        {
+            let mut log = self.deliveries.entry(tenant.to_string()).or_default();
+            log.push_front(delivery);
+            log.truncate(LOG_SIZE);
+        }
+        tokio::spawn(Arc::clone(self).deliver(tenant.to_string(), id));
+    }
+
+    async fn deliver(self: Arc<Self>, tenant: String, id: Uuid) {
+        for attempt in 0..self.max_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(backoff(self.retry_delay, attempt - 1)).await;
+            }
+            // Dropped from the log; nothing left to send or record
+            let Some(delivery) = self.find(&tenant, id) else {
+                return;
+            };
+            let secret = self.endpoint(&tenant).secret;
+            let result = self.send(&delivery, &secret).await;
+
+            let delivered = matches!(&result, Ok(status) if (200..300).contains(status));
+            let (status, error) = match result {
+                Ok(status) => (Some(status), None),
+                Err(e) => (None, Some(format!("{:#}", e))),
+            };
+            self.update(&tenant, id, |delivery| {
+                delivery.attempts.push(Attempt {
+                    timestamp: SystemTime::now(),
+                    status,
+                    error,
+                });
+                if delivered {
+                    delivery.state = DeliveryState::Delivered;
+                }
+            });
+            if delivered {
+                info!(
+                    "Delivered {} for {} to {}",
+                    delivery.event, delivery.analysis_id, delivery.url
+                );
+                return;
+            }
+        }
+        warn!(
+            "Giving up on webhook delivery {} after {} attempts",
+            id, self.max_attempts
+        );
+        self.update(&tenant, id, |delivery| {
+            delivery.state = DeliveryState::Failed
+        });
+    }
+
+    async fn send(&self, delivery: &Delivery, secret: &str) -> anyhow::Result<u16> {
+        // Host names are checked by the resolver; IP literals never reach it
+        if !self.allow_private {
+            let url = Url::parse(&delivery.url)?;
+            if literal_ip(&url).is_some_and(|ip| !is_public(ip)) {
+                anyhow::bail!("{} is not a public address", delivery.url);
+            }
+        }
+        let body = serde_json::to_vec(&delivery.payload).unwrap_or_default();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(secret, timestamp, &body))
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(EVENT_HEADER, delivery.event)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .body(body)
+            .send()
+            .await?;
+        Ok(response.status().as_u16())
+    }
+
+    fn find(&self, tenant: &str, id: Uuid) -> Option<Delivery> {
+        self.deliveries
+            .get(tenant)?
+            .iter()
+            .find(|d| d.id == id)
+            .cloned()
+    }
+
+    fn update(&self, tenant: &str, id: Uuid, f: impl FnOnce(&mut Delivery)) {
+        if let Some(mut log) = self.deliveries.get_mut(tenant) {
+            if let Some(delivery) = log.iter_mut().find(|d| d.id == id) {
+                f(delivery);
+            }
+        }
+    }
+}
+
+/// Resolves webhook hosts to their public addresses only, so a DNS change
+/// after a URL was validated cannot point a delivery at an internal host.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(public_addrs(name.as_str().to_string()))
+    }
+}
+
+async fn public_addrs(host: String) -> Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+        .await?
+        .filter(|addr| is_public(addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} has no public address", host).into());
+    }
+    Ok(Box::new(addrs.into_iter()))
+}
+
+/// The host of `url` if it is an IP address rather than a name.
+fn literal_ip(url: &Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether `ip` is routable on the internet: not loopback, private,
+/// link-local, shared, unspecified, broadcast or documentation space.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || a == 0
+        // Shared address space (carrier-grade NAT)
+        || (a == 100 && (64..128).contains(&b))
+        // Reserved, including multicast
+        || a >= 224)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local fc00::/7 and link-local fe80::/10
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        // Documentation 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// The JSON body of an event; the same for every delivery of it. `status` is
+/// `None` for a cancelled analysis.
+fn payload(
+    event: Event,
+    tenant: &str,
+    analysis_id: Uuid,
+    status: Option<&AnalysisStatus>,
+) -> Value {
+    let (overall_score, error, code) = match status {
+        Some(AnalysisStatus::Complete { analysis, .. }) => {
+            (Some(analysis.summary.overall_score), None, None)
+        }
+        Some(AnalysisStatus::Failed { error, code, .. }) => {
+            (None, Some(error.clone()), code.clone())
+        }
+        _ => (None, None, None),
+    };
+    json!({
+        "event": event.name(),
+        "analysis_id": analysis_id,
+        "tenant": tenant,
+        "status": status.map_or("cancelled", status_name),
+        "overall_score": overall_score,
+        "error": error,
+        "code": code,
+        "timestamp": SystemTime::now(),
+    })
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `<timestamp>.<body>`.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// Wait before retry `retry` (0-based): doubling from `base`, capped.
+fn backoff(base: Duration, retry: u32) -> Duration {
+    base.checked_mul(1 << retry.min(16))
+        .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::analysis;
+    use actix_web::http::StatusCode;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use std::sync::Mutex;
+
+    /// A request the receiver got: its event header, whether the signature
+    /// matched `secret`, and the body.
+    type Received = (String, bool, Value);
+
+    /// Starts a receiver on a local port that answers with `statuses` in turn
+    /// and 200 after them. Returns its URL and what it has received.
+    fn receiver(statuses: Vec<u16>, secret: String) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let statuses = Arc::new(Mutex::new(VecDeque::from(statuses)));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&received);
+        let handler = move |req: HttpRequest, body: web::Bytes| {
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let timestamp = header(TIMESTAMP_HEADER).parse().unwrap_or_default();
+            log.lock().unwrap().push((
+                header(EVENT_HEADER),
+                header(SIGNATURE_HEADER) == sign(&secret, timestamp, &body),
+                serde_json::from_slice(&body).unwrap_or_default(),
+            ));
+            let status = statuses.lock().unwrap().pop_front().unwrap_or(200);
+            async move { HttpResponse::new(StatusCode::from_u16(status).unwrap()) }
+        };
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = HttpServer::new(move || App::new().default_service(web::to(handler.clone())))
+            .workers(1)
+            .disable_signals()
+            .listen(listener)
+            .unwrap()
+            .run();
+        actix_web::rt::spawn(server);
+        (url, received)
+    }
+
+    /// Webhooks that may call the local receiver and retry without waiting long.
+    fn local_webhooks(max_attempts: u32) -> Arc<Webhooks> {
+        let mut webhooks = Webhooks::new(max_attempts, true).unwrap();
+        webhooks.retry_delay = Duration::from_millis(10);
+        Arc::new(webhooks)
+    }
+
+    fn failed() -> AnalysisStatus {
+        AnalysisStatus::Failed {
+            error: "No frames could be analyzed".to_string(),
+            code: Some("model_unavailable".to_string()),
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    /// The tenant's deliveries once none is pending.
+    async fn settled(webhooks: &Webhooks, tenant: &str) -> Vec<Delivery> {
+        for _ in 0..500 {
+            let deliveries = webhooks.deliveries(Some(tenant), None);
+            if deliveries.iter().all(|d| d.state != DeliveryState::Pending) {
+                return deliveries;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Deliveries to {} are still pending", tenant);
+    }
+
+    fn statuses(delivery: &Delivery) -> Vec<Option<u16>> {
+        delivery.attempts.iter().map(|a| a.status).collect()
+    }
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("key", 1_700_000_000, b"{}");
+        assert_eq!(
+            signature,
+            "sha256=9d713ed406bb7076d4123f0dc2c39d2df5c654ed4b0cd56b52c8b4c940bd63ae"
+        );
+        assert_ne!(signature, sign("other", 1_700_000_000, b"{}"));
+        assert_ne!(signature, sign("key", 1_700_000_001, b"{}"));
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(BASE_DELAY, 0), Duration::from_secs(2));
+        assert_eq!(backoff(BASE_DELAY, 3), Duration::from_secs(16));
+        assert_eq!(backoff(BASE_DELAY, 10), MAX_DELAY);
+        assert_eq!(backoff(BASE_DELAY, u32::MAX), MAX_DELAY);
+    }
+
+    #[test]
+    fn test_urls_and_endpoints() {
+        assert!(Webhooks::parse_url("https://example.com/hook").is_ok());
+        assert!(Webhooks::parse_url("ftp://example.com/hook").is_err());
+        assert!(Webhooks::parse_url("/hook").is_err());
+
+        let webhooks = Webhooks::new(DEFAULT_MAX_ATTEMPTS, false).unwrap();
+        let secret = webhooks.endpoint("acme").secret;
+        let endpoint = webhooks.register("acme", "https://example.com/hook".to_string());
+        assert_eq!(endpoint.secret, secret);
+        assert!(webhooks.unregister("acme"));
+        assert!(!webhooks.unregister("acme"));
+        assert_eq!(webhooks.endpoint("acme").secret, secret);
+    }
+
+    #[test]
+    fn test_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} is internal", ip);
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{} is public", ip);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_validate_url() {
+        let webhooks = Webhooks::new(DEFAULT_MAX_ATTEMPTS, false).unwrap();
+        for url in [
+            "http://localhost:9997/v1/chat/completions",
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.0.0.5/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "ftp://93.184.216.34/hook",
+        ] {
+            assert!(
+                webhooks.validate_url(url).await.is_err(),
+                "{} is rejected",
+                url
+            );
+        }
+        assert!(webhooks
+            .validate_url("https://93.184.216.34/hook")
+            .await
+            .is_ok());
+
+        let webhooks = Webhooks::new(DEFAULT_MAX_ATTEMPTS, true).unwrap();
+        assert!(webhooks
+            .validate_url("http://127.0.0.1:8080/hook")
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_retries_until_delivered() {
+        let webhooks = local_webhooks(3);
+        let (url, received) = receiver(vec![500, 503], webhooks.endpoint("acme").secret);
+        webhooks.register("acme", url.clone());
+        let id = Uuid::new_v4();
+        // The upload's callback is the same URL, so it is sent once
+        webhooks.set_callback(id, url.clone());
+        webhooks.notify("acme", id, &AnalysisStatus::Queued);
+        assert!(webhooks.deliveries(None, None).is_empty());
+
+        webhooks.notify(
+            "acme",
+            id,
+            &AnalysisStatus::Complete {
+                analysis: analysis(id, 87.0),
+                completion_time: SystemTime::now(),
+            },
+        );
+        let deliveries = settled(&webhooks, "acme").await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].state, DeliveryState::Delivered);
+        assert_eq!(deliveries[0].url, url);
+        assert_eq!(statuses(&deliveries[0]), [Some(500), Some(503), Some(200)]);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        for (event, signed, body) in received.iter() {
+            assert_eq!(event, "analysis.complete");
+            assert!(signed);
+            assert_eq!(body["analysis_id"], json!(id));
+            assert_eq!(body["overall_score"], json!(87.0));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_gives_up_and_redelivers() {
+        let webhooks = local_webhooks(2);
+        let (url, received) = receiver(vec![500, 500], webhooks.endpoint("acme").secret);
+        let id = Uuid::new_v4();
+        webhooks.set_callback(id, url);
+        webhooks.notify("acme", id, &failed());
+
+        let deliveries = settled(&webhooks, "acme").await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].state, DeliveryState::Failed);
+        assert_eq!(statuses(&deliveries[0]), [Some(500), Some(500)]);
+        let failed_id = deliveries[0].id;
+
+        // Other tenants can neither see nor resend it
+        assert!(webhooks.deliveries(Some("globex"), None).is_empty());
+        assert!(webhooks.redeliver(Some("globex"), failed_id).is_none());
+
+        let redelivered = webhooks.redeliver(Some("acme"), failed_id).unwrap();
+        assert_ne!(redelivered.id, failed_id);
+        assert_eq!(redelivered.state, DeliveryState::Pending);
+        let deliveries = settled(&webhooks, "acme").await;
+        let ids: Vec<Uuid> = deliveries.iter().map(|d| d.id).collect();
+        assert_eq!(ids, [redelivered.id, failed_id]);
+        assert_eq!(deliveries[0].state, DeliveryState::Delivered);
+        assert_eq!(deliveries[0].payload, deliveries[1].payload);
+        assert_eq!(webhooks.deliveries(None, Some(id)).len(), 2);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        assert!(received.iter().all(|(event, signed, body)| {
+            event == "analysis.failed" && *signed && body["code"] == "model_unavailable"
+        }));
+    }
+
+    #[actix_web::test]
+    async fn test_callbacks_are_per_analysis() {
+        let webhooks = local_webhooks(1);
+        let (url, received) = receiver(Vec::new(), webhooks.endpoint("acme").secret);
+        let (deleted, sent) = (Uuid::new_v4(), Uuid::new_v4());
+        webhooks.set_callback(deleted, url.clone());
+        webhooks.set_callback(sent, url);
+        webhooks.remove_callback(deleted);
+
+        webhooks.notify("acme", deleted, &failed());
+        webhooks.notify("acme", sent, &failed());
+        let deliveries = settled(&webhooks, "acme").await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].analysis_id, sent);
+        assert_eq!(deliveries[0].state, DeliveryState::Delivered);
+
+        // Sent once; a second final status finds no callback left
+        webhooks.notify("acme", sent, &failed());
+        assert_eq!(webhooks.deliveries(Some("acme"), None).len(), 1);
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_cancelled() {
+        let webhooks = local_webhooks(1);
+        let (url, received) = receiver(Vec::new(), webhooks.endpoint("acme").secret);
+        let id = Uuid::new_v4();
+        webhooks.set_callback(id, url);
+        webhooks.cancelled("acme", id);
+
+        let deliveries = settled(&webhooks, "acme").await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, "analysis.cancelled");
+        assert_eq!(deliveries[0].state, DeliveryState::Delivered);
+
+        // The callback is used up, so a late final status sends nothing
+        webhooks.notify("acme", id, &failed());
+        assert_eq!(webhooks.deliveries(Some("acme"), None).len(), 1);
+
+        let received = received.lock().unwrap();
+        let (event, signed, body) = &received[0];
+        assert_eq!(event, "analysis.cancelled");
+        assert!(signed);
+        assert_eq!(body["status"], "cancelled");
+        assert!(body["error"].is_null());
+    }
+}
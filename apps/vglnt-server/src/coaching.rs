@@ -0,0 +1,372 @@
+//! Coaching suggestions for the weakest categories of a drive.
+
+use crate::llm::LLMClient;
+use crate::types::{FrameAnalysis, ImprovementArea, RiskFactor, RiskFactorType};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{info, warn};
+
+const DEFAULT_MAX_AREAS: usize = 3;
+
+/// A scored category of the frame rubric, with the points `prepare.py` awards
+/// for a fully compliant frame.
+pub(crate) struct Category {
+    pub(crate) name: &'static str,
+    pub(crate) max_score: f32,
+    pub(crate) score: fn(&FrameAnalysis) -> f32,
+    factor_type: fn() -> RiskFactorType,
+}
+
+pub(crate) const CATEGORIES: [Category; 8] = [
+    Category {
+        name: "lane_centering",
+        max_score: 20.0,
+        score: |f| f.lane_centering.score,
+        factor_type: || RiskFactorType::LaneDeviation,
+    },
+    Category {
+        name: "following_distance",
+        max_score: 15.0,
+        score: |f| f.following_distance.score,
+        factor_type: || RiskFactorType::FollowingDistance,
+    },
+    Category {
+        name: "signal_compliance",
+        max_score: 20.0,
+        score: |f| f.signal_compliance.score,
+        factor_type: || RiskFactorType::SignalCompliance,
+    },
+    Category {
+        name: "merging_lane_change",
+        max_score: 10.0,
+        score: |f| f.merging_lane_change.score,
+        factor_type: || RiskFactorType::MergingTechnique,
+    },
+    Category {
+        name: "pedestrian_yielding",
+        max_score: 10.0,
+        score: |f| f.pedestrian_yielding.score,
+        factor_type: || RiskFactorType::PedestrianAwareness,
+    },
+    Category {
+        name: "intersection_behavior",
+        max_score: 10.0,
+        score: |f| f.intersection_behavior.score,
+        factor_type: || RiskFactorType::IntersectionBehavior,
+    },
+    Category {
+        name: "road_sign_awareness",
+        max_score: 20.0,
+        score: |f| f.road_sign_awareness.score,
+        factor_type: || RiskFactorType::SpeedControl,
+    },
+    Category {
+        name: "shoulder_use",
+        max_score: 5.0,
+        score: |f| f.shoulder_use.score,
+        factor_type: || RiskFactorType::Other("shoulder_use".to_string()),
+    },
+];
+
+/// Target scores on the normalized 0–100 scale.
+///
+/// When fleet scores are given for a category its target is the
+/// `fleet_percentile` of them; otherwise the fixed target applies.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Benchmarks {
+    #[serde(default = "default_target")]
+    pub default_target: f32,
+    #[serde(default)]
+    pub targets: HashMap<String, f32>,
+    #[serde(default = "default_percentile")]
+    pub fleet_percentile: f32,
+    #[serde(default)]
+    pub fleet_scores: HashMap<String, Vec<f32>>,
+}
+
+fn default_target() -> f32 {
+    90.0
+}
+
+fn default_percentile() -> f32 {
+    75.0
+}
+
+impl Default for Benchmarks {
+    fn default() -> Self {
+        Self {
+            default_target: default_target(),
+            targets: HashMap::new(),
+            fleet_percentile: default_percentile(),
+            fleet_scores: HashMap::new(),
+        }
+    }
+}
+
+impl Benchmarks {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read benchmarks from {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid benchmarks in {}", path.display()))
+    }
+
+    pub fn target(&self, category: &str) -> f32 {
+        match self.fleet_scores.get(category) {
+            Some(scores) if !scores.is_empty() => percentile(scores, self.fleet_percentile),
+            _ => self
+                .targets
+                .get(category)
+                .copied()
+                .unwrap_or(self.default_target),
+        }
+    }
+}
+
+/// Nearest-rank percentile.
+fn percentile(values: &[f32], percentile: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let rank = (percentile.clamp(0.0, 100.0) / 100.0 * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub struct Coach {
+    benchmarks: Benchmarks,
+    max_areas: usize,
+    use_llm: bool,
+}
+
+impl Coach {
+    pub fn new(benchmarks: Benchmarks, max_areas: usize, use_llm: bool) -> Self {
+        Self {
+            benchmarks,
+            max_areas,
+            use_llm,
+        }
+    }
+
+    /// Reads `VGLNT_BENCHMARKS_FILE` and `VGLNT_COACHING_LLM`.
+    pub fn from_env() -> Result<Self> {
+        let benchmarks = match std::env::var("VGLNT_BENCHMARKS_FILE") {
+            Ok(path) => Benchmarks::load(Path::new(&path))?,
+            Err(_) => Benchmarks::default(),
+        };
+        let use_llm = std::env::var("VGLNT_COACHING_LLM")
+            .map(|v| matches!(v.as_str(), "1" | "true"))
+            .unwrap_or(false);
+        Ok(Self::new(benchmarks, DEFAULT_MAX_AREAS, use_llm))
+    }
+
+    /// The weakest categories, most urgent first, with templated suggestions.
+    ///
+    /// A category qualifies when it scores below its target or has a risk
+    /// factor; urgency is the gap to target plus the risk severity in points.
+    pub fn improvement_areas(
+        &self,
+        frames: &[FrameAnalysis],
+        risks: &[RiskFactor],
+    ) -> Vec<ImprovementArea> {
+        if frames.is_empty() {
+            return Vec::new();
+        }
+
+        let mut ranked: Vec<(f32, ImprovementArea)> = CATEGORIES
+            .iter()
+            .filter_map(|category| {
+                let factor_type = (category.factor_type)();
+                let total: f32 = frames.iter().map(category.score).sum();
+                let current_score =
+                    (total / frames.len() as f32 / category.max_score * 100.0).clamp(0.0, 100.0);
+                let target_score = self.benchmarks.target(category.name).max(current_score);
+                let severity = risks
+                    .iter()
+                    .filter(|r| r.factor_type == factor_type)
+                    .map(|r| r.severity)
+                    .fold(0.0, f32::max);
+                if target_score - current_score <= 0.0 && severity == 0.0 {
+                    return None;
+                }
+
+                let urgency = target_score - current_score + severity * 100.0;
+                let area = ImprovementArea {
+                    area: category.name.to_string(),
+                    current_score,
+                    target_score,
+                    suggestions: templates(&factor_type)
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                };
+                Some((urgency, area))
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranked
+            .into_iter()
+            .take(self.max_areas)
+            .map(|(_, area)| area)
+            .collect()
+    }
+
+    /// Replaces templated suggestions with model-written ones when enabled.
+    ///
+    /// Any area the model fails on keeps its templates.
+    pub async fn enrich(
+        &self,
+        llm: &LLMClient,
+        areas: Vec<ImprovementArea>,
+    ) -> Vec<ImprovementArea> {
+        if !self.use_llm {
+            return areas;
+        }
+
+        let mut enriched = Vec::with_capacity(areas.len());
+        for mut area in areas {
+            match suggest(llm, &area).await {
+                Ok(suggestions) => area.suggestions = suggestions,
+                Err(e) => warn!("Using template coaching for {}: {:#}", area.area, e),
+            }
+            enriched.push(area);
+        }
+        info!("Generated coaching for {} areas", enriched.len());
+        enriched
+    }
+}
+
+impl Default for Coach {
+    fn default() -> Self {
+        Self::new(Benchmarks::default(), DEFAULT_MAX_AREAS, false)
+    }
+}
+
+async fn suggest(llm: &LLMClient, area: &ImprovementArea) -> Result<Vec<String>> {
+    let prompt = format!(
+        "You are a driving instructor. A driver scored {:.0}/100 on {} against a target of {:.0}. \
+         Give three short, specific tips as a bulleted list, one per line, and nothing else.\n\
+         Example tips:\n- {}\n",
+        area.current_score,
+        area.area.replace('_', " "),
+        area.target_score,
+        area.suggestions.join("\n- ")
+    );
+    let response = llm.complete_text(&prompt).await?;
+
+    let suggestions: Vec<String> = response
+        .lines()
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(|c: char| c == '-' || c == '*' || c == '•')
+                .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == ')')
+                .trim()
+                .to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .take(3)
+        .collect();
+    if suggestions.is_empty() {
+        anyhow::bail!("Empty coaching response");
+    }
+    Ok(suggestions)
+}
+
+fn templates(factor_type: &RiskFactorType) -> &'static [&'static str] {
+    match factor_type {
+        RiskFactorType::LaneDeviation => &[
+            "Look further ahead down the lane rather than just over the bonnet.",
+            "Make small, early steering corrections to stay centred.",
+            "Reduce in-cab distractions that pull attention off the road.",
+        ],
+        RiskFactorType::FollowingDistance => &[
+            "Keep at least a three-second gap to the vehicle ahead.",
+            "Double the gap in rain, fog or at night.",
+            "Ease off early when traffic ahead slows instead of braking late.",
+        ],
+        RiskFactorType::SpeedControl => &[
+            "Scan for speed limit signs after every junction.",
+            "Check the speedometer regularly when the limit changes.",
+            "Lift off early so you enter lower limits at the posted speed.",
+        ],
+        RiskFactorType::SignalCompliance => &[
+            "Anticipate signal changes and prepare to stop on a stale green.",
+            "Stop for amber when it is safe to do so.",
+            "Never proceed until the light is green and the junction is clear.",
+        ],
+        RiskFactorType::PedestrianAwareness => &[
+            "Cover the brake near crossings and busy pavements.",
+            "Yield to pedestrians waiting at or stepping onto a crossing.",
+            "Check for pedestrians before turning across a footway.",
+        ],
+        RiskFactorType::IntersectionBehavior => &[
+            "Come to a complete stop at the stop line before proceeding.",
+            "Give way to traffic with right of way before entering a junction.",
+            "Approach junctions at a speed that lets you stop if needed.",
+        ],
+        RiskFactorType::MergingTechnique => &[
+            "Signal well before changing lanes or merging.",
+            "Check mirrors and blind spots before every lane change.",
+            "Match the speed of the target lane before moving across.",
+        ],
+        RiskFactorType::Other(name) if name == "shoulder_use" => &[
+            "Use the hard shoulder only in an emergency.",
+            "Plan exits early so you never need the shoulder to reach them.",
+        ],
+        RiskFactorType::Other(_) => {
+            &["Review the flagged moments of this drive and plan how to avoid them."]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::timeline;
+    use crate::types::RiskSource;
+
+    #[test]
+    fn test_perfect_drive_needs_no_coaching() {
+        assert!(Coach::default()
+            .improvement_areas(&timeline(4), &[])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_weakest_categories_ranked() {
+        let mut frames = timeline(4);
+        for frame in &mut frames {
+            frame.following_distance.score = 7.5;
+            frame.lane_centering.score = 16.0;
+        }
+        let risks = vec![RiskFactor {
+            factor_type: RiskFactorType::SignalCompliance,
+            severity: 0.9,
+            frequency: 0.25,
+            temporal_correlation: 0.0,
+            sources: vec![RiskSource::Model],
+            evidence_frames: vec![30],
+        }];
+
+        let areas = Coach::default().improvement_areas(&frames, &risks);
+        let names: Vec<&str> = areas.iter().map(|a| a.area.as_str()).collect();
+        assert_eq!(
+            names,
+            ["signal_compliance", "following_distance", "lane_centering"]
+        );
+        assert_eq!(areas[1].current_score, 50.0);
+        assert_eq!(areas[1].target_score, 90.0);
+        assert!(!areas[1].suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_fleet_percentile_target() {
+        let mut benchmarks = Benchmarks::default();
+        benchmarks
+            .fleet_scores
+            .insert("lane_centering".to_string(), vec![60.0, 70.0, 80.0, 95.0]);
+        assert_eq!(benchmarks.target("lane_centering"), 80.0);
+        assert_eq!(benchmarks.target("shoulder_use"), 90.0);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::smoothing::Smoother;
+use crate::types::{FrameAnalysis, FrameData};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+/// Frame rate assumed for annotation timestamps; `prepare.py` keeps every frame.
+const ANNOTATION_FPS: f64 = 30.0;
+
+/// One labelled drive: its per-frame timeline and the 0–5 drive label.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub video: String,
+    pub frames: Vec<FrameAnalysis>,
+    pub label: f32,
+}
+
+/// Reads `video,label` rows. A header row is skipped if its label is not a number.
+pub fn load_labels(path: &Path) -> Result<HashMap<String, f32>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read labels from {}", path.display()))?;
+    let mut labels = HashMap::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let location = format!("{}:{}", path.display(), idx + 1);
+        if line.is_empty() {
+            continue;
+        }
+        let (video, label) = line
+            .split_once(',')
+            .with_context(|| format!("{}: expected video,label", location))?;
+        match label.trim().parse::<f32>() {
+            Ok(label) => {
+                labels.insert(video.trim().to_string(), label);
+            }
+            Err(_) if idx == 0 => continue,
+            Err(e) => anyhow::bail!("{}: invalid label: {}", location, e),
+        }
+    }
+
+    Ok(labels)
+}
+
+/// Reads the `frame_*.json` files of one video directory in frame order.
+pub fn load_annotations(video_dir: &Path, max_frames: usize) -> Result<Vec<FrameAnalysis>> {
+    let mut entries: Vec<(u32, std::path::PathBuf)> = fs::read_dir(video_dir)
+        .with_context(|| format!("Failed to read {}", video_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?;
+            let frame_number = stem.strip_prefix("frame_")?.parse().ok()?;
+            (path.extension()? == "json").then_some((frame_number, path))
+        })
+        .collect();
+    entries.sort_by_key(|(frame_number, _)| *frame_number);
+
+    let mut frames = Vec::with_capacity(entries.len().min(max_frames));
+    for (frame_number, path) in entries.into_iter().take(max_frames) {
+        let parsed = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(serde_json::from_str::<FrameData>(&contents)?));
+        let timestamp = frame_number as f64 / ANNOTATION_FPS;
+        match parsed {
+            Ok(frame_data) => frames.push(frame_data.into_analysis(frame_number, timestamp)),
+            Err(e) => warn!("Skipping {}: {}", path.display(), e),
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Loads every labelled video under `annotations_dir`, with its timeline
+/// passed through `smoother` as the server does before the sequence model.
+///
+/// Videos without a label or without readable frames are skipped with a warning.
+pub fn load_samples(
+    annotations_dir: &Path,
+    labels_path: &Path,
+    max_frames: usize,
+    smoother: &Smoother,
+) -> Result<Vec<Sample>> {
+    let labels = load_labels(labels_path)?;
+    let mut video_dirs: Vec<_> = fs::read_dir(annotations_dir)
+        .with_context(|| format!("Failed to read {}", annotations_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    video_dirs.sort();
+
+    let mut samples = Vec::new();
+    for dir in video_dirs {
+        let video = match dir.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let Some(label) = labels.get(&video).copied() else {
+            warn!("No label for {}, skipping", video);
+            continue;
+        };
+        let frames = load_annotations(&dir, max_frames)?;
+        if frames.is_empty() {
+            warn!("No frames for {}, skipping", video);
+            continue;
+        }
+        samples.push(Sample {
+            video,
+            frames: smoother.smooth(&frames),
+            label,
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Shuffles with a seeded xorshift so splits are reproducible across runs.
+pub fn shuffle<T>(items: &mut [T], seed: &mut u64) {
+    for idx in (1..items.len()).rev() {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        items.swap(idx, (*seed % (idx as u64 + 1)) as usize);
+    }
+}
+
+/// Splits off `fraction` of the samples after a seeded shuffle.
+pub fn split(mut samples: Vec<Sample>, fraction: f32, seed: u64) -> (Vec<Sample>, Vec<Sample>) {
+    let mut seed = seed.max(1);
+    shuffle(&mut samples, &mut seed);
+    let held_out = ((samples.len() as f32) * fraction).round() as usize;
+    let kept = samples.split_off(held_out.min(samples.len()));
+    (kept, samples)
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::types::fixtures::frame_data;
+    use std::fs;
+    use std::path::Path;
+
+    /// Writes compliant `frame_<n>.json` annotations for `video` under `dir`.
+    pub fn write_video(dir: &Path, video: &str, frame_numbers: &[u32]) {
+        let video_dir = dir.join(video);
+        fs::create_dir_all(&video_dir).unwrap();
+        let contents = serde_json::to_string(&frame_data()).unwrap();
+        for frame_number in frame_numbers {
+            fs::write(
+                video_dir.join(format!("frame_{}.json", frame_number)),
+                &contents,
+            )
+            .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::write_video;
+    use super::*;
+
+    fn labels(contents: &str) -> Result<HashMap<String, f32>> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labels.csv");
+        fs::write(&path, contents).unwrap();
+        load_labels(&path)
+    }
+
+    fn samples(count: usize) -> Vec<Sample> {
+        (0..count)
+            .map(|idx| Sample {
+                video: format!("video_{}", idx),
+                frames: Vec::new(),
+                label: idx as f32,
+            })
+            .collect()
+    }
+
+    fn videos(samples: &[Sample]) -> Vec<&str> {
+        samples.iter().map(|sample| sample.video.as_str()).collect()
+    }
+
+    #[test]
+    fn test_load_labels() {
+        let loaded = labels("video,label\nclip_a,4.5\n\n clip_b , 3\n").unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded["clip_a"], 4.5);
+        assert_eq!(loaded["clip_b"], 3.0);
+
+        let loaded = labels("clip_a,4\nclip_b,2\n").unwrap();
+        assert_eq!(loaded.len(), 2);
+    }
+
+    #[test]
+    fn test_load_labels_rejects_bad_rows() {
+        let error = labels("video,label\nclip_a\n").unwrap_err().to_string();
+        assert!(error.contains(":2: expected video,label"), "{}", error);
+
+        let error = labels("clip_a,4\nclip_b,good\n").unwrap_err().to_string();
+        assert!(error.contains(":2: invalid label"), "{}", error);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_labels(&dir.path().join("missing.csv")).is_err());
+    }
+
+    #[test]
+    fn test_load_annotations_in_frame_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_video(dir.path(), "drive", &[10, 2, 1]);
+        let video_dir = dir.path().join("drive");
+        fs::write(video_dir.join("frame_5.json"), "{").unwrap();
+        fs::write(video_dir.join("frame_3.txt"), "").unwrap();
+        fs::write(video_dir.join("notes.json"), "{}").unwrap();
+
+        let frames = load_annotations(&video_dir, 10).unwrap();
+        let numbers: Vec<u32> = frames.iter().map(|f| f.frame_number).collect();
+        assert_eq!(numbers, vec![1, 2, 10]);
+        assert_eq!(frames[2].timestamp, 10.0 / ANNOTATION_FPS);
+
+        let frames = load_annotations(&video_dir, 2).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].frame_number, 2);
+    }
+
+    #[test]
+    fn test_load_samples_skips_unusable_videos() {
+        let dir = tempfile::tempdir().unwrap();
+        let annotations = dir.path().join("annotations");
+        write_video(&annotations, "labelled", &[0, 1, 2]);
+        write_video(&annotations, "unlabelled", &[0]);
+        write_video(&annotations, "empty", &[]);
+        let labels_path = dir.path().join("labels.csv");
+        fs::write(&labels_path, "video,label\nlabelled,4\nempty,2\n").unwrap();
+
+        let samples = load_samples(&annotations, &labels_path, 10, &Smoother::default()).unwrap();
+        assert_eq!(videos(&samples), vec!["labelled"]);
+        assert_eq!(samples[0].frames.len(), 3);
+        assert_eq!(samples[0].label, 4.0);
+    }
+
+    #[test]
+    fn test_split_is_deterministic() {
+        let (kept, held_out) = split(samples(20), 0.25, 42);
+        assert_eq!((kept.len(), held_out.len()), (15, 5));
+        let (kept_again, held_out_again) = split(samples(20), 0.25, 42);
+        assert_eq!(videos(&kept), videos(&kept_again));
+        assert_eq!(videos(&held_out), videos(&held_out_again));
+
+        let mut all = videos(&kept);
+        all.extend(videos(&held_out));
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 20);
+
+        // A zero seed would leave xorshift stuck at zero
+        let (kept_zero, _) = split(samples(20), 0.25, 0);
+        let (kept_one, _) = split(samples(20), 0.25, 1);
+        assert_eq!(videos(&kept_zero), videos(&kept_one));
+    }
+}
@@ -1,5 +1,15 @@
-use actix_web::{HttpResponse, ResponseError};
+use crate::api::request_id;
+use crate::llm::InvalidResponse;
+use actix_multipart::MultipartError;
+use actix_web::error::{JsonPayloadError, PathError, PayloadError, QueryPayloadError};
+use actix_web::http::header::{RETRY_AFTER, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
 use thiserror::Error;
+use tracing::error;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -14,14 +24,395 @@ pub enum AppError {
 
     #[error("Not found: {0}")]
     NotFound(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    /// The model server answered with an error or an unusable response.
+    #[error("Upstream error: {0}")]
+    UpstreamError(String),
+
+    /// The model server could not be reached or is overloaded.
+    #[error("Model unavailable: {0}")]
+    ModelUnavailable(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    /// The server is not taking new work, e.g. while it shuts down.
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+
+    /// A rate limit or usage quota was reached; retrying later may succeed.
+    #[error("Quota exceeded: {message}")]
+    QuotaExceeded {
+        message: String,
+        retry_after: Duration,
+    },
+
+    /// Another error with structured context for the response body.
+    #[error("{error}")]
+    Detailed {
+        error: Box<AppError>,
+        details: Value,
+    },
+}
+
+/// The body of every error response.
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    /// Stable, machine-readable error kind, e.g. `not_found`.
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<Value>,
+    pub request_id: Option<String>,
+}
+
+impl AppError {
+    pub fn with_details(self, details: Value) -> Self {
+        AppError::Detailed {
+            error: Box::new(self),
+            details,
+        }
+    }
+
+    /// Rebuilds an error recorded by its code, such as the failure of an
+    /// analysis. Missing codes, and those needing more than a message, are
+    /// processing errors.
+    pub fn from_code(code: Option<&str>, message: String) -> Self {
+        match code {
+            Some("invalid_input") => AppError::InvalidInput(message),
+            Some("internal_error") => AppError::Internal(message),
+            Some("not_found") => AppError::NotFound(message),
+            Some("unauthorized") => AppError::Unauthorized(message),
+            Some("payload_too_large") => AppError::PayloadTooLarge(message),
+            Some("unsupported_media_type") => AppError::UnsupportedMediaType(message),
+            Some("upstream_error") => AppError::UpstreamError(message),
+            Some("model_unavailable") => AppError::ModelUnavailable(message),
+            Some("timeout") => AppError::Timeout(message),
+            Some("unavailable") => AppError::Unavailable(message),
+            _ => AppError::ProcessingError(message),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::ProcessingError(_) => "processing_error",
+            AppError::Internal(_) => "internal_error",
+            AppError::NotFound(_) => "not_found",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::UpstreamError(_) => "upstream_error",
+            AppError::ModelUnavailable(_) => "model_unavailable",
+            AppError::Timeout(_) => "timeout",
+            AppError::Unavailable(_) => "unavailable",
+            AppError::QuotaExceeded { .. } => "quota_exceeded",
+            AppError::Detailed { error, .. } => error.code(),
+        }
+    }
+
+    /// The message without the kind prefix of `Display`.
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::InvalidInput(message)
+            | AppError::ProcessingError(message)
+            | AppError::Internal(message)
+            | AppError::NotFound(message)
+            | AppError::Unauthorized(message)
+            | AppError::PayloadTooLarge(message)
+            | AppError::UnsupportedMediaType(message)
+            | AppError::UpstreamError(message)
+            | AppError::ModelUnavailable(message)
+            | AppError::Timeout(message)
+            | AppError::Unavailable(message)
+            | AppError::QuotaExceeded { message, .. } => message,
+            AppError::Detailed { error, .. } => error.message(),
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AppError::QuotaExceeded { retry_after, .. } => Some(*retry_after),
+            AppError::Detailed { error, .. } => error.retry_after(),
+            _ => None,
+        }
+    }
+
+    fn details(&self) -> Option<&Value> {
+        match self {
+            AppError::Detailed { details, .. } => Some(details),
+            _ => None,
+        }
+    }
 }
 
 impl ResponseError for AppError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self {
-            AppError::InvalidInput(_) => HttpResponse::BadRequest().json(self.to_string()),
-            AppError::NotFound(_) => HttpResponse::NotFound().json(self.to_string()),
-            _ => HttpResponse::InternalServerError().json(self.to_string()),
+            AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
+            AppError::ModelUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::ProcessingError(_) | AppError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            AppError::Detailed { error, .. } => error.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if self.status_code() == StatusCode::UNAUTHORIZED {
+            response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+        if let Some(retry_after) = self.retry_after() {
+            // Whole seconds, rounded up so clients never retry too early
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response.insert_header((RETRY_AFTER, seconds.to_string()));
+        }
+        response.json(ErrorBody {
+            code: self.code(),
+            message: self.message().to_string(),
+            details: self.details().cloned(),
+            request_id: request_id::current(),
+        })
+    }
+}
+
+/// Classifies failures from the model layers by the first recognised cause.
+///
+/// The full chain is logged; the response only carries a short message, the
+/// outermost context for processing errors.
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        error!("{:#}", e);
+        for cause in e.chain() {
+            if let Some(cause) = cause.downcast_ref::<reqwest::Error>() {
+                let unavailable = cause
+                    .status()
+                    .map_or(false, |status| matches!(status.as_u16(), 429 | 503));
+                return if cause.is_timeout() {
+                    AppError::Timeout("The model server did not answer in time".to_string())
+                } else if cause.is_connect() || unavailable {
+                    AppError::ModelUnavailable("The model server is unavailable".to_string())
+                } else {
+                    AppError::UpstreamError("The model server returned an error".to_string())
+                };
+            }
+            if cause.is::<InvalidResponse>() {
+                return AppError::UpstreamError(
+                    "The model server returned an unusable response".to_string(),
+                );
+            }
+            if cause.is::<tokio::time::error::Elapsed>() {
+                return AppError::Timeout("The analysis did not finish in time".to_string());
+            }
         }
+        AppError::ProcessingError(e.to_string())
+    }
+}
+
+impl From<MultipartError> for AppError {
+    fn from(e: MultipartError) -> Self {
+        match e {
+            MultipartError::NoContentType | MultipartError::ParseContentType => {
+                AppError::UnsupportedMediaType("Expected a multipart/form-data body".to_string())
+            }
+            MultipartError::Payload(PayloadError::Overflow) => {
+                AppError::PayloadTooLarge("Upload exceeds the size limit".to_string())
+            }
+            other => AppError::InvalidInput(format!("Malformed multipart body: {}", other)),
+        }
+    }
+}
+
+fn extractor_error(source: &str, message: &str, reason: String) -> actix_web::Error {
+    AppError::InvalidInput(message.to_string())
+        .with_details(json!({ "source": source, "reason": reason }))
+        .into()
+}
+
+/// Reports malformed path parameters, such as a bad UUID, in the error envelope.
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|e: PathError, _: &HttpRequest| {
+        extractor_error("path", "Invalid path parameter", e.to_string())
+    })
+}
+
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|e: QueryPayloadError, _: &HttpRequest| {
+        extractor_error("query", "Invalid query parameter", e.to_string())
+    })
+}
+
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|e: JsonPayloadError, _: &HttpRequest| match e {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            AppError::PayloadTooLarge(e.to_string()).into()
+        }
+        JsonPayloadError::ContentType => {
+            AppError::UnsupportedMediaType("Expected an application/json body".to_string()).into()
+        }
+        e => extractor_error("body", "Invalid JSON body", e.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+    use actix_web::{test, App};
+    use uuid::Uuid;
+
+    #[actix_web::test]
+    async fn test_error_envelope() {
+        let error = AppError::NotFound("Analysis not found".to_string())
+            .with_details(json!({ "analysis_id": "abc" }));
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(error.error_response().into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["message"], "Analysis not found");
+        assert_eq!(body["details"]["analysis_id"], "abc");
+        assert!(body["request_id"].is_null());
+    }
+
+    #[test]
+    fn test_anyhow_classification() {
+        let error = AppError::from(
+            anyhow::Error::new(InvalidResponse("no content".to_string()))
+                .context("Failed to analyze frame 3"),
+        );
+        assert_eq!(error.code(), "upstream_error");
+        assert!(!error.message().contains("no content"));
+
+        let error = AppError::from(anyhow::anyhow!("Cannot process an empty frame sequence"));
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error.code(), "processing_error");
+
+        let error = AppError::from(
+            anyhow::anyhow!("No such file or directory").context("Failed to open the video"),
+        );
+        assert_eq!(error.message(), "Failed to open the video");
+    }
+
+    #[test]
+    fn test_from_code() {
+        let error = AppError::from_code(Some("model_unavailable"), "Down".to_string());
+        assert_eq!(error.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(error.code(), "model_unavailable");
+        assert_eq!(error.message(), "Down");
+
+        for code in [None, Some("quota_exceeded"), Some("unknown")] {
+            let error = AppError::from_code(code, "Failed".to_string());
+            assert_eq!(error.code(), "processing_error");
+        }
+    }
+
+    #[test]
+    fn test_multipart_errors() {
+        let code = |e: MultipartError| AppError::from(e).code();
+        assert_eq!(
+            code(MultipartError::NoContentType),
+            "unsupported_media_type"
+        );
+        assert_eq!(
+            code(MultipartError::ParseContentType),
+            "unsupported_media_type"
+        );
+        assert_eq!(
+            code(MultipartError::Payload(PayloadError::Overflow)),
+            "payload_too_large"
+        );
+        assert_eq!(code(MultipartError::Boundary), "invalid_input");
+        assert_eq!(code(MultipartError::Incomplete), "invalid_input");
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Page {
+        #[allow(dead_code)]
+        limit: u32,
+    }
+
+    #[actix_web::test]
+    async fn test_extractor_errors() {
+        let app = test::init_service(
+            App::new()
+                .app_data(path_config())
+                .app_data(query_config())
+                .app_data(json_config().limit(64))
+                .route(
+                    "/{id}",
+                    web::get()
+                        .to(|_: web::Path<Uuid>, _: web::Query<Page>| async { HttpResponse::Ok() }),
+                )
+                .route(
+                    "/",
+                    web::post().to(|_: web::Json<Value>| async { HttpResponse::Ok() }),
+                ),
+        )
+        .await;
+        let app = &app;
+        let call = move |request: test::TestRequest| async move {
+            let response = test::call_service(app, request.to_request()).await;
+            let status = response.status();
+            let body: Value = test::read_body_json(response).await;
+            (status, body)
+        };
+        let id = Uuid::new_v4();
+
+        let (status, body) = call(test::TestRequest::get().uri("/not-a-uuid?limit=1")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_input");
+        assert_eq!(body["details"]["source"], "path");
+
+        let uri = format!("/{}?limit=many", id);
+        let (status, body) = call(test::TestRequest::get().uri(&uri)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["source"], "query");
+
+        let (status, body) = call(
+            test::TestRequest::post()
+                .uri("/")
+                .insert_header(("content-type", "application/json"))
+                .set_payload("{not json"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["source"], "body");
+
+        let (status, body) = call(
+            test::TestRequest::post()
+                .uri("/")
+                .insert_header(("content-type", "text/plain"))
+                .set_payload("{}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["code"], "unsupported_media_type");
+
+        let (status, body) = call(
+            test::TestRequest::post()
+                .uri("/")
+                .set_json(json!({ "padding": "x".repeat(128) })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], "payload_too_large");
     }
 }
@@ -0,0 +1,398 @@
+use crate::dataset::{self, Sample};
+use crate::lstm::{self, calibrate_drive_score, DRIVE_SCORE_MAX, RISK_HEAD};
+use crate::risk::{self, RiskEngine};
+use crate::smoothing::Smoother;
+use crate::types::RiskFactorType;
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+const CALIBRATION_BINS: usize = 10;
+
+#[derive(Debug, Args)]
+pub struct EvalArgs {
+    /// Directory of `<video>/frame_*.json` annotations from prepare.py
+    #[arg(long, default_value = "../../data/annotations")]
+    pub annotations: PathBuf,
+    /// CSV of `video,label` rows with drive labels on the 0–5 scale
+    #[arg(long)]
+    pub labels: PathBuf,
+    /// CSV of `video,factor_type` rows naming the risks a human saw in each video
+    #[arg(long)]
+    pub events: Option<PathBuf>,
+    /// `tch` or `onnx`
+    #[arg(long, default_value = "tch")]
+    pub backend: String,
+    /// Weights (`.ot`, `.npz`) or ONNX model to evaluate
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Report path without extension; `.json` and `.md` are written
+    #[arg(long, default_value = "eval_report")]
+    pub report: PathBuf,
+    /// Evaluate every labelled video instead of the held-out split
+    #[arg(long)]
+    pub all: bool,
+    /// Must match the `train` run so the held-out split is the same
+    #[arg(long, default_value_t = 0.15)]
+    pub val_split: f32,
+    #[arg(long, default_value_t = 200)]
+    pub max_frames: usize,
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EvalReport {
+    pub backend: String,
+    pub model: Option<String>,
+    pub videos: usize,
+    pub failed_videos: Vec<String>,
+    pub regression: RegressionMetrics,
+    pub risk_factors: Vec<RiskFactorMetrics>,
+    pub calibration: Vec<CalibrationBin>,
+}
+
+/// Drive score errors on the 0–5 label scale.
+#[derive(Debug, Serialize)]
+pub struct RegressionMetrics {
+    pub mae: f32,
+    pub rmse: f32,
+    pub r2: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RiskFactorMetrics {
+    pub factor_type: RiskFactorType,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub precision: Option<f32>,
+    pub recall: Option<f32>,
+}
+
+/// One reliability bin over the calibrated 0–100 score.
+#[derive(Debug, Serialize)]
+pub struct CalibrationBin {
+    pub lower: f32,
+    pub upper: f32,
+    pub count: usize,
+    pub mean_predicted: Option<f32>,
+    pub mean_actual: Option<f32>,
+}
+
+struct Prediction {
+    label: f32,
+    drive_score: f32,
+    risks: HashSet<RiskFactorType>,
+    expected_risks: HashSet<RiskFactorType>,
+}
+
+pub fn run(args: &EvalArgs) -> Result<()> {
+    let samples = dataset::load_samples(
+        &args.annotations,
+        &args.labels,
+        args.max_frames,
+        &Smoother::from_env(),
+    )?;
+    let samples = if args.all {
+        samples
+    } else {
+        dataset::split(samples, args.val_split, args.seed).1
+    };
+    if samples.is_empty() {
+        anyhow::bail!("No labelled videos to evaluate");
+    }
+    let events = match &args.events {
+        Some(path) => load_events(path)?,
+        None => HashMap::new(),
+    };
+
+    let model = lstm::load_backend(&args.backend, args.model.as_deref())?;
+    let risk_engine = RiskEngine::from_env()?;
+    let mut predictions = Vec::with_capacity(samples.len());
+    let mut failed_videos = Vec::new();
+    for Sample {
+        video,
+        frames,
+        label,
+    } in samples
+    {
+        let heads = match model.infer(&frames) {
+            Ok(heads) => heads,
+            Err(e) => {
+                error!("Failed to evaluate {}: {}", video, e);
+                failed_videos.push(video);
+                continue;
+            }
+        };
+        let output = lstm::interpret_heads(&frames, &heads);
+        let risks = risk::merge(output.risk_factors, risk_engine.evaluate(&frames));
+        predictions.push(Prediction {
+            label,
+            drive_score: heads.drive_score,
+            risks: risks.into_iter().map(|r| r.factor_type).collect(),
+            expected_risks: events.get(&video).cloned().unwrap_or_default(),
+        });
+    }
+    if predictions.is_empty() {
+        anyhow::bail!("Every video failed to evaluate");
+    }
+
+    let report = EvalReport {
+        backend: model.name().to_string(),
+        model: args.model.clone(),
+        videos: predictions.len(),
+        failed_videos,
+        regression: regression_metrics(&predictions),
+        risk_factors: risk_factor_metrics(&predictions),
+        calibration: calibration_table(&predictions),
+    };
+
+    let json_path = args.report.with_extension("json");
+    let markdown_path = args.report.with_extension("md");
+    fs::write(&json_path, serde_json::to_string_pretty(&report)?)
+        .with_context(|| format!("Failed to write {}", json_path.display()))?;
+    fs::write(&markdown_path, render_markdown(&report))
+        .with_context(|| format!("Failed to write {}", markdown_path.display()))?;
+    info!(
+        "MAE {:.3}, RMSE {:.3}; report written to {} and {}",
+        report.regression.mae,
+        report.regression.rmse,
+        json_path.display(),
+        markdown_path.display()
+    );
+    Ok(())
+}
+
+/// Reads `video,factor_type` rows; factor types use the `RiskFactorType` variant names.
+fn load_events(path: &Path) -> Result<HashMap<String, HashSet<RiskFactorType>>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read events from {}", path.display()))?;
+    let mut events: HashMap<String, HashSet<RiskFactorType>> = HashMap::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (idx == 0 && line.starts_with("video,")) {
+            continue;
+        }
+        let (video, factor) = line.split_once(',').with_context(|| {
+            format!("{}:{}: expected video,factor_type", path.display(), idx + 1)
+        })?;
+        let factor = factor.trim();
+        let factor_type = serde_json::from_value(serde_json::Value::String(factor.to_string()))
+            .unwrap_or_else(|_| RiskFactorType::Other(factor.to_string()));
+        events
+            .entry(video.trim().to_string())
+            .or_default()
+            .insert(factor_type);
+    }
+
+    Ok(events)
+}
+
+fn regression_metrics(predictions: &[Prediction]) -> RegressionMetrics {
+    let n = predictions.len() as f32;
+    let errors: Vec<f32> = predictions
+        .iter()
+        .map(|p| p.drive_score - p.label)
+        .collect();
+    let mae = errors.iter().map(|e| e.abs()).sum::<f32>() / n;
+    let sse = errors.iter().map(|e| e.powi(2)).sum::<f32>();
+
+    let mean_label = predictions.iter().map(|p| p.label).sum::<f32>() / n;
+    let sst = predictions
+        .iter()
+        .map(|p| (p.label - mean_label).powi(2))
+        .sum::<f32>();
+
+    RegressionMetrics {
+        mae,
+        rmse: (sse / n).sqrt(),
+        r2: (sst > 0.0).then(|| 1.0 - sse / sst),
+    }
+}
+
+fn risk_factor_metrics(predictions: &[Prediction]) -> Vec<RiskFactorMetrics> {
+    let mut factor_types: Vec<RiskFactorType> = RISK_HEAD.to_vec();
+    for prediction in predictions {
+        for factor_type in prediction.risks.iter().chain(&prediction.expected_risks) {
+            if !factor_types.contains(factor_type) {
+                factor_types.push(factor_type.clone());
+            }
+        }
+    }
+
+    factor_types
+        .into_iter()
+        .map(|factor_type| {
+            let (mut tp, mut fp, mut fn_) = (0, 0, 0);
+            for prediction in predictions {
+                let predicted = prediction.risks.contains(&factor_type);
+                let expected = prediction.expected_risks.contains(&factor_type);
+                match (predicted, expected) {
+                    (true, true) => tp += 1,
+                    (true, false) => fp += 1,
+                    (false, true) => fn_ += 1,
+                    (false, false) => {}
+                }
+            }
+            RiskFactorMetrics {
+                factor_type,
+                true_positives: tp,
+                false_positives: fp,
+                false_negatives: fn_,
+                precision: (tp + fp > 0).then(|| tp as f32 / (tp + fp) as f32),
+                recall: (tp + fn_ > 0).then(|| tp as f32 / (tp + fn_) as f32),
+            }
+        })
+        .collect()
+}
+
+/// Bins calibrated predictions and compares them with labels on the same 0–100 scale.
+fn calibration_table(predictions: &[Prediction]) -> Vec<CalibrationBin> {
+    let width = 100.0 / CALIBRATION_BINS as f32;
+    let mut bins: Vec<(usize, f32, f32)> = vec![(0, 0.0, 0.0); CALIBRATION_BINS];
+
+    for prediction in predictions {
+        let predicted = calibrate_drive_score(prediction.drive_score);
+        let actual = prediction.label.clamp(0.0, DRIVE_SCORE_MAX) / DRIVE_SCORE_MAX * 100.0;
+        let idx = ((predicted / width) as usize).min(CALIBRATION_BINS - 1);
+        bins[idx].0 += 1;
+        bins[idx].1 += predicted;
+        bins[idx].2 += actual;
+    }
+
+    bins.into_iter()
+        .enumerate()
+        .map(|(idx, (count, predicted, actual))| CalibrationBin {
+            lower: idx as f32 * width,
+            upper: (idx + 1) as f32 * width,
+            count,
+            mean_predicted: (count > 0).then(|| predicted / count as f32),
+            mean_actual: (count > 0).then(|| actual / count as f32),
+        })
+        .collect()
+}
+
+fn render_markdown(report: &EvalReport) -> String {
+    let fmt = |value: Option<f32>| value.map_or("–".to_string(), |v| format!("{:.3}", v));
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# Sequence model evaluation\n");
+    let _ = writeln!(
+        out,
+        "Backend `{}`, model `{}`, {} videos ({} failed).\n",
+        report.backend,
+        report.model.as_deref().unwrap_or("untrained"),
+        report.videos,
+        report.failed_videos.len()
+    );
+
+    let _ = writeln!(out, "## Drive score (0–5)\n");
+    let _ = writeln!(out, "| MAE | RMSE | R² |\n|---|---|---|");
+    let _ = writeln!(
+        out,
+        "| {:.3} | {:.3} | {} |\n",
+        report.regression.mae,
+        report.regression.rmse,
+        fmt(report.regression.r2)
+    );
+
+    let _ = writeln!(out, "## Risk factors\n");
+    let _ = writeln!(out, "| Factor | TP | FP | FN | Precision | Recall |");
+    let _ = writeln!(out, "|---|---|---|---|---|---|");
+    for metrics in &report.risk_factors {
+        let _ = writeln!(
+            out,
+            "| {:?} | {} | {} | {} | {} | {} |",
+            metrics.factor_type,
+            metrics.true_positives,
+            metrics.false_positives,
+            metrics.false_negatives,
+            fmt(metrics.precision),
+            fmt(metrics.recall)
+        );
+    }
+
+    let _ = writeln!(out, "\n## Calibration (0–100)\n");
+    let _ = writeln!(out, "| Bin | Count | Mean predicted | Mean actual |");
+    let _ = writeln!(out, "|---|---|---|---|");
+    for bin in &report.calibration {
+        let _ = writeln!(
+            out,
+            "| {:.0}–{:.0} | {} | {} | {} |",
+            bin.lower,
+            bin.upper,
+            bin.count,
+            fmt(bin.mean_predicted),
+            fmt(bin.mean_actual)
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prediction(label: f32, drive_score: f32) -> Prediction {
+        Prediction {
+            label,
+            drive_score,
+            risks: HashSet::new(),
+            expected_risks: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_regression_metrics() {
+        let predictions = vec![
+            prediction(1.0, 2.0),
+            prediction(3.0, 3.0),
+            prediction(5.0, 4.0),
+        ];
+        let metrics = regression_metrics(&predictions);
+        assert!((metrics.mae - 2.0 / 3.0).abs() < 1e-6);
+        assert!((metrics.rmse - (2.0f32 / 3.0).sqrt()).abs() < 1e-6);
+        assert!((metrics.r2.unwrap() - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_risk_precision_recall() {
+        let mut hit = prediction(3.0, 3.0);
+        hit.risks.insert(RiskFactorType::LaneDeviation);
+        hit.expected_risks.insert(RiskFactorType::LaneDeviation);
+        let mut false_alarm = prediction(3.0, 3.0);
+        false_alarm.risks.insert(RiskFactorType::LaneDeviation);
+        let mut missed = prediction(3.0, 3.0);
+        missed.expected_risks.insert(RiskFactorType::LaneDeviation);
+
+        let metrics = risk_factor_metrics(&[hit, false_alarm, missed]);
+        let lane = metrics
+            .iter()
+            .find(|m| m.factor_type == RiskFactorType::LaneDeviation)
+            .unwrap();
+        assert_eq!(lane.precision, Some(0.5));
+        assert_eq!(lane.recall, Some(0.5));
+    }
+
+    #[test]
+    fn test_calibration_bins() {
+        let predictions = vec![
+            prediction(5.0, 4.9),
+            prediction(0.0, 0.1),
+            prediction(5.0, 9.0),
+        ];
+        let bins = calibration_table(&predictions);
+        assert_eq!(bins.len(), CALIBRATION_BINS);
+        assert_eq!(bins[0].count, 1);
+        assert_eq!(bins[CALIBRATION_BINS - 1].count, 2);
+        assert_eq!(bins[CALIBRATION_BINS - 1].mean_actual, Some(100.0));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::types::{CriticalEvent, FrameAnalysis, SafetyStatus, SignalColor};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Episodes of the same event separated by at most this many seconds are merged.
+const DEFAULT_MERGE_GAP: f64 = 2.0;
+
+type Severity = fn(&FrameAnalysis) -> Option<f32>;
+type Context = fn(&FrameAnalysis) -> Vec<(&'static str, String)>;
+
+/// A discrete event over the frame timeline.
+///
+/// `severity` returns `Some` for frames that are part of the event; the
+/// event's severity is the peak over its frames and its context is taken
+/// from the frame where that peak occurs.
+struct EventSpec {
+    event_type: &'static str,
+    min_frames: usize,
+    severity: Severity,
+    context: Context,
+}
+
+pub struct EventDetector {
+    specs: Vec<EventSpec>,
+    merge_gap: f64,
+}
+
+impl EventDetector {
+    pub fn new(merge_gap: f64) -> Self {
+        Self {
+            specs: builtin_events(),
+            merge_gap,
+        }
+    }
+
+    /// Detected events ordered by start time.
+    pub fn detect(&self, frames: &[FrameAnalysis]) -> Vec<CriticalEvent> {
+        let mut events: Vec<CriticalEvent> = self
+            .specs
+            .iter()
+            .flat_map(|spec| {
+                self.episodes(spec, frames)
+                    .into_iter()
+                    .map(move |episode| build_event(spec, &frames[episode]))
+            })
+            .collect();
+        events.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        events
+    }
+
+    /// Runs of matching frames, with runs closer than `merge_gap` joined.
+    fn episodes(&self, spec: &EventSpec, frames: &[FrameAnalysis]) -> Vec<Range<usize>> {
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        let mut start = None;
+
+        for idx in 0..=frames.len() {
+            let matches = idx < frames.len() && (spec.severity)(&frames[idx]).is_some();
+            match (matches, start) {
+                (true, None) => start = Some(idx),
+                (false, Some(first)) => {
+                    let run = first..idx;
+                    match runs.last_mut() {
+                        Some(previous)
+                            if frames[run.start].timestamp - frames[previous.end - 1].timestamp
+                                <= self.merge_gap =>
+                        {
+                            previous.end = run.end
+                        }
+                        _ => runs.push(run),
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        runs.retain(|run| {
+            let matching = frames[run.clone()]
+                .iter()
+                .filter(|f| (spec.severity)(f).is_some())
+                .count();
+            matching >= spec.min_frames.max(1)
+        });
+        runs
+    }
+}
+
+impl Default for EventDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_MERGE_GAP)
+    }
+}
+
+fn build_event(spec: &EventSpec, episode: &[FrameAnalysis]) -> CriticalEvent {
+    let matching: Vec<(&FrameAnalysis, f32)> = episode
+        .iter()
+        .filter_map(|f| (spec.severity)(f).map(|severity| (f, severity)))
+        .collect();
+    let (peak_frame, severity) = matching
+        .iter()
+        .copied()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("episodes contain a matching frame");
+
+    let mut context: HashMap<String, String> = (spec.context)(peak_frame)
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    context.insert(
+        "peak_frame".to_string(),
+        peak_frame.frame_number.to_string(),
+    );
+
+    let first = &episode[0];
+    let last = &episode[episode.len() - 1];
+    CriticalEvent {
+        event_type: spec.event_type.to_string(),
+        timestamp: first.timestamp,
+        end_timestamp: last.timestamp,
+        severity: severity.clamp(0.0, 1.0),
+        frames: matching.iter().map(|(f, _)| f.frame_number).collect(),
+        context,
+    }
+}
+
+fn builtin_events() -> Vec<EventSpec> {
+    vec![
+        EventSpec {
+            event_type: "red_light_run",
+            min_frames: 1,
+            severity: |f| {
+                let light = &f.signal_compliance.traffic_light;
+                (matches!(light.status, SignalColor::Red) && !light.compliance)
+                    .then_some(0.9 * f.signal_compliance.confidence)
+            },
+            context: |f| {
+                let light = &f.signal_compliance.traffic_light;
+                vec![
+                    ("signal_colour", format!("{:?}", light.status)),
+                    ("distance_meters", format!("{:.1}", light.distance)),
+                ]
+            },
+        },
+        EventSpec {
+            event_type: "rolling_stop",
+            min_frames: 1,
+            severity: |f| {
+                let stop_sign = &f.signal_compliance.stop_sign;
+                (stop_sign.present
+                    && stop_sign.compliance == Some(false)
+                    && !f.intersection_behavior.complete_stop)
+                    .then_some(0.7 * f.intersection_behavior.confidence)
+            },
+            context: |f| {
+                let mut context = vec![(
+                    "stop_line_observance",
+                    f.intersection_behavior.stop_line_observance.to_string(),
+                )];
+                if let Some(duration) = f.signal_compliance.stop_sign.stop_duration {
+                    context.push(("stop_duration", format!("{:.1}", duration)));
+                }
+                context
+            },
+        },
+        EventSpec {
+            event_type: "tailgating",
+            min_frames: 2,
+            severity: |f| {
+                let following = &f.following_distance;
+                matches!(following.safe_distance, SafetyStatus::Unsafe)
+                    .then_some(0.7 * following.confidence)
+            },
+            context: |f| {
+                let following = &f.following_distance;
+                vec![
+                    (
+                        "distance_meters",
+                        format!("{:.1}", following.distance_meters),
+                    ),
+                    (
+                        "time_to_collision",
+                        format!("{:.1}", following.time_to_collision),
+                    ),
+                ]
+            },
+        },
+        EventSpec {
+            event_type: "unsafe_lane_change",
+            min_frames: 1,
+            severity: |f| {
+                let merging = &f.merging_lane_change;
+                (merging.in_progress && !merging.safe_merging).then_some(0.7 * merging.confidence)
+            },
+            context: |f| {
+                let merging = &f.merging_lane_change;
+                vec![
+                    ("signal_used", merging.signal_used.to_string()),
+                    ("blind_spot_check", merging.blind_spot_check.to_string()),
+                ]
+            },
+        },
+        EventSpec {
+            event_type: "pedestrian_not_yielded",
+            min_frames: 1,
+            severity: |f| {
+                let pedestrian = &f.pedestrian_yielding;
+                (pedestrian.pedestrian_present && !pedestrian.proper_yielding)
+                    .then_some(0.9 * pedestrian.confidence)
+            },
+            context: |f| match f.pedestrian_yielding.distance_to_pedestrian {
+                Some(distance) => vec![("distance_meters", format!("{:.1}", distance))],
+                None => Vec::new(),
+            },
+        },
+        EventSpec {
+            event_type: "shoulder_driving",
+            min_frames: 2,
+            severity: |f| {
+                let shoulder = &f.shoulder_use;
+                (shoulder.using_shoulder && !shoulder.emergency_situation)
+                    .then_some(0.5 * shoulder.confidence)
+            },
+            context: |f| {
+                vec![(
+                    "emergency_situation",
+                    f.shoulder_use.emergency_situation.to_string(),
+                )]
+            },
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::timeline;
+
+    #[test]
+    fn test_red_light_run() {
+        let mut frames = timeline(6);
+        for frame in &mut frames[2..4] {
+            frame.signal_compliance.traffic_light.status = SignalColor::Red;
+            frame.signal_compliance.traffic_light.compliance = false;
+        }
+        frames[3].signal_compliance.traffic_light.distance = 4.0;
+
+        let events = EventDetector::default().detect(&frames);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "red_light_run");
+        assert_eq!(events[0].timestamp, 2.0);
+        assert_eq!(events[0].end_timestamp, 3.0);
+        assert_eq!(events[0].frames, vec![60, 90]);
+        assert_eq!(events[0].context["signal_colour"], "Red");
+    }
+
+    #[test]
+    fn test_adjacent_episodes_merge() {
+        let mut frames = timeline(10);
+        for idx in [1, 2, 4, 5, 9] {
+            frames[idx].following_distance.safe_distance = SafetyStatus::Unsafe;
+        }
+        frames[4].following_distance.confidence = 0.5;
+
+        let events = EventDetector::default().detect(&frames);
+        assert_eq!(events.len(), 1, "frame 9 alone is too short to report");
+        assert_eq!(events[0].event_type, "tailgating");
+        assert_eq!(events[0].timestamp, 1.0);
+        assert_eq!(events[0].end_timestamp, 5.0);
+        assert_eq!(events[0].frames, vec![30, 60, 120, 150]);
+        assert_eq!(events[0].severity, 0.7);
+    }
+
+    #[test]
+    fn test_unsafe_lane_change_needs_merge_in_progress() {
+        let mut frames = timeline(5);
+        frames[3].merging_lane_change.safe_merging = false;
+        assert!(EventDetector::default().detect(&frames).is_empty());
+
+        frames[3].merging_lane_change.in_progress = true;
+        let events = EventDetector::default().detect(&frames);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "unsafe_lane_change");
+    }
+
+    #[test]
+    fn test_compliant_drive_has_no_events() {
+        assert!(EventDetector::default().detect(&timeline(5)).is_empty());
+    }
+}
@@ -2,13 +2,27 @@ use anyhow::Result;
 use reqwest::Client;
 use serde_json::{json, Value};
 use crate::types::FrameData;
-use tracing::{error, info};
-use std::time::Duration;
+use crate::metrics::METRICS;
+use tracing::{error, info, info_span, warn, Instrument};
+use std::time::{Duration, Instant};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use thiserror::Error;
+
+const DEFAULT_MAX_RETRIES: u32 = 2;
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+/// Wait before the first retry; doubled for each one after.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The model server answered, but not with something usable.
+#[derive(Error, Debug)]
+#[error("Invalid model response: {0}")]
+pub struct InvalidResponse(pub String);
 
 pub struct LLMClient {
     client: Client,
     endpoint: String,
+    max_retries: u32,
+    retry_delay: Duration,
 }
 
 impl LLMClient {
@@ -18,6 +32,11 @@ impl LLMClient {
                 .timeout(Duration::from_secs(300))
                 .build()?,
             endpoint: "http://localhost:9997/completion".to_string(),
+            max_retries: std::env::var("VGLNT_LLM_MAX_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+            retry_delay: RETRY_DELAY,
         })
     }
 
@@ -32,10 +51,10 @@ DRIVING_ANALYSIS_SCHEMA = {{
     "lane_centering": {{"following_lane_discipline": bool, "score": float}},
     "following_distance": {{"safe_distance": "safe" | "unsafe", "score": float}},
     "signal_compliance": {{
-        "traffic_light": {{"status": "red" | "yellow" | "green", "compliance": bool, "score": float}},
+        "traffic_light": {{"status": "red" | "yellow" | "green" | "none", "compliance": bool, "score": float}},
         "stop_sign": {{"present": bool, "compliance": bool | "N/A", "score": float}}
     }},
-    "merging_lane_change": {{"safe_merging": bool, "score": float}},
+    "merging_lane_change": {{"in_progress": bool, "safe_merging": bool, "score": float}},
     "pedestrian_yielding": {{"pedestrian_present": bool, "score": float}},
     "intersection_behavior": {{"stop_line_observance": bool, "score": float}},
     "road_sign_awareness": {{
@@ -49,22 +68,13 @@ DRIVING_ANALYSIS_SCHEMA = {{
             frame_number, base64_image
         );
 
-        let response = self.client
-            .post(&self.endpoint)
-            .json(&json!({
-                "prompt": prompt,
-                "max_tokens": 1000,
-                "temperature": 0.1,
-                "stop": ["}}", "\n"],
-                "stream": false
-            }))
-            .send()
-            .await?;
-
-        let resp_json: Value = response.json().await?;
-        let content = resp_json["content"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid response format"))?;
+        let content = self.complete("frame", json!({
+            "prompt": prompt,
+            "max_tokens": 1000,
+            "temperature": 0.1,
+            "stop": ["}}", "\n"],
+            "stream": false
+        })).await?;
 
         let cleaned_content = content
             .trim()
@@ -73,12 +83,84 @@ DRIVING_ANALYSIS_SCHEMA = {{
         let analysis: FrameData = serde_json::from_str(cleaned_content)
             .map_err(|e| {
                 error!("Failed to parse LLM response: {}", cleaned_content);
-                anyhow::anyhow!("JSON parse error: {}", e)
+                METRICS.llm_parse_failures.inc();
+                METRICS.llm_errors.with_label_values(&["frame", "parse"]).inc();
+                InvalidResponse(format!("JSON parse error: {}", e))
             })?;
 
         Ok(analysis)
     }
 
+    /// Text-only completion, used for prose that does not need the image.
+    pub async fn complete_text(&self, prompt: &str) -> Result<String> {
+        let content = self.complete("text", json!({
+            "prompt": prompt,
+            "max_tokens": 300,
+            "temperature": 0.3,
+            "stream": false
+        })).await?;
+
+        Ok(content.trim().to_string())
+    }
+
+    /// Checks that the model server is up and has finished loading its model.
+    pub async fn health(&self) -> Result<()> {
+        let mut url = reqwest::Url::parse(&self.endpoint)?;
+        url.set_path("/health");
+        self.client
+            .get(url)
+            .timeout(HEALTH_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Posts a completion request, timing each attempt and counting failures
+    /// by kind. Timeouts, connection failures and 429 or 5xx answers are
+    /// retried up to `max_retries` times with exponential backoff.
+    async fn complete(&self, operation: &str, body: Value) -> Result<String> {
+        let mut retry = 0;
+        loop {
+            let started = Instant::now();
+            let span = info_span!("llm_request", operation, attempt = retry + 1);
+            let result = self.request(&body).instrument(span).await;
+            METRICS.llm_request_seconds
+                .with_label_values(&[operation])
+                .observe(started.elapsed().as_secs_f64());
+
+            let e = match result {
+                Ok(content) => return Ok(content),
+                Err(e) => e,
+            };
+            METRICS.llm_errors.with_label_values(&[operation, error_kind(&e)]).inc();
+            let reason = match retry_reason(&e) {
+                Some(reason) if retry < self.max_retries => reason,
+                _ => return Err(e),
+            };
+            let delay = self.retry_delay.saturating_mul(2u32.saturating_pow(retry));
+            warn!("LLM request failed ({}), retrying in {:?}", e, delay);
+            METRICS.llm_retries.with_label_values(&[reason]).inc();
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+
+    async fn request(&self, body: &Value) -> Result<String> {
+        let response = self.client
+            .post(&self.endpoint)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut resp_json: Value = response.json().await?;
+        match resp_json["content"].take() {
+            Value::String(content) => Ok(content),
+            _ => Err(InvalidResponse("missing content".to_string()).into()),
+        }
+    }
+
     pub async fn process_batch(&self, frames: Vec<(&[u8], u32)>) -> Result<Vec<FrameData>> {
         let mut results = Vec::with_capacity(frames.len());
         
@@ -95,4 +177,90 @@ DRIVING_ANALYSIS_SCHEMA = {{
 
         Ok(results)
     }
-}
\ No newline at end of file
+}
+
+/// The `reason` label of a transient failure worth retrying; `None` otherwise.
+fn retry_reason(e: &anyhow::Error) -> Option<&'static str> {
+    let e = e.downcast_ref::<reqwest::Error>()?;
+    match e.status() {
+        _ if e.is_timeout() => Some("timeout"),
+        _ if e.is_connect() => Some("connect"),
+        Some(status) if status.as_u16() == 429 => Some("rate_limited"),
+        Some(status) if status.is_server_error() => Some("server_error"),
+        _ => None,
+    }
+}
+
+/// The `kind` label of a failed request.
+fn error_kind(e: &anyhow::Error) -> &'static str {
+    match e.downcast_ref::<reqwest::Error>() {
+        Some(e) if e.is_timeout() => "timeout",
+        Some(e) if e.is_connect() => "connect",
+        Some(e) if e.is_status() => "status",
+        Some(_) => "request",
+        None => "invalid",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// A model server on a local port that answers 503 to the first
+    /// `failures` requests and `content` after them. Returns its endpoint and
+    /// how many requests it has had.
+    fn model_server(failures: u32, content: &'static str) -> (String, Arc<AtomicU32>) {
+        let requests = Arc::new(AtomicU32::new(0));
+        let seen = Arc::clone(&requests);
+        let handler = move || {
+            let request = seen.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if request < failures {
+                    HttpResponse::ServiceUnavailable().finish()
+                } else {
+                    HttpResponse::Ok().json(json!({ "content": content }))
+                }
+            }
+        };
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/completion", listener.local_addr().unwrap());
+        let server = HttpServer::new(move || App::new().default_service(web::to(handler.clone())))
+            .workers(1)
+            .disable_signals()
+            .listen(listener)
+            .unwrap()
+            .run();
+        actix_web::rt::spawn(server);
+        (endpoint, requests)
+    }
+
+    fn client(endpoint: String, max_retries: u32) -> LLMClient {
+        LLMClient {
+            client: Client::new(),
+            endpoint,
+            max_retries,
+            retry_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_retries_transient_errors() {
+        let retries = || METRICS.llm_retries.with_label_values(&["server_error"]).get();
+        let before = retries();
+        let (endpoint, requests) = model_server(1, " A calm drive ");
+        let content = client(endpoint, 2).complete_text("Summarize").await.unwrap();
+        assert_eq!(content, "A calm drive");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(retries() - before, 1);
+
+        // Gives up once the retries are spent
+        let (endpoint, requests) = model_server(u32::MAX, "");
+        let error = client(endpoint, 1).complete_text("Summarize").await.unwrap_err();
+        assert_eq!(retry_reason(&error), Some("server_error"));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,126 @@
+//! Subscriber setup and HTTP request spans.
+//!
+//! `RUST_LOG` filters as usual (default `info`), `VGLNT_LOG_FORMAT=json`
+//! switches to one JSON object per line with the enclosing spans, and
+//! `VGLNT_OTLP_ENDPOINT` (e.g. `http://localhost:4317`) also exports spans to
+//! an OpenTelemetry collector over gRPC.
+
+use crate::api::request_id;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{Config, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{info, info_span, Instrument};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+const SERVICE_NAME: &str = "vglnt-server";
+
+/// Flushes exported spans when dropped; keep it alive until exit.
+pub struct Guard {
+    provider: Option<TracerProvider>,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            for result in provider.force_flush() {
+                if let Err(e) = result {
+                    eprintln!("Failed to flush spans: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Installs the global subscriber. Must run inside the Tokio runtime when
+/// OTLP export is enabled.
+pub fn init() -> Result<Guard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let json = std::env::var("VGLNT_LOG_FORMAT").is_ok_and(|v| v == "json");
+    let output = if json {
+        fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed()
+    } else {
+        fmt::layer().boxed()
+    };
+
+    let provider = match std::env::var("VGLNT_OTLP_ENDPOINT") {
+        Ok(endpoint) if !endpoint.is_empty() => Some(otlp_provider(&endpoint)?),
+        _ => None,
+    };
+    let otlp = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output)
+        .with(otlp)
+        .try_init()
+        .context("Failed to install the tracing subscriber")?;
+    Ok(Guard { provider })
+}
+
+fn otlp_provider(endpoint: &str) -> Result<TracerProvider> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            Config::default().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                SERVICE_NAME,
+            )])),
+        )
+        .install_batch(runtime::Tokio)
+        .with_context(|| format!("Failed to set up OTLP export to {}", endpoint))
+}
+
+/// Middleware running each request in an `http_request` span that carries
+/// its request id, route pattern and status. Wrap it inside the request id
+/// middleware so the id is set.
+pub async fn trace_http(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let span = info_span!(
+        "http_request",
+        method = %req.method(),
+        path = req.path(),
+        request_id = %request_id::current().unwrap_or_default(),
+        route = Empty,
+        status = Empty,
+    );
+    if let Some(route) = req.match_pattern() {
+        span.record("route", route.as_str());
+    }
+    let started = Instant::now();
+    let result = next.call(req).instrument(span.clone()).await;
+
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    span.record("status", status.as_u16());
+    info!(
+        parent: &span,
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "Request finished"
+    );
+    result
+}
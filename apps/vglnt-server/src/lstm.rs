@@ -1,102 +1,458 @@
+use crate::onnx::OnnxModel;
 use crate::types::{
-    BehavioralMetrics, FrameAnalysis, LSTMOutput, RiskFactor, RiskFactorType, RiskLevel,
-    TemporalPattern,
+    BehavioralMetrics, FrameAnalysis, LSTMOutput, RiskFactor, RiskFactorType, RiskSource,
+    SafetyStatus, SignalColor, TemporalPattern,
 };
 use anyhow::{Context, Result};
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tch::nn::{Module, RNN};
 use tch::{nn, Device, Kind, Tensor};
-use tracing::{error, info};
 
-const INPUT_SIZE: i64 = 8;
-const HIDDEN_SIZE: i64 = 128;
-const NUM_LAYERS: i64 = 2;
-const SEQUENCE_LENGTH: i64 = 30;
+// Matches `extract_features` in vglnt-lstm-py/main.py, one value per entry.
+pub const INPUT_SIZE: i64 = 30;
+// Stacked LSTM widths and dense widths of the Keras sequence-to-one model.
+const LSTM_SIZES: [i64; 3] = [512, 256, 128];
+const DENSE_SIZES: [i64; 2] = [128, 64];
+const DROPOUT: f64 = 0.5;
+const FRAMES_PER_SECOND: f32 = 30.0;
+
+// Output heads. The per-timestep heads read every output of the last LSTM
+// layer; `fc2` emits `HEAD_WIDTH` values laid out as [risk logits | pattern
+// logits]. The score head reads only the final unpadded timestep.
+
+/// Risk head, one logit per category. Sigmoid gives the probability in
+/// `[0, 1]` that the category is a risk at that timestep.
+pub const RISK_HEAD: [RiskFactorType; 7] = [
+    RiskFactorType::LaneDeviation,
+    RiskFactorType::FollowingDistance,
+    RiskFactorType::SpeedControl,
+    RiskFactorType::SignalCompliance,
+    RiskFactorType::PedestrianAwareness,
+    RiskFactorType::IntersectionBehavior,
+    RiskFactorType::MergingTechnique,
+];
+
+/// Pattern head, one logit per behaviour. Sigmoid gives the lapse intensity
+/// in `[0, 1]` for that behaviour at that timestep (1.0 = full lapse).
+pub const PATTERN_HEAD: [&str; 5] = [
+    "Lane Discipline",
+    "Following Distance",
+    "Signal Compliance",
+    "Merging Behavior",
+    "Pedestrian Awareness",
+];
+
+/// Score head, the Keras regression output: a single linear value on the
+/// training label scale `[0, DRIVE_SCORE_MAX]`, see `calibrate_drive_score`.
+pub const SCORE_HEAD: &str = "Overall Safety";
+
+/// Upper end of the drive label used by the Python trainer (5 = safest).
+pub const DRIVE_SCORE_MAX: f32 = 5.0;
+
+pub(crate) const RISK_OFFSET: i64 = 0;
+pub(crate) const PATTERN_OFFSET: i64 = RISK_OFFSET + RISK_HEAD.len() as i64;
+pub(crate) const HEAD_WIDTH: i64 = PATTERN_OFFSET + PATTERN_HEAD.len() as i64;
+
+const RISK_THRESHOLD: f32 = 0.5;
+const PATTERN_THRESHOLD: f32 = 0.7;
+const PATTERN_MAX_VARIANCE: f32 = 0.1;
+
+/// Per-timestep head activations after the sigmoid, copied off the device.
+#[derive(Debug, Clone)]
+pub struct HeadOutputs {
+    /// `risk[t][i]` is the probability for `RISK_HEAD[i]` at timestep `t`.
+    pub risk: Vec<Vec<f32>>,
+    /// `pattern[t][i]` is the lapse intensity for `PATTERN_HEAD[i]` at timestep `t`.
+    pub pattern: Vec<Vec<f32>>,
+    /// Raw score head output on the `[0, DRIVE_SCORE_MAX]` label scale.
+    pub drive_score: f32,
+}
+
+/// Maps the 0–5 drive label onto the API's 0–100 `overall_safety_score`.
+///
+/// The calibration is linear: the regressor is clamped to the label range
+/// it was trained on and scaled so that 0 → 0 and 5 → 100.
+pub fn calibrate_drive_score(raw: f32) -> f32 {
+    (raw.clamp(0.0, DRIVE_SCORE_MAX) / DRIVE_SCORE_MAX) * 100.0
+}
+
+/// A sequence model that turns a frame timeline into head activations.
+///
+/// Backends only run inference; interpreting the heads into an `LSTMOutput`
+/// is shared so every backend reports risks and patterns the same way.
+/// Backends without per-timestep heads return empty `risk`/`pattern` rows.
+pub trait SequenceBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// False while running on untrained weights.
+    fn weights_loaded(&self) -> bool {
+        true
+    }
+
+    /// False when risks and patterns have no trained per-timestep heads behind
+    /// them, so only the drive score is reported.
+    fn heads_loaded(&self) -> bool {
+        true
+    }
+
+    fn infer(&self, analyses: &[FrameAnalysis]) -> Result<HeadOutputs>;
+
+    fn process_sequence(&self, analyses: &[FrameAnalysis]) -> Result<LSTMOutput> {
+        if analyses.is_empty() {
+            anyhow::bail!("Cannot process an empty frame sequence");
+        }
+        Ok(interpret_heads(analyses, &self.infer(analyses)?))
+    }
+}
+
+pub fn interpret_heads(analyses: &[FrameAnalysis], heads: &HeadOutputs) -> LSTMOutput {
+    LSTMOutput {
+        overall_safety_score: calibrate_drive_score(heads.drive_score),
+        risk_factors: analyze_risks(analyses, heads),
+        temporal_patterns: detect_patterns(heads),
+        behavioral_metrics: calculate_metrics(heads),
+    }
+}
+
+/// Builds the backend named by `VGLNT_MODEL_BACKEND` (`tch` or `onnx`),
+/// loading weights from `VGLNT_MODEL_PATH` when set.
+pub fn backend_from_env() -> Result<Box<dyn SequenceBackend>> {
+    let backend = std::env::var("VGLNT_MODEL_BACKEND").unwrap_or_else(|_| "tch".to_string());
+    let model_path = std::env::var("VGLNT_MODEL_PATH").ok();
+    load_backend(&backend, model_path.as_deref())
+}
+
+pub fn load_backend(backend: &str, model_path: Option<&str>) -> Result<Box<dyn SequenceBackend>> {
+    match backend {
+        "tch" => {
+            let mut model = LSTMModel::new()?;
+            if let Some(path) = model_path {
+                model.load_weights(path)?;
+            }
+            Ok(Box::new(model))
+        }
+        "onnx" => {
+            let path = model_path
+                .context("A model path to an .onnx file is required for the onnx backend")?;
+            Ok(Box::new(OnnxModel::load(Path::new(path))?))
+        }
+        other => anyhow::bail!("Unknown model backend: {}", other),
+    }
+}
+
+/// Per-feature standardisation exported from the trainer's `StandardScaler`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureScaler {
+    pub mean: Vec<f32>,
+    pub scale: Vec<f32>,
+    /// Set by a trainer that fitted the per-timestep heads of the checkpoint
+    /// this scaler sits next to. Absent from Keras exports.
+    #[serde(default)]
+    pub heads_trained: bool,
+}
+
+impl FeatureScaler {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scaler from {}", path.display()))?;
+        let scaler: Self = serde_json::from_str(&contents)?;
+        if scaler.mean.len() != INPUT_SIZE as usize || scaler.scale.len() != INPUT_SIZE as usize {
+            anyhow::bail!(
+                "Scaler has {} means and {} scales, expected {}",
+                scaler.mean.len(),
+                scaler.scale.len(),
+                INPUT_SIZE
+            );
+        }
+        Ok(scaler)
+    }
+
+    /// Fits per-feature mean and population standard deviation, matching
+    /// sklearn's `StandardScaler`, over the given frames.
+    pub fn fit<'a>(frames: impl IntoIterator<Item = &'a FrameAnalysis>) -> Self {
+        let width = INPUT_SIZE as usize;
+        let mut sum = vec![0f64; width];
+        let mut sum_sq = vec![0f64; width];
+        let mut count = 0usize;
+
+        for frame in frames {
+            for (idx, value) in frame_features(frame).into_iter().enumerate() {
+                sum[idx] += value as f64;
+                sum_sq[idx] += (value as f64).powi(2);
+            }
+            count += 1;
+        }
+
+        let n = count.max(1) as f64;
+        let mean: Vec<f32> = sum.iter().map(|s| (s / n) as f32).collect();
+        let scale = sum
+            .iter()
+            .zip(&sum_sq)
+            .map(|(s, sq)| {
+                let std = (sq / n - (s / n).powi(2)).max(0.0).sqrt();
+                if std == 0.0 {
+                    1.0
+                } else {
+                    std as f32
+                }
+            })
+            .collect();
+
+        Self {
+            mean,
+            scale,
+            heads_trained: false,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write scaler to {}", path.display()))
+    }
+
+    pub fn apply(&self, features: &mut [f32]) {
+        for (idx, value) in features.iter_mut().enumerate() {
+            let scale = self.scale[idx];
+            *value = (*value - self.mean[idx]) / if scale == 0.0 { 1.0 } else { scale };
+        }
+    }
+}
 
 pub struct LSTMModel {
     vs: nn::VarStore,
-    lstm: nn::LSTM,
+    lstm_layers: Vec<nn::LSTM>,
+    dense_layers: Vec<nn::Linear>,
+    score_head: nn::Linear,
     fc1: nn::Linear,
     fc2: nn::Linear,
+    scaler: Option<FeatureScaler>,
     device: Device,
+    weights_loaded: bool,
+    /// False until `fc1`/`fc2` come from a checkpoint whose scaler marks
+    /// them trained; a Keras export has only the score head.
+    heads_loaded: bool,
 }
 
 impl LSTMModel {
     pub fn new() -> Result<Self> {
-        let device = Device::cuda_if_available();
+        Self::with_device(Device::cuda_if_available())
+    }
+
+    pub fn with_device(device: Device) -> Result<Self> {
         let vs = nn::VarStore::new(device);
         let root = vs.root();
 
-        let lstm = nn::lstm(INPUT_SIZE, HIDDEN_SIZE, NUM_LAYERS, &vs.root());
-        let fc1 = nn::linear(HIDDEN_SIZE, 64, &root);
-        let fc2 = nn::linear(64, 32, &root);
+        let mut lstm_layers = Vec::with_capacity(LSTM_SIZES.len());
+        let mut input_size = INPUT_SIZE;
+        for (idx, hidden_size) in LSTM_SIZES.iter().enumerate() {
+            lstm_layers.push(nn::lstm(
+                &root / format!("lstm_{}", idx),
+                input_size,
+                *hidden_size,
+                Default::default(),
+            ));
+            input_size = *hidden_size;
+        }
+
+        let mut dense_layers = Vec::with_capacity(DENSE_SIZES.len());
+        for (idx, size) in DENSE_SIZES.iter().enumerate() {
+            dense_layers.push(nn::linear(
+                &root / format!("dense_{}", idx),
+                input_size,
+                *size,
+                Default::default(),
+            ));
+            input_size = *size;
+        }
+        let score_head = nn::linear(
+            &root / format!("dense_{}", DENSE_SIZES.len()),
+            input_size,
+            1,
+            Default::default(),
+        );
+
+        let last_hidden = LSTM_SIZES[LSTM_SIZES.len() - 1];
+        let fc1 = nn::linear(&root / "fc1", last_hidden, 64, Default::default());
+        let fc2 = nn::linear(&root / "fc2", 64, HEAD_WIDTH, Default::default());
 
         Ok(Self {
             vs,
-            lstm,
+            lstm_layers,
+            dense_layers,
+            score_head,
             fc1,
             fc2,
+            scaler: None,
             device,
+            weights_loaded: false,
+            heads_loaded: false,
         })
     }
 
+    /// Loads weights and, when present next to them, the feature scaler.
+    ///
+    /// `.npz` files are treated as a Keras export from vglnt-lstm-py and
+    /// converted; anything else is loaded as a tch `VarStore` checkpoint.
+    /// The scaler is read from the same path with a `.scaler.json` extension.
+    /// The per-timestep heads are only reported from a checkpoint whose scaler
+    /// sets `heads_trained`; otherwise the model reports the drive score alone.
     pub fn load_weights(&mut self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+        let keras = path.extension().map_or(false, |ext| ext == "npz");
+        if keras {
+            self.load_keras_npz(path)?;
+        } else {
+            self.vs
+                .load(path)
+                .with_context(|| format!("Failed to load weights from {}", path.display()))?;
+        }
+
+        let scaler_path = path.with_extension("scaler.json");
+        let scaler = if scaler_path.exists() {
+            Some(FeatureScaler::load(&scaler_path)?)
+        } else {
+            None
+        };
+        self.heads_loaded = !keras && scaler.as_ref().map_or(false, |scaler| scaler.heads_trained);
+        if scaler.is_some() {
+            self.scaler = scaler;
+        }
+        self.weights_loaded = true;
+        Ok(())
+    }
+
+    /// Writes a `VarStore` checkpoint and, if set, the scaler next to it in
+    /// the layout `load_weights` reads back.
+    pub fn save_weights(&self, path: &Path) -> Result<()> {
         self.vs
-            .load(path)
-            .with_context(|| format!("Failed to load weights from {}", path))?;
+            .save(path)
+            .with_context(|| format!("Failed to save weights to {}", path.display()))?;
+        if let Some(scaler) = &self.scaler {
+            scaler.save(&path.with_extension("scaler.json"))?;
+        }
         Ok(())
     }
 
-    pub fn process_sequence(&self, analyses: &[FrameAnalysis]) -> Result<LSTMOutput> {
-        let features = self.extract_features(analyses)?;
+    pub fn set_scaler(&mut self, scaler: FeatureScaler) {
+        self.scaler = Some(scaler);
+    }
 
-        let lstm_out = self.forward_pass(&features)?;
+    pub fn var_store(&self) -> &nn::VarStore {
+        &self.vs
+    }
 
-        let risk_factors = self.analyze_risks(&lstm_out)?;
-        let temporal_patterns = self.detect_patterns(&lstm_out)?;
-        let behavioral_metrics = self.calculate_metrics(&lstm_out)?;
-        let overall_score = self.calculate_safety_score(&lstm_out);
+    /// Regresses the raw drive score for a batch of sequences of any length.
+    ///
+    /// Sequences are padded at the end to the longest one and the padding is
+    /// masked by `forward_pass`. With `train` set, dropout is active.
+    pub fn predict_batch(&self, sequences: &[&[FrameAnalysis]], train: bool) -> Result<Tensor> {
+        let max_len = sequences.iter().map(|s| s.len()).max().unwrap_or(0);
+        if max_len == 0 || sequences.iter().any(|s| s.is_empty()) {
+            anyhow::bail!("Cannot predict on an empty frame sequence");
+        }
 
-        Ok(LSTMOutput {
-            overall_safety_score: overall_score,
-            risk_factors,
-            temporal_patterns,
-            behavioral_metrics,
-        })
+        let width = INPUT_SIZE as usize;
+        let batch = sequences.len();
+        // Time-major buffer: element (t, b, f) lives at (t * batch + b) * width + f
+        let mut feature_vec = vec![0f32; max_len * batch * width];
+        for (b, sequence) in sequences.iter().enumerate() {
+            for (t, analysis) in sequence.iter().enumerate() {
+                let mut features = frame_features(analysis);
+                if let Some(scaler) = &self.scaler {
+                    scaler.apply(&mut features);
+                }
+                let start = (t * batch + b) * width;
+                feature_vec[start..start + width].copy_from_slice(&features);
+            }
+        }
+
+        let features = Tensor::of_slice(&feature_vec)
+            .to_device(self.device)
+            .reshape(&[max_len as i64, batch as i64, INPUT_SIZE]);
+        let lengths: Vec<i64> = sequences.iter().map(|s| s.len() as i64).collect();
+
+        let (_, drive_score) = self.forward_pass(&features, &lengths, train);
+        Ok(drive_score)
+    }
+
+    /// Copies Keras weights into the matching tch variables.
+    ///
+    /// Expects the keys written by `export_npz` in vglnt-lstm-py/main.py:
+    /// `lstm_{i}.kernel`, `lstm_{i}.recurrent_kernel`, `lstm_{i}.bias`,
+    /// `dense_{i}.kernel` and `dense_{i}.bias`. Keras and libtorch share the
+    /// i, f, g, o gate order, so kernels only need transposing; Keras has a
+    /// single LSTM bias, so `bias_hh` is zeroed. The per-timestep heads are
+    /// not part of the Keras model and keep their current values, which
+    /// `infer` does not report.
+    fn load_keras_npz(&mut self, path: &Path) -> Result<()> {
+        let arrays: std::collections::HashMap<String, Tensor> = Tensor::read_npz(path)
+            .with_context(|| format!("Failed to read Keras export {}", path.display()))?
+            .into_iter()
+            .collect();
+        let take = |key: &str| -> Result<Tensor> {
+            arrays
+                .get(key)
+                .map(|t| t.to_kind(Kind::Float))
+                .with_context(|| format!("Keras export is missing {}", key))
+        };
+
+        let mut variables = self.vs.variables();
+        let mut assign = |name: &str, value: Tensor| -> Result<()> {
+            let var = variables
+                .get_mut(name)
+                .with_context(|| format!("Model has no variable {}", name))?;
+            if var.size() != value.size() {
+                anyhow::bail!(
+                    "Shape mismatch for {}: model {:?}, export {:?}",
+                    name,
+                    var.size(),
+                    value.size()
+                );
+            }
+            tch::no_grad(|| var.copy_(&value));
+            Ok(())
+        };
+
+        for idx in 0..LSTM_SIZES.len() {
+            let prefix = format!("lstm_{}", idx);
+            let bias = take(&format!("{}.bias", prefix))?;
+            assign(
+                &format!("{}.weight_ih_l0", prefix),
+                take(&format!("{}.kernel", prefix))?.tr(),
+            )?;
+            assign(
+                &format!("{}.weight_hh_l0", prefix),
+                take(&format!("{}.recurrent_kernel", prefix))?.tr(),
+            )?;
+            assign(&format!("{}.bias_hh_l0", prefix), bias.zeros_like())?;
+            assign(&format!("{}.bias_ih_l0", prefix), bias)?;
+        }
+
+        for idx in 0..=DENSE_SIZES.len() {
+            let prefix = format!("dense_{}", idx);
+            assign(
+                &format!("{}.weight", prefix),
+                take(&format!("{}.kernel", prefix))?.tr(),
+            )?;
+            assign(
+                &format!("{}.bias", prefix),
+                take(&format!("{}.bias", prefix))?,
+            )?;
+        }
+
+        Ok(())
     }
 
+    /// Builds a `[seq, 1, INPUT_SIZE]` tensor, standardised when a scaler is loaded.
     fn extract_features(&self, analyses: &[FrameAnalysis]) -> Result<Tensor> {
-        let mut feature_vec = Vec::new();
+        let mut feature_vec = Vec::with_capacity(analyses.len() * INPUT_SIZE as usize);
 
         for analysis in analyses {
-            // Extract key features from each frame
-            let frame_features = vec![
-                if analysis.lane_centering.following_lane_discipline {
-                    1.0
-                } else {
-                    0.0
-                },
-                analysis.lane_centering.score / 100.0,
-                match analysis.following_distance.safe_distance {
-                    "safe" => 1.0,
-                    "unsafe" => 0.0,
-                    _ => 0.5,
-                },
-                analysis.following_distance.score / 100.0,
-                analysis.signal_compliance.traffic_light.score / 100.0,
-                if analysis.merging_lane_change.safe_merging {
-                    1.0
-                } else {
-                    0.0
-                },
-                // Pedestrian awareness
-                if analysis.pedestrian_yielding.pedestrian_present {
-                    1.0
-                } else {
-                    0.0
-                },
-                self.calculate_frame_score(analysis),
-            ];
-
+            let mut frame_features = frame_features(analysis);
+            if let Some(scaler) = &self.scaler {
+                scaler.apply(&mut frame_features);
+            }
             feature_vec.extend(frame_features);
         }
 
@@ -107,158 +463,291 @@ impl LSTMModel {
         Ok(tensor)
     }
 
-    fn forward_pass(&self, features: &Tensor) -> Result<Tensor> {
-        let (lstm_out, _) = self.lstm.seq_init(1).seq(features)?;
-
-        let hidden = self.fc1.forward(&lstm_out);
-        let output = self.fc2.forward(&hidden);
-
-        Ok(output)
-    }
-
-    fn analyze_risks(&self, lstm_output: &Tensor) -> Result<Vec<RiskFactor>> {
-        let mut risk_factors = Vec::new();
-        let output_slice = lstm_output.mean_dim(&[0], true, Kind::Float)?;
-
-        if let Ok(scores) = Vec::<f32>::try_from(&output_slice) {
-            if scores[0] > 0.7 {
-                risk_factors.push(RiskFactor {
-                    factor_type: RiskFactorType::LaneDeviation,
-                    severity: scores[0],
-                    frequency: self.calculate_frequency(lstm_output, 0)?,
-                    temporal_correlation: self.calculate_temporal_correlation(lstm_output, 0)?,
-                });
-            }
+    /// Runs a time-major `[seq, batch, INPUT_SIZE]` batch padded at the end.
+    ///
+    /// Returns the per-timestep head logits `[seq, batch, HEAD_WIDTH]` and the
+    /// drive score `[batch]`. The score is regressed from the last LSTM
+    /// output at `lengths[b] - 1`, so trailing padding never reaches it.
+    fn forward_pass(&self, features: &Tensor, lengths: &[i64], train: bool) -> (Tensor, Tensor) {
+        let mut sequence = features.shallow_clone();
+        for lstm in &self.lstm_layers {
+            let (out, _) = lstm.seq(&sequence);
+            sequence = out.dropout(DROPOUT, train);
+        }
 
-            if scores[1] > 0.6 {
-                risk_factors.push(RiskFactor {
-                    factor_type: RiskFactorType::FollowingDistance,
-                    severity: scores[1],
-                    frequency: self.calculate_frequency(lstm_output, 1)?,
-                    temporal_correlation: self.calculate_temporal_correlation(lstm_output, 1)?,
-                });
+        let final_states: Vec<Tensor> = lengths
+            .iter()
+            .enumerate()
+            .map(|(b, len)| sequence.get(len - 1).get(b as i64))
+            .collect();
+        let mut hidden = Tensor::stack(&final_states, 0);
+        // Keras drops out after Dense(128) only, not after Dense(64)
+        for (idx, dense) in self.dense_layers.iter().enumerate() {
+            hidden = dense.forward(&hidden).relu();
+            if idx == 0 {
+                hidden = hidden.dropout(DROPOUT, train);
             }
         }
+        let drive_score = self.score_head.forward(&hidden).squeeze_dim(1);
+
+        let timestep_hidden = self.fc1.forward(&sequence).relu();
+        let timestep_out = self.fc2.forward(&timestep_hidden);
 
-        Ok(risk_factors)
+        (timestep_out, drive_score)
     }
 
-    fn detect_patterns(&self, lstm_output: &Tensor) -> Result<Vec<TemporalPattern>> {
-        let mut patterns = Vec::new();
-        let sequence_length = lstm_output.size()[0];
+    /// Splits a single-sequence forward pass into the named heads.
+    fn split_heads(&self, timestep_out: &Tensor, drive_out: &Tensor) -> Result<HeadOutputs> {
+        let output = timestep_out.select(1, 0).to_device(Device::Cpu);
 
-        for feature_idx in 0..INPUT_SIZE {
-            let feature_scores = lstm_output.select(2, feature_idx);
+        let risk = output
+            .narrow(1, RISK_OFFSET, RISK_HEAD.len() as i64)
+            .sigmoid();
+        let pattern = output
+            .narrow(1, PATTERN_OFFSET, PATTERN_HEAD.len() as i64)
+            .sigmoid();
 
-            let mean_score = feature_scores.mean(Kind::Float)?;
-            let variance = feature_scores.var(true, Kind::Float)?;
+        Ok(HeadOutputs {
+            risk: Vec::<Vec<f32>>::try_from(&risk)?,
+            pattern: Vec::<Vec<f32>>::try_from(&pattern)?,
+            drive_score: drive_out.double_value(&[0]) as f32,
+        })
+    }
+}
 
-            if variance.double_value(&[]) < 0.1 && mean_score.double_value(&[]) > 0.7 {
-                patterns.push(TemporalPattern {
-                    pattern_type: self.get_pattern_type(feature_idx),
-                    duration: (sequence_length as f32) / 30.0, // Convert to seconds assuming 30fps
-                    frequency: self.calculate_pattern_frequency(&feature_scores)?,
-                    risk_contribution: self.calculate_risk_contribution(&feature_scores)?,
-                });
-            }
-        }
+impl SequenceBackend for LSTMModel {
+    fn name(&self) -> &'static str {
+        "tch"
+    }
 
-        Ok(patterns)
+    fn weights_loaded(&self) -> bool {
+        self.weights_loaded
     }
 
-    fn calculate_metrics(&self, lstm_output: &Tensor) -> Result<BehavioralMetrics> {
-        Ok(BehavioralMetrics {
-            aggression_index: self.calculate_aggression_index(lstm_output)?,
-            attention_score: self.calculate_attention_score(lstm_output)?,
-            consistency_rating: self.calculate_consistency_rating(lstm_output)?,
-            anticipation_level: self.calculate_anticipation_level(lstm_output)?,
-        })
+    fn heads_loaded(&self) -> bool {
+        self.heads_loaded
     }
 
-    fn calculate_safety_score(&self, lstm_output: &Tensor) -> f32 {
-        let score = lstm_output
-            .mean_dim(&[0, 1], false, Kind::Float)
-            .get(0)
-            .unwrap_or(0.0);
+    fn infer(&self, analyses: &[FrameAnalysis]) -> Result<HeadOutputs> {
+        let features = self.extract_features(analyses)?;
+        let lengths = [analyses.len() as i64];
 
-        (score * 100.0).clamp(0.0, 100.0)
+        let (timestep_out, drive_out) =
+            tch::no_grad(|| self.forward_pass(&features, &lengths, false));
+        let mut heads = self.split_heads(&timestep_out, &drive_out)?;
+        // Untrained heads would report random risks and patterns
+        if !self.heads_loaded {
+            heads.risk.clear();
+            heads.pattern.clear();
+        }
+        Ok(heads)
     }
+}
 
-    fn calculate_frame_score(&self, analysis: &FrameAnalysis) -> f32 {
-        let scores = vec![
-            analysis.lane_centering.score,
-            analysis.following_distance.score,
-            analysis.signal_compliance.traffic_light.score,
-            analysis.merging_lane_change.score,
-            analysis.pedestrian_yielding.score,
-            analysis.intersection_behavior.score,
-        ];
+fn analyze_risks(analyses: &[FrameAnalysis], heads: &HeadOutputs) -> Vec<RiskFactor> {
+    let mut risk_factors = Vec::new();
 
-        scores.iter().sum::<f32>() / scores.len() as f32
-    }
+    for (idx, factor_type) in RISK_HEAD.iter().enumerate() {
+        let probabilities = column(&heads.risk, idx);
+        let severity = mean(&probabilities);
 
-    fn calculate_frequency(&self, output: &Tensor, feature_idx: i64) -> Result<f32> {
-        let feature_scores = output.select(2, feature_idx);
-        let threshold = 0.7;
-        let high_scores = feature_scores.gt(threshold);
-        Ok((high_scores.sum(Kind::Float)?.double_value(&[]) as f32)
-            / (feature_scores.size()[0] as f32))
+        if severity > RISK_THRESHOLD {
+            risk_factors.push(RiskFactor {
+                factor_type: factor_type.clone(),
+                severity,
+                frequency: fraction_above(&probabilities, RISK_THRESHOLD),
+                temporal_correlation: lag_one_correlation(&probabilities),
+                sources: vec![RiskSource::Model],
+                evidence_frames: probabilities
+                    .iter()
+                    .zip(analyses)
+                    .filter(|(p, _)| **p > RISK_THRESHOLD)
+                    .map(|(_, analysis)| analysis.frame_number)
+                    .collect(),
+            });
+        }
     }
 
-    fn calculate_temporal_correlation(&self, output: &Tensor, feature_idx: i64) -> Result<f32> {
-        let feature_scores = output.select(2, feature_idx);
-        let shifted_scores = feature_scores.slice(0, 1, feature_scores.size()[0], 1);
-        let correlation = feature_scores
-            .slice(0, 0, feature_scores.size()[0] - 1, 1)
-            .corrcoef(&shifted_scores)?;
-        Ok(correlation.double_value(&[]) as f32)
-    }
+    risk_factors
+}
+
+fn detect_patterns(heads: &HeadOutputs) -> Vec<TemporalPattern> {
+    let mut patterns = Vec::new();
+    let sequence_length = heads.pattern.len();
 
-    fn get_pattern_type(&self, feature_idx: i64) -> String {
-        match feature_idx {
-            0 => "Lane Discipline".to_string(),
-            1 => "Following Distance".to_string(),
-            2 => "Signal Compliance".to_string(),
-            3 => "Merging Behavior".to_string(),
-            4 => "Pedestrian Awareness".to_string(),
-            _ => "General Driving".to_string(),
+    for (idx, pattern_type) in PATTERN_HEAD.iter().enumerate() {
+        let intensities = column(&heads.pattern, idx);
+        let mean_intensity = mean(&intensities);
+        let variance = variance(&intensities);
+
+        // A sustained lapse: consistently high intensity with little variation
+        if variance < PATTERN_MAX_VARIANCE && mean_intensity > PATTERN_THRESHOLD {
+            patterns.push(TemporalPattern {
+                pattern_type: pattern_type.to_string(),
+                duration: sequence_length as f32 / FRAMES_PER_SECOND,
+                frequency: calculate_pattern_frequency(&intensities),
+                risk_contribution: mean_intensity * (1.0 + variance),
+            });
         }
     }
 
-    fn calculate_pattern_frequency(&self, scores: &Tensor) -> Result<f32> {
-        let threshold = 0.7;
-        let transitions = scores.gt(threshold).diff();
-        Ok((transitions.sum(Kind::Float)?.double_value(&[]) as f32) / (scores.size()[0] as f32))
+    patterns
+}
+
+fn calculate_metrics(heads: &HeadOutputs) -> Option<BehavioralMetrics> {
+    if heads.risk.is_empty() || heads.pattern.is_empty() {
+        return None;
     }
+    Some(BehavioralMetrics {
+        aggression_index: calculate_aggression_index(heads),
+        attention_score: calculate_attention_score(heads),
+        consistency_rating: calculate_consistency_rating(heads),
+        anticipation_level: calculate_anticipation_level(heads),
+    })
+}
 
-    fn calculate_risk_contribution(&self, scores: &Tensor) -> Result<f32> {
-        let mean_score = scores.mean(Kind::Float)?;
-        let variance = scores.var(true, Kind::Float)?;
-        Ok((mean_score.double_value(&[]) * (1.0 + variance.double_value(&[]))) as f32)
+fn calculate_pattern_frequency(intensities: &[f32]) -> f32 {
+    if intensities.is_empty() {
+        return 0.0;
     }
+    let transitions = intensities
+        .windows(2)
+        .filter(|w| (w[0] > PATTERN_THRESHOLD) != (w[1] > PATTERN_THRESHOLD))
+        .count();
+    transitions as f32 / intensities.len() as f32
+}
+
+fn calculate_aggression_index(heads: &HeadOutputs) -> f32 {
+    mean_abs_step(&heads.risk).clamp(0.0, 1.0)
+}
+
+fn calculate_attention_score(heads: &HeadOutputs) -> f32 {
+    let variances: Vec<f32> = (0..PATTERN_HEAD.len())
+        .map(|idx| variance(&column(&heads.pattern, idx)))
+        .collect();
+    (1.0 - mean(&variances)).clamp(0.0, 1.0)
+}
 
-    fn calculate_aggression_index(&self, output: &Tensor) -> Result<f32> {
-        let sudden_changes = output.diff().abs().mean(Kind::Float)?;
-        Ok(sudden_changes.double_value(&[]) as f32)
+fn calculate_consistency_rating(heads: &HeadOutputs) -> f32 {
+    let deviations: Vec<f32> = (0..RISK_HEAD.len())
+        .map(|idx| variance(&column(&heads.risk, idx)).sqrt())
+        .collect();
+    (1.0 - mean(&deviations)).clamp(0.0, 1.0)
+}
+
+fn calculate_anticipation_level(heads: &HeadOutputs) -> f32 {
+    (1.0 - mean_abs_step(&heads.pattern)).clamp(0.0, 1.0)
+}
+
+/// Encodes one frame exactly like `extract_features` in vglnt-lstm-py/main.py:
+/// same columns, same order, and the per-sign sub-scores rather than their
+/// category totals.
+pub fn frame_features(analysis: &FrameAnalysis) -> Vec<f32> {
+    let flag = |value: bool| if value { 1.0 } else { 0.0 };
+    let signals = &analysis.signal_compliance;
+    let signs = &analysis.road_sign_awareness;
+
+    let mut features = Vec::with_capacity(INPUT_SIZE as usize);
+    features.push(flag(analysis.lane_centering.following_lane_discipline));
+    features.push(analysis.lane_centering.score);
+    features.extend(match analysis.following_distance.safe_distance {
+        SafetyStatus::Safe => [1.0, 0.0, 0.0],
+        SafetyStatus::Marginal => [0.0, 1.0, 0.0],
+        SafetyStatus::Unsafe => [0.0, 0.0, 1.0],
+        SafetyStatus::Unknown => [0.0, 0.0, 0.0],
+    });
+    features.push(analysis.following_distance.score);
+    features.extend(match signals.traffic_light.status {
+        SignalColor::Red => [1.0, 0.0, 0.0],
+        SignalColor::Yellow => [0.0, 1.0, 0.0],
+        SignalColor::Green => [0.0, 0.0, 1.0],
+        SignalColor::Unknown => [0.0, 0.0, 0.0],
+    });
+    features.push(flag(signals.traffic_light.compliance));
+    features.push(signals.traffic_light.score);
+    features.push(flag(signals.stop_sign.present));
+    features.extend(match signals.stop_sign.compliance {
+        Some(true) => [1.0, 0.0],
+        Some(false) => [0.0, 1.0],
+        None => [0.0, 0.0],
+    });
+    features.push(signals.stop_sign.score);
+    features.push(flag(signs.speed_limit.visible));
+    features.extend(match signs.speed_limit.compliance {
+        Some(true) => [1.0, 0.0, 0.0],
+        Some(false) => [0.0, 1.0, 0.0],
+        None => [0.0, 0.0, 1.0],
+    });
+    features.push(signs.speed_limit.score);
+    features.push(flag(signs.yield_sign.visible));
+    features.push(signs.yield_sign.score);
+    features.push(flag(analysis.shoulder_use.using_shoulder));
+    features.push(analysis.shoulder_use.score);
+    features.push(flag(analysis.merging_lane_change.safe_merging));
+    features.push(analysis.merging_lane_change.score);
+    features.push(flag(analysis.pedestrian_yielding.pedestrian_present));
+    features.push(analysis.pedestrian_yielding.score);
+    features.push(flag(analysis.intersection_behavior.stop_line_observance));
+    features.push(analysis.intersection_behavior.score);
+    features
+}
+
+fn column(rows: &[Vec<f32>], idx: usize) -> Vec<f32> {
+    rows.iter().map(|row| row[idx]).collect()
+}
+
+fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
     }
+    values.iter().sum::<f32>() / values.len() as f32
+}
 
-    fn calculate_attention_score(&self, output: &Tensor) -> Result<f32> {
-        let attention_variance = output.var(true, Kind::Float)?;
-        Ok((1.0 - attention_variance.double_value(&[])).clamp(0.0, 1.0) as f32)
+fn variance(values: &[f32]) -> f32 {
+    if values.len() < 2 {
+        return 0.0;
     }
+    let m = mean(values);
+    values.iter().map(|v| (v - m).powi(2)).sum::<f32>() / (values.len() - 1) as f32
+}
 
-    fn calculate_consistency_rating(&self, output: &Tensor) -> Result<f32> {
-        let consistency = 1.0 - output.std(true, Kind::Float)?.double_value(&[]);
-        Ok(consistency as f32)
+fn fraction_above(values: &[f32], threshold: f32) -> f32 {
+    if values.is_empty() {
+        return 0.0;
     }
+    values.iter().filter(|v| **v > threshold).count() as f32 / values.len() as f32
+}
 
-    fn calculate_anticipation_level(&self, output: &Tensor) -> Result<f32> {
-        let transitions = output.diff();
-        let smoothness = 1.0 - transitions.abs().mean(Kind::Float)?.double_value(&[]);
-        Ok(smoothness as f32)
+pub(crate) fn lag_one_correlation(values: &[f32]) -> f32 {
+    if values.len() < 3 {
+        return 0.0;
+    }
+    let (head, tail) = (&values[..values.len() - 1], &values[1..]);
+    let (mh, mt) = (mean(head), mean(tail));
+    let cov: f32 = head
+        .iter()
+        .zip(tail)
+        .map(|(a, b)| (a - mh) * (b - mt))
+        .sum();
+    let denom = (head.iter().map(|a| (a - mh).powi(2)).sum::<f32>()
+        * tail.iter().map(|b| (b - mt).powi(2)).sum::<f32>())
+    .sqrt();
+    if denom == 0.0 {
+        0.0
+    } else {
+        cov / denom
     }
 }
 
+/// Mean absolute change between consecutive timesteps, across all outputs.
+fn mean_abs_step(rows: &[Vec<f32>]) -> f32 {
+    let steps: Vec<f32> = rows
+        .windows(2)
+        .flat_map(|w| w[0].iter().zip(&w[1]).map(|(a, b)| (b - a).abs()))
+        .collect();
+    mean(&steps)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -278,17 +767,321 @@ mod tests {
         assert!(features.is_ok());
     }
 
+    #[test]
+    fn test_sequence_to_one() {
+        let model = LSTMModel::new().unwrap();
+        let analyses = create_test_analyses();
+        let output = model.process_sequence(&analyses).unwrap();
+        assert!((0.0..=100.0).contains(&output.overall_safety_score));
+    }
+
+    #[test]
+    fn test_untrained_heads_are_not_reported() {
+        let mut model = LSTMModel::new().unwrap();
+        let analyses = create_test_analyses();
+        let output = model.process_sequence(&analyses).unwrap();
+        assert!(output.risk_factors.is_empty());
+        assert!(output.temporal_patterns.is_empty());
+        assert!(output.behavioral_metrics.is_none());
+        assert!(!model.heads_loaded());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.ot");
+        model.save_weights(&path).unwrap();
+        model.load_weights(path.to_str().unwrap()).unwrap();
+        assert!(!model.heads_loaded());
+        assert!(model.infer(&analyses).unwrap().risk.is_empty());
+
+        // Only a checkpoint marked as having trained heads reports them
+        model.set_scaler(FeatureScaler {
+            heads_trained: true,
+            ..FeatureScaler::fit(&analyses)
+        });
+        model.save_weights(&path).unwrap();
+        model.load_weights(path.to_str().unwrap()).unwrap();
+        assert!(model.heads_loaded());
+        let heads = model.infer(&analyses).unwrap();
+        assert_eq!(heads.risk.len(), analyses.len());
+        assert_eq!(heads.pattern.len(), analyses.len());
+    }
+
+    #[test]
+    fn test_padding_is_masked() {
+        let model = LSTMModel::new().unwrap();
+        let features = model.extract_features(&create_test_analyses()).unwrap();
+        let padded = Tensor::cat(&[&features, &features.ones_like()], 0);
+
+        let (_, unpadded_score) = model.forward_pass(&features, &[1], false);
+        let (_, padded_score) = model.forward_pass(&padded, &[1], false);
+        assert!(unpadded_score.allclose(&padded_score, 1e-6, 1e-6, false));
+    }
+
+    #[test]
+    fn test_frame_features_match_python_width() {
+        let features = frame_features(&create_test_analyses()[0]);
+        assert_eq!(features.len(), INPUT_SIZE as usize);
+        // safe_distance one-hot sits at indices 2..5
+        assert_eq!(&features[2..5], &[1.0, 0.0, 0.0]);
+    }
+
+    /// Checks a frame against the row `extract_features` produced for it,
+    /// written by vglnt-lstm-py/feature_fixture.py.
+    #[test]
+    fn test_frame_features_parity() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/features.json");
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(fixture).unwrap()).unwrap();
+        let frame: FrameData = serde_json::from_value(fixture["frame"].clone()).unwrap();
+        let expected: Vec<f32> = serde_json::from_value(fixture["features"].clone()).unwrap();
+
+        assert_eq!(frame_features(&frame.into_analysis(0, 0.0)), expected);
+    }
+
+    #[test]
+    fn test_drive_score_calibration() {
+        assert_eq!(calibrate_drive_score(0.0), 0.0);
+        assert_eq!(calibrate_drive_score(2.5), 50.0);
+        assert_eq!(calibrate_drive_score(5.0), 100.0);
+        assert_eq!(calibrate_drive_score(7.0), 100.0);
+        assert_eq!(calibrate_drive_score(-1.0), 0.0);
+    }
+
+    /// Compares the two backends, heads included: ONNX on fixtures/parity.onnx,
+    /// written by vglnt-lstm-py/parity_fixture.py, and tch on the same weights
+    /// exported the way `export_npz` does, with the heads set directly.
+    #[test]
+    fn test_onnx_parity() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/parity.onnx");
+        let onnx_model = OnnxModel::load(&fixture).unwrap();
+
+        let weights: Vec<(String, Tensor)> = parity_weights()
+            .into_iter()
+            .enumerate()
+            .map(|(k, (name, shape))| {
+                let size = shape.iter().product::<i64>();
+                let values = (Tensor::arange(size, (Kind::Float, Device::Cpu)) * PARITY_FREQ
+                    + k as f64)
+                    .sin()
+                    * PARITY_SCALE;
+                (name, values.reshape(&shape))
+            })
+            .collect();
+        let (heads, keras): (Vec<_>, Vec<_>) = weights
+            .into_iter()
+            .partition(|(name, _)| name.starts_with("fc"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let npz = dir.path().join("parity.npz");
+        Tensor::write_npz(&keras, &npz).unwrap();
+        std::fs::copy(
+            fixture.with_extension("scaler.json"),
+            npz.with_extension("scaler.json"),
+        )
+        .unwrap();
+        let mut tch_model = LSTMModel::new().unwrap();
+        tch_model.load_weights(npz.to_str().unwrap()).unwrap();
+        let mut variables = tch_model.var_store().variables();
+        for (name, value) in &heads {
+            let (layer, param) = name.split_once('.').unwrap();
+            let (var, value) = match param {
+                "kernel" => (format!("{}.weight", layer), value.tr()),
+                _ => (name.clone(), value.shallow_clone()),
+            };
+            tch::no_grad(|| variables.get_mut(&var).unwrap().copy_(&value));
+        }
+        tch_model.heads_loaded = true;
+
+        let template = create_test_analyses().remove(0);
+        let analyses: Vec<FrameAnalysis> = (0..24)
+            .map(|idx| {
+                let mut frame = template.clone();
+                frame.frame_number = idx;
+                frame.lane_centering.score = (idx % 20) as f32;
+                frame.following_distance.safe_distance = match idx % 3 {
+                    0 => SafetyStatus::Safe,
+                    1 => SafetyStatus::Marginal,
+                    _ => SafetyStatus::Unsafe,
+                };
+                frame.signal_compliance.traffic_light.status = match idx % 4 {
+                    0 => SignalColor::Red,
+                    1 => SignalColor::Yellow,
+                    _ => SignalColor::Green,
+                };
+                frame.pedestrian_yielding.pedestrian_present = idx % 5 == 0;
+                frame
+            })
+            .collect();
+
+        let expected = tch_model.infer(&analyses).unwrap();
+        let actual = onnx_model.infer(&analyses).unwrap();
+        assert!(
+            (expected.drive_score - actual.drive_score).abs() < 1e-4,
+            "tch {} vs onnx {}",
+            expected.drive_score,
+            actual.drive_score
+        );
+        for (expected, actual) in [
+            (&expected.risk, &actual.risk),
+            (&expected.pattern, &actual.pattern),
+        ] {
+            assert_eq!(expected.len(), analyses.len());
+            assert_eq!(actual.len(), analyses.len());
+            for (e, a) in expected.iter().flatten().zip(actual.iter().flatten()) {
+                assert!((e - a).abs() < 1e-4, "tch {} vs onnx {}", e, a);
+            }
+        }
+    }
+
+    // Weight formula of vglnt-lstm-py/parity_fixture.py
+    const PARITY_FREQ: f64 = 0.7;
+    const PARITY_SCALE: f64 = 0.1;
+
+    /// (name, Keras shape) of every fixture tensor, in the order of
+    /// `weights()` in parity_fixture.py.
+    fn parity_weights() -> Vec<(String, Vec<i64>)> {
+        let mut shapes = Vec::new();
+        let mut input_size = INPUT_SIZE;
+        for (idx, hidden) in LSTM_SIZES.iter().enumerate() {
+            shapes.push((format!("lstm_{}.kernel", idx), vec![input_size, 4 * hidden]));
+            shapes.push((
+                format!("lstm_{}.recurrent_kernel", idx),
+                vec![*hidden, 4 * hidden],
+            ));
+            shapes.push((format!("lstm_{}.bias", idx), vec![4 * hidden]));
+            input_size = *hidden;
+        }
+        for (idx, size) in DENSE_SIZES.iter().chain(&[1]).enumerate() {
+            shapes.push((format!("dense_{}.kernel", idx), vec![input_size, *size]));
+            shapes.push((format!("dense_{}.bias", idx), vec![*size]));
+            input_size = *size;
+        }
+        let last_hidden = LSTM_SIZES[LSTM_SIZES.len() - 1];
+        shapes.push(("fc1.kernel".to_string(), vec![last_hidden, 64]));
+        shapes.push(("fc1.bias".to_string(), vec![64]));
+        shapes.push(("fc2.kernel".to_string(), vec![64, HEAD_WIDTH]));
+        shapes.push(("fc2.bias".to_string(), vec![HEAD_WIDTH]));
+        shapes
+    }
+
+    #[test]
+    fn test_head_layout() {
+        assert_eq!(PATTERN_OFFSET, RISK_HEAD.len() as i64);
+        assert_eq!(HEAD_WIDTH, (RISK_HEAD.len() + PATTERN_HEAD.len()) as i64);
+    }
+
+    #[test]
+    fn test_risks_use_named_heads() {
+        let mut row = vec![0.1; RISK_HEAD.len()];
+        row[3] = 0.9;
+        let heads = HeadOutputs {
+            risk: vec![row; 4],
+            pattern: vec![vec![0.0; PATTERN_HEAD.len()]; 4],
+            drive_score: 4.0,
+        };
+
+        let risks = analyze_risks(&create_test_analyses(), &heads);
+        assert_eq!(risks.len(), 1);
+        assert!(matches!(
+            risks[0].factor_type,
+            RiskFactorType::SignalCompliance
+        ));
+        assert_eq!(risks[0].frequency, 1.0);
+    }
+
+    #[test]
+    fn test_sustained_lapse_detected() {
+        let mut row = vec![0.0; PATTERN_HEAD.len()];
+        row[1] = 0.9;
+        let heads = HeadOutputs {
+            risk: vec![vec![0.0; RISK_HEAD.len()]; 30],
+            pattern: vec![row; 30],
+            drive_score: 2.5,
+        };
+
+        let patterns = detect_patterns(&heads);
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(patterns[0].pattern_type, "Following Distance");
+        assert_eq!(patterns[0].duration, 1.0);
+    }
+
     fn create_test_analyses() -> Vec<FrameAnalysis> {
         vec![FrameAnalysis {
             frame_number: 1,
             timestamp: 0.0,
             lane_centering: LaneCentering {
                 following_lane_discipline: true,
-                score: 95.0,
+                deviation_from_center: 0.1,
+                score: 18.0,
+                confidence: 0.9,
             },
             following_distance: FollowingDistance {
-                safe_distance: "safe".to_string(),
-                score: 90.0,
+                safe_distance: SafetyStatus::Safe,
+                distance_meters: 25.0,
+                time_to_collision: 4.0,
+                score: 14.0,
+                confidence: 0.9,
+            },
+            signal_compliance: SignalCompliance {
+                traffic_light: TrafficLightStatus {
+                    status: SignalColor::Green,
+                    compliance: true,
+                    distance: 40.0,
+                    score: 12.0,
+                },
+                stop_sign: StopSignStatus {
+                    present: false,
+                    compliance: None,
+                    stop_duration: None,
+                    score: 3.0,
+                },
+                score: 15.0,
+                confidence: 0.8,
+            },
+            merging_lane_change: MergingLaneChange {
+                in_progress: false,
+                safe_merging: true,
+                signal_used: true,
+                blind_spot_check: true,
+                speed_adjustment: 0.0,
+                score: 10.0,
+                confidence: 0.8,
+            },
+            pedestrian_yielding: PedestrianYielding {
+                pedestrian_present: false,
+                proper_yielding: true,
+                distance_to_pedestrian: None,
+                score: 10.0,
+                confidence: 0.9,
+            },
+            intersection_behavior: IntersectionBehavior {
+                stop_line_observance: true,
+                complete_stop: false,
+                right_of_way_compliance: true,
+                score: 10.0,
+                confidence: 0.8,
+            },
+            road_sign_awareness: RoadSignAwareness {
+                speed_limit: SpeedLimitStatus {
+                    visible: false,
+                    limit: None,
+                    current_speed: None,
+                    compliance: None,
+                    score: 11.0,
+                },
+                yield_sign: YieldSignStatus {
+                    visible: false,
+                    compliance: None,
+                    score: 4.0,
+                },
+                other_signs: Vec::new(),
+                score: 15.0,
+                confidence: 0.7,
+            },
+            shoulder_use: ShoulderUse {
+                using_shoulder: false,
+                emergency_situation: false,
+                score: 5.0,
+                confidence: 0.9,
             },
         }]
     }
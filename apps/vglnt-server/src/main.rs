@@ -1,28 +1,143 @@
+use actix_web::dev::Service;
+use actix_web::middleware::from_fn;
 use actix_web::{web, App, HttpServer};
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
 
 mod api;
+mod coaching;
+mod dataset;
+mod eval;
+mod events;
 mod video;
 mod llm;
 mod lstm;
+mod onnx;
+mod risk;
+mod rules;
+mod smoothing;
+mod stats;
+mod telemetry;
+mod summary;
+mod train;
 mod types;
 mod error;
+mod logging;
+mod metrics;
+
+#[derive(Parser)]
+#[command(name = "vglnt-server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP API (the default)
+    Serve,
+    /// Train the sequence model from prepare.py annotations
+    Train(train::TrainArgs),
+    /// Evaluate the sequence model on labelled annotations
+    Eval(eval::EvalArgs),
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    tracing_subscriber::fmt::init();
+    let _guard = logging::init().map_err(to_io_error)?;
+
+    let cli = Cli::parse();
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve().await,
+        Command::Train(args) => train::run(&args).map_err(to_io_error),
+        Command::Eval(args) => eval::run(&args).map_err(to_io_error),
+    }
+}
+
+/// How long shutdown waits for running analyses, unless overridden.
+const DEFAULT_SHUTDOWN_DEADLINE: Duration = Duration::from_secs(60);
 
-    let app_state = Arc::new(api::AppState::new()?);
-    
-    HttpServer::new(move || {
+async fn serve() -> std::io::Result<()> {
+    let app_state = Arc::new(api::AppState::new().map_err(to_io_error)?);
+    let resumed = api::jobs::recover(&app_state).map_err(to_io_error)?;
+    if resumed > 0 {
+        info!("Resumed {} queued analyses", resumed);
+    }
+    let deadline = std::env::var("VGLNT_SHUTDOWN_DEADLINE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map_or(DEFAULT_SHUTDOWN_DEADLINE, Duration::from_secs);
+
+    let server_state = Arc::clone(&app_state);
+    let server = HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(Arc::clone(&app_state)))
+            .wrap(from_fn(metrics::record_http))
+            .wrap(from_fn(logging::trace_http))
+            .wrap_fn(|req, srv| {
+                let request_id = api::request_id::from_request(&req);
+                let request = req.request().clone();
+                api::request_id::scope(request, request_id, srv.call(req))
+            })
+            .app_data(web::Data::new(Arc::clone(&server_state)))
+            .app_data(error::path_config())
+            .app_data(error::query_config())
+            .app_data(error::json_config())
             .service(api::routes::video_routes())
             .service(api::routes::analysis_routes())
+            .service(api::routes::usage_routes())
+            .service(api::routes::webhook_routes())
+            .service(api::routes::metrics_route())
+            .service(api::routes::healthz_route())
+            .service(api::routes::readyz_route())
+            .default_service(web::route().to(api::handlers::route_not_found))
     })
+    .disable_signals()
+    .shutdown_timeout(deadline.as_secs())
     .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    .run();
+
+    // Refuse uploads first, then let in-flight requests finish before stopping
+    let handle = server.handle();
+    let state = Arc::clone(&app_state);
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down, no longer accepting uploads");
+        state.begin_shutdown();
+        handle.stop(true).await;
+    });
+    server.await?;
+
+    // Analyses run on this runtime, so they are still going; give them what
+    // is left of the deadline, then leave the rest queued for the next start
+    api::jobs::drain(&app_state, deadline).await;
+    Ok(())
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("Cannot listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("Cannot listen for Ctrl-C: {}", e);
+    }
+}
+
+fn to_io_error(e: anyhow::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, format!("{:#}", e))
 }
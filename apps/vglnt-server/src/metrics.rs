@@ -0,0 +1,230 @@
+//! Prometheus metrics for the upload and analysis pipeline, served as text
+//! from `GET /metrics`.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use prometheus::{
+    core::Collector, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Instant;
+
+pub static METRICS: LazyLock<Metrics> =
+    LazyLock::new(|| Metrics::new().expect("metric definitions are valid"));
+
+/// Model calls take seconds rather than milliseconds.
+const SLOW_BUCKETS: &[f64] = &[
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+const FAST_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+/// Most requests are quick, but uploads and renders stream for minutes.
+const HTTP_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+pub struct Metrics {
+    registry: Registry,
+    pub uploads: IntCounter,
+    pub upload_bytes: IntCounter,
+    /// Finished analyses, by final status.
+    pub jobs: IntCounterVec,
+    pub queue_depth: IntGauge,
+    pub frame_decode_seconds: Histogram,
+    /// By operation: `frame` or `text`.
+    pub llm_request_seconds: HistogramVec,
+    /// By operation and kind: `timeout`, `connect`, `status` or `parse`.
+    pub llm_errors: IntCounterVec,
+    /// By reason: `timeout`, `connect`, `rate_limited` or `server_error`.
+    pub llm_retries: IntCounterVec,
+    pub llm_parse_failures: IntCounter,
+    /// By sequence model backend.
+    pub lstm_inference_seconds: HistogramVec,
+    /// By method, route pattern and status code.
+    pub http_request_seconds: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("vglnt".to_string()), None)?;
+        let histogram = |name: &str, help: &str, buckets: &[f64]| {
+            HistogramOpts::new(name, help).buckets(buckets.to_vec())
+        };
+
+        Ok(Self {
+            uploads: register(
+                &registry,
+                IntCounter::new("uploads_total", "Accepted video uploads")?,
+            )?,
+            upload_bytes: register(
+                &registry,
+                IntCounter::new("upload_bytes_total", "Bytes received in uploads")?,
+            )?,
+            jobs: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("jobs_total", "Finished analyses by status"),
+                    &["status"],
+                )?,
+            )?,
+            queue_depth: register(
+                &registry,
+                IntGauge::new("queue_depth", "Analyses queued or running")?,
+            )?,
+            frame_decode_seconds: register(
+                &registry,
+                Histogram::with_opts(histogram(
+                    "frame_decode_seconds",
+                    "Time to decode one video frame",
+                    FAST_BUCKETS,
+                ))?,
+            )?,
+            llm_request_seconds: register(
+                &registry,
+                HistogramVec::new(
+                    histogram(
+                        "llm_request_seconds",
+                        "Vision model request latency",
+                        SLOW_BUCKETS,
+                    ),
+                    &["operation"],
+                )?,
+            )?,
+            llm_errors: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("llm_errors_total", "Failed vision model requests"),
+                    &["operation", "kind"],
+                )?,
+            )?,
+            llm_retries: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("llm_retries_total", "Retried vision model requests"),
+                    &["reason"],
+                )?,
+            )?,
+            llm_parse_failures: register(
+                &registry,
+                IntCounter::new(
+                    "llm_parse_failures_total",
+                    "Vision model responses that were not valid frame JSON",
+                )?,
+            )?,
+            lstm_inference_seconds: register(
+                &registry,
+                HistogramVec::new(
+                    histogram(
+                        "lstm_inference_seconds",
+                        "Sequence model inference time per analysis",
+                        FAST_BUCKETS,
+                    ),
+                    &["backend"],
+                )?,
+            )?,
+            http_request_seconds: register(
+                &registry,
+                HistogramVec::new(
+                    histogram("http_request_seconds", "HTTP request latency", HTTP_BUCKETS),
+                    &["method", "route", "status"],
+                )?,
+            )?,
+            registry,
+        })
+    }
+
+    /// Every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::warn!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+fn register<T: Collector + Clone + 'static>(
+    registry: &Registry,
+    metric: T,
+) -> prometheus::Result<T> {
+    registry.register(Box::new(metric.clone()))?;
+    Ok(metric)
+}
+
+/// Middleware timing each request under its route pattern, so paths with ids
+/// share one series. Errors are timed under the status they respond with.
+pub async fn record_http(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let result = next.call(req).await;
+
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    METRICS
+        .http_request_seconds
+        .with_label_values(&[&method, &route, status.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::Service;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn refuse(
+        _: ServiceRequest,
+        _: Next<impl MessageBody>,
+    ) -> Result<ServiceResponse, actix_web::Error> {
+        Err(actix_web::error::ErrorTooManyRequests("Slow down"))
+    }
+
+    #[actix_web::test]
+    async fn test_errors_are_timed() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(record_http))
+                .wrap(from_fn(refuse))
+                .route("/limited/{id}", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let result = app
+            .call(test::TestRequest::get().uri("/limited/1").to_request())
+            .await;
+        assert!(result.is_err());
+
+        let timed =
+            METRICS
+                .http_request_seconds
+                .with_label_values(&["GET", "/limited/{id}", "429"]);
+        assert_eq!(timed.get_sample_count(), 1);
+    }
+
+    #[test]
+    fn test_render() {
+        METRICS.uploads.inc();
+        METRICS.jobs.with_label_values(&["complete"]).inc();
+        METRICS.llm_retries.with_label_values(&["timeout"]).inc();
+        METRICS
+            .llm_request_seconds
+            .with_label_values(&["frame"])
+            .observe(1.5);
+
+        let text = METRICS.render();
+        assert!(text.contains("# TYPE vglnt_uploads_total counter"));
+        assert!(text.contains("vglnt_jobs_total{status=\"complete\"}"));
+        assert!(text.contains("vglnt_llm_retries_total{reason=\"timeout\"}"));
+        assert!(text.contains("vglnt_llm_request_seconds_bucket{operation=\"frame\",le=\"2.5\"}"));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::lstm::{
+    frame_features, FeatureScaler, HeadOutputs, SequenceBackend, HEAD_WIDTH, INPUT_SIZE,
+    PATTERN_HEAD, PATTERN_OFFSET, RISK_HEAD, RISK_OFFSET,
+};
+use crate::types::FrameAnalysis;
+use anyhow::{Context, Result};
+use std::path::Path;
+use tract_onnx::prelude::*;
+
+type Plan = TypedSimplePlan<TypedModel>;
+
+/// CPU inference over a sequence model exported to ONNX (e.g. with tf2onnx).
+///
+/// The graph takes one batch-major `[1, seq, INPUT_SIZE]` float input. Output
+/// 0 is the drive score `[1, 1]` on the 0–5 label scale; an optional output 1
+/// holds the per-timestep head logits `[1, seq, HEAD_WIDTH]`. The scaler is
+/// read from the same path with a `.scaler.json` extension, as for tch.
+pub struct OnnxModel {
+    plan: Plan,
+    scaler: Option<FeatureScaler>,
+    has_heads: bool,
+}
+
+impl OnnxModel {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut model = tract_onnx::onnx()
+            .model_for_path(path)
+            .with_context(|| format!("Failed to load ONNX model from {}", path.display()))?;
+        let seq = model.symbol_table.sym("S");
+        model.set_input_fact(
+            0,
+            f32::fact([1.to_dim(), seq.to_dim(), INPUT_SIZE.to_dim()]).into(),
+        )?;
+        let has_heads = model.output_outlets()?.len() > 1;
+        let plan = model.into_optimized()?.into_runnable()?;
+
+        let scaler_path = path.with_extension("scaler.json");
+        let scaler = if scaler_path.exists() {
+            Some(FeatureScaler::load(&scaler_path)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            plan,
+            scaler,
+            has_heads,
+        })
+    }
+}
+
+impl SequenceBackend for OnnxModel {
+    fn name(&self) -> &'static str {
+        "onnx"
+    }
+
+    fn heads_loaded(&self) -> bool {
+        self.has_heads
+    }
+
+    fn infer(&self, analyses: &[FrameAnalysis]) -> Result<HeadOutputs> {
+        let mut feature_vec = Vec::with_capacity(analyses.len() * INPUT_SIZE as usize);
+        for analysis in analyses {
+            let mut frame_features = frame_features(analysis);
+            if let Some(scaler) = &self.scaler {
+                scaler.apply(&mut frame_features);
+            }
+            feature_vec.extend(frame_features);
+        }
+
+        let input = tract_ndarray::Array3::from_shape_vec(
+            (1, analyses.len(), INPUT_SIZE as usize),
+            feature_vec,
+        )?;
+        let outputs = self.plan.run(tvec!(Tensor::from(input).into()))?;
+
+        let drive_score = *outputs[0]
+            .to_array_view::<f32>()?
+            .iter()
+            .next()
+            .context("ONNX model returned an empty drive score")?;
+
+        let (risk, pattern) = if self.has_heads {
+            let logits = outputs[1].to_array_view::<f32>()?;
+            let logits = logits
+                .into_shape((analyses.len(), HEAD_WIDTH as usize))
+                .context("ONNX head output is not [1, seq, HEAD_WIDTH]")?;
+            let sigmoid = |x: f32| 1.0 / (1.0 + (-x).exp());
+            let slice = |offset: i64, width: usize| -> Vec<Vec<f32>> {
+                logits
+                    .outer_iter()
+                    .map(|row| {
+                        row.iter()
+                            .skip(offset as usize)
+                            .take(width)
+                            .map(|x| sigmoid(*x))
+                            .collect()
+                    })
+                    .collect()
+            };
+            (
+                slice(RISK_OFFSET, RISK_HEAD.len()),
+                slice(PATTERN_OFFSET, PATTERN_HEAD.len()),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        Ok(HeadOutputs {
+            risk,
+            pattern,
+            drive_score,
+        })
+    }
+}
@@ -0,0 +1,341 @@
+use crate::lstm::lag_one_correlation;
+use crate::rules::RulesFile;
+use crate::types::{
+    FrameAnalysis, RiskFactor, RiskFactorType, RiskSource, SafetyStatus, SignalColor,
+};
+use anyhow::Result;
+use std::ops::Range;
+use std::path::Path;
+
+type Condition = Box<dyn Fn(&FrameAnalysis) -> bool + Send + Sync>;
+
+/// How long a condition must hold, over consecutive sampled frames, before a rule fires.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Window {
+    pub min_frames: usize,
+    /// Seconds between the first and last matching frame of the episode.
+    pub min_duration: f64,
+}
+
+/// A deterministic check over the frame timeline producing one `RiskFactor`.
+pub struct Rule {
+    pub name: String,
+    pub factor_type: RiskFactorType,
+    pub severity: f32,
+    pub window: Window,
+    condition: Condition,
+}
+
+impl Rule {
+    pub fn new(
+        name: &str,
+        factor_type: RiskFactorType,
+        severity: f32,
+        window: Window,
+        condition: impl Fn(&FrameAnalysis) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            factor_type,
+            severity: severity.clamp(0.0, 1.0),
+            window,
+            condition: Box::new(condition),
+        }
+    }
+
+    /// Runs of consecutive frames matching the condition that satisfy the window.
+    pub fn episodes(&self, frames: &[FrameAnalysis]) -> Vec<Range<usize>> {
+        let mut episodes = Vec::new();
+        let mut start = None;
+
+        for idx in 0..=frames.len() {
+            let matches = idx < frames.len() && (self.condition)(&frames[idx]);
+            match (matches, start) {
+                (true, None) => start = Some(idx),
+                (false, Some(first)) => {
+                    let episode = first..idx;
+                    if self.satisfies_window(frames, &episode) {
+                        episodes.push(episode);
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        episodes
+    }
+
+    fn satisfies_window(&self, frames: &[FrameAnalysis], episode: &Range<usize>) -> bool {
+        let duration = frames[episode.end - 1].timestamp - frames[episode.start].timestamp;
+        episode.len() >= self.window.min_frames.max(1) && duration >= self.window.min_duration
+    }
+
+    pub fn evaluate(&self, frames: &[FrameAnalysis]) -> Option<RiskFactor> {
+        let episodes = self.episodes(frames);
+        if episodes.is_empty() {
+            return None;
+        }
+
+        let mut indicator = vec![0.0; frames.len()];
+        for episode in &episodes {
+            for idx in episode.clone() {
+                indicator[idx] = 1.0;
+            }
+        }
+        let evidence_frames: Vec<u32> = episodes
+            .iter()
+            .flat_map(|episode| frames[episode.clone()].iter().map(|f| f.frame_number))
+            .collect();
+
+        Some(RiskFactor {
+            factor_type: self.factor_type.clone(),
+            severity: self.severity,
+            frequency: evidence_frames.len() as f32 / frames.len() as f32,
+            temporal_correlation: lag_one_correlation(&indicator),
+            sources: vec![RiskSource::Rule(self.name.clone())],
+            evidence_frames,
+        })
+    }
+}
+
+pub struct RiskEngine {
+    rules: Vec<Rule>,
+    custom: Option<RulesFile>,
+}
+
+impl RiskEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            custom: None,
+        }
+    }
+
+    /// Built-in rules plus any declared in the file named by `VGLNT_RULES_FILE`.
+    pub fn from_env() -> Result<Self> {
+        let mut engine = Self::default();
+        if let Ok(path) = std::env::var("VGLNT_RULES_FILE") {
+            engine.custom = Some(RulesFile::load(Path::new(&path))?);
+        }
+        Ok(engine)
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn evaluate(&self, frames: &[FrameAnalysis]) -> Vec<RiskFactor> {
+        let mut risks: Vec<RiskFactor> = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.evaluate(frames))
+            .collect();
+        if let Some(custom) = &self.custom {
+            risks.extend(
+                custom
+                    .rules()
+                    .iter()
+                    .filter_map(|rule| rule.evaluate(frames)),
+            );
+        }
+        risks
+    }
+}
+
+impl Default for RiskEngine {
+    fn default() -> Self {
+        Self::new(builtin_rules())
+    }
+}
+
+pub fn builtin_rules() -> Vec<Rule> {
+    let frames = |min_frames| Window {
+        min_frames,
+        min_duration: 0.0,
+    };
+    let seconds = |min_duration| Window {
+        min_frames: 2,
+        min_duration,
+    };
+
+    vec![
+        Rule::new(
+            "red_light_violation",
+            RiskFactorType::SignalCompliance,
+            0.9,
+            frames(2),
+            |f| {
+                let light = &f.signal_compliance.traffic_light;
+                matches!(light.status, SignalColor::Red) && !light.compliance
+            },
+        ),
+        Rule::new(
+            "sustained_unsafe_following",
+            RiskFactorType::FollowingDistance,
+            0.7,
+            seconds(3.0),
+            |f| matches!(f.following_distance.safe_distance, SafetyStatus::Unsafe),
+        ),
+        Rule::new(
+            "stop_sign_no_complete_stop",
+            RiskFactorType::IntersectionBehavior,
+            0.8,
+            frames(1),
+            |f| {
+                let stop_sign = &f.signal_compliance.stop_sign;
+                stop_sign.present
+                    && stop_sign.compliance == Some(false)
+                    && !f.intersection_behavior.complete_stop
+            },
+        ),
+        Rule::new(
+            "speed_limit_exceeded",
+            RiskFactorType::SpeedControl,
+            0.6,
+            frames(2),
+            |f| f.road_sign_awareness.speed_limit.compliance == Some(false),
+        ),
+        Rule::new(
+            "pedestrian_not_yielded",
+            RiskFactorType::PedestrianAwareness,
+            0.9,
+            frames(1),
+            |f| f.pedestrian_yielding.pedestrian_present && !f.pedestrian_yielding.proper_yielding,
+        ),
+        Rule::new(
+            "unsafe_merge",
+            RiskFactorType::MergingTechnique,
+            0.7,
+            frames(1),
+            |f| f.merging_lane_change.in_progress && !f.merging_lane_change.safe_merging,
+        ),
+        Rule::new(
+            "lane_departure",
+            RiskFactorType::LaneDeviation,
+            0.5,
+            seconds(2.0),
+            |f| !f.lane_centering.following_lane_discipline,
+        ),
+    ]
+}
+
+/// Merges model and rule risks so each `RiskFactorType` appears once.
+///
+/// Severity and frequency take the maximum of the merged entries, sources
+/// and evidence frames are combined, and the result is sorted by severity.
+pub fn merge(model: Vec<RiskFactor>, rules: Vec<RiskFactor>) -> Vec<RiskFactor> {
+    let mut merged: Vec<RiskFactor> = Vec::new();
+
+    for risk in model.into_iter().chain(rules) {
+        match merged
+            .iter_mut()
+            .find(|m| m.factor_type == risk.factor_type)
+        {
+            Some(existing) => {
+                if risk.severity > existing.severity {
+                    existing.severity = risk.severity;
+                    existing.temporal_correlation = risk.temporal_correlation;
+                }
+                existing.frequency = existing.frequency.max(risk.frequency);
+                for source in risk.sources {
+                    if !existing.sources.contains(&source) {
+                        existing.sources.push(source);
+                    }
+                }
+                existing.evidence_frames.extend(risk.evidence_frames);
+                existing.evidence_frames.sort_unstable();
+                existing.evidence_frames.dedup();
+            }
+            None => merged.push(risk),
+        }
+    }
+
+    merged.sort_by(|a, b| b.severity.total_cmp(&a.severity));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::timeline;
+
+    #[test]
+    fn test_single_red_frame_ignored() {
+        let mut frames = timeline(5);
+        frames[2].signal_compliance.traffic_light.status = SignalColor::Red;
+        frames[2].signal_compliance.traffic_light.compliance = false;
+
+        assert!(RiskEngine::default().evaluate(&frames).is_empty());
+    }
+
+    #[test]
+    fn test_red_light_violation() {
+        let mut frames = timeline(5);
+        for frame in &mut frames[1..3] {
+            frame.signal_compliance.traffic_light.status = SignalColor::Red;
+            frame.signal_compliance.traffic_light.compliance = false;
+        }
+
+        let risks = RiskEngine::default().evaluate(&frames);
+        assert_eq!(risks.len(), 1);
+        assert_eq!(risks[0].factor_type, RiskFactorType::SignalCompliance);
+        assert_eq!(risks[0].evidence_frames, vec![30, 60]);
+        assert_eq!(risks[0].frequency, 0.4);
+    }
+
+    #[test]
+    fn test_unsafe_following_needs_three_seconds() {
+        let mut frames = timeline(8);
+        for frame in &mut frames[0..3] {
+            frame.following_distance.safe_distance = SafetyStatus::Unsafe;
+        }
+        assert!(RiskEngine::default().evaluate(&frames).is_empty());
+
+        for frame in &mut frames[3..5] {
+            frame.following_distance.safe_distance = SafetyStatus::Unsafe;
+        }
+        let risks = RiskEngine::default().evaluate(&frames);
+        assert_eq!(risks.len(), 1);
+        assert_eq!(risks[0].factor_type, RiskFactorType::FollowingDistance);
+    }
+
+    #[test]
+    fn test_unsafe_merge_needs_merge_in_progress() {
+        let mut frames = timeline(5);
+        frames[2].merging_lane_change.safe_merging = false;
+        assert!(RiskEngine::default().evaluate(&frames).is_empty());
+
+        frames[2].merging_lane_change.in_progress = true;
+        let risks = RiskEngine::default().evaluate(&frames);
+        assert_eq!(risks.len(), 1);
+        assert_eq!(risks[0].factor_type, RiskFactorType::MergingTechnique);
+    }
+
+    #[test]
+    fn test_merge_combines_sources() {
+        let model = RiskFactor {
+            factor_type: RiskFactorType::SignalCompliance,
+            severity: 0.6,
+            frequency: 0.5,
+            temporal_correlation: 0.1,
+            sources: vec![RiskSource::Model],
+            evidence_frames: vec![60, 90],
+        };
+        let rule = RiskFactor {
+            factor_type: RiskFactorType::SignalCompliance,
+            severity: 0.9,
+            frequency: 0.4,
+            temporal_correlation: 0.3,
+            sources: vec![RiskSource::Rule("red_light_violation".to_string())],
+            evidence_frames: vec![30, 60],
+        };
+
+        let merged = merge(vec![model], vec![rule]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].severity, 0.9);
+        assert_eq!(merged[0].frequency, 0.5);
+        assert_eq!(merged[0].sources.len(), 2);
+        assert_eq!(merged[0].evidence_frames, vec![30, 60, 90]);
+    }
+}
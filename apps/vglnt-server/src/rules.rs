@@ -0,0 +1,397 @@
+//! Declarative risk rules loaded from a TOML file.
+//!
+//! ```toml
+//! [[rule]]
+//! name = "shoulder_without_emergency"
+//! severity = 0.6
+//! min_frames = 2        # optional, consecutive sampled frames
+//! min_duration = 1.0    # optional, seconds from first to last matching frame
+//! when = [
+//!     { field = "shoulder_use.using_shoulder", op = "==", value = true },
+//!     { field = "shoulder_use.emergency_situation", op = "==", value = false },
+//! ]
+//! ```
+//!
+//! `field` is a dotted path into the serialized `FrameAnalysis`; enums compare
+//! against their variant names (e.g. `"Red"`). All conditions must hold for a
+//! frame to match. Each rule produces `RiskFactorType::Other(name)`.
+
+use crate::risk::{Rule, Window};
+use crate::types::{FrameAnalysis, FrameData, RiskFactorType};
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use toml::Spanned;
+use tracing::{error, info};
+
+#[derive(Deserialize)]
+struct RulesDocument {
+    #[serde(default)]
+    rule: Vec<Spanned<RuleSpec>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    name: Spanned<String>,
+    severity: Spanned<f32>,
+    #[serde(default)]
+    min_frames: Option<usize>,
+    #[serde(default)]
+    min_duration: Option<f64>,
+    when: Vec<Spanned<ConditionSpec>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConditionSpec {
+    field: Spanned<String>,
+    op: Spanned<String>,
+    value: toml::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    pointer: String,
+    op: Op,
+    value: Value,
+}
+
+impl Condition {
+    fn matches(&self, frame: &Value) -> bool {
+        let Some(actual) = frame.pointer(&self.pointer) else {
+            return false;
+        };
+        match (actual.as_f64(), self.value.as_f64()) {
+            (Some(actual), Some(expected)) => match self.op {
+                Op::Eq => actual == expected,
+                Op::Ne => actual != expected,
+                Op::Lt => actual < expected,
+                Op::Le => actual <= expected,
+                Op::Gt => actual > expected,
+                Op::Ge => actual >= expected,
+            },
+            _ => match self.op {
+                Op::Eq => actual == &self.value,
+                Op::Ne => actual != &self.value,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// A validation problem tied to a line of the rules file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses and validates a rules document, reporting every problem found.
+pub fn parse_rules(source: &str) -> Result<Vec<Rule>, Vec<RuleError>> {
+    let line_of = |offset: usize| source[..offset.min(source.len())].matches('\n').count() + 1;
+
+    let document: RulesDocument = toml::from_str(source).map_err(|e| {
+        vec![RuleError {
+            line: e.span().map_or(1, |span| line_of(span.start)),
+            message: e.message().to_string(),
+        }]
+    })?;
+
+    // Every path a condition may reference, with its JSON type when known
+    let probe = serde_json::to_value(FrameData::default().into_analysis(0, 0.0))
+        .expect("FrameAnalysis serializes");
+
+    let mut errors = Vec::new();
+    let mut names = HashSet::new();
+    let mut rules = Vec::new();
+
+    for spec in document.rule {
+        let rule_line = line_of(spec.span().start);
+        let spec = spec.into_inner();
+        let name = spec.name.get_ref().trim().to_string();
+        let mut rule_errors = Vec::new();
+
+        if name.is_empty() {
+            rule_errors.push(RuleError {
+                line: line_of(spec.name.span().start),
+                message: "rule name must not be empty".to_string(),
+            });
+        } else if !names.insert(name.clone()) {
+            rule_errors.push(RuleError {
+                line: line_of(spec.name.span().start),
+                message: format!("duplicate rule name `{}`", name),
+            });
+        }
+        if !(0.0..=1.0).contains(spec.severity.get_ref()) {
+            rule_errors.push(RuleError {
+                line: line_of(spec.severity.span().start),
+                message: format!("severity {} is outside 0.0–1.0", spec.severity.get_ref()),
+            });
+        }
+        if spec.when.is_empty() {
+            rule_errors.push(RuleError {
+                line: rule_line,
+                message: format!("rule `{}` has no `when` conditions", name),
+            });
+        }
+
+        let mut conditions = Vec::new();
+        for condition in spec.when {
+            let line = line_of(condition.span().start);
+            match validate_condition(condition.into_inner(), &probe) {
+                Ok(condition) => conditions.push(condition),
+                Err(message) => rule_errors.push(RuleError { line, message }),
+            }
+        }
+
+        if rule_errors.is_empty() {
+            let window = Window {
+                min_frames: spec.min_frames.unwrap_or(1),
+                min_duration: spec.min_duration.unwrap_or(0.0),
+            };
+            rules.push(Rule::new(
+                &name,
+                RiskFactorType::Other(name.clone()),
+                *spec.severity.get_ref(),
+                window,
+                move |frame: &FrameAnalysis| {
+                    let Ok(frame) = serde_json::to_value(frame) else {
+                        return false;
+                    };
+                    conditions.iter().all(|c| c.matches(&frame))
+                },
+            ));
+        }
+        errors.extend(rule_errors);
+    }
+
+    if errors.is_empty() {
+        Ok(rules)
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_condition(spec: ConditionSpec, probe: &Value) -> Result<Condition, String> {
+    let field = spec.field.into_inner();
+    let pointer = format!("/{}", field.replace('.', "/"));
+    let Some(probe_value) = probe.pointer(&pointer) else {
+        return Err(format!("unknown field `{}`", field));
+    };
+    if probe_value.is_object() || probe_value.is_array() {
+        return Err(format!("field `{}` is not a single value", field));
+    }
+
+    let op = match spec.op.get_ref().as_str() {
+        "==" => Op::Eq,
+        "!=" => Op::Ne,
+        "<" => Op::Lt,
+        "<=" => Op::Le,
+        ">" => Op::Gt,
+        ">=" => Op::Ge,
+        other => return Err(format!("unknown operator `{}`", other)),
+    };
+
+    let value = serde_json::to_value(&spec.value).map_err(|e| e.to_string())?;
+    let compatible = match probe_value {
+        Value::Null => true,
+        Value::Bool(_) => value.is_boolean(),
+        Value::Number(_) => value.is_number(),
+        Value::String(_) => value.is_string(),
+        _ => false,
+    };
+    if !compatible {
+        return Err(format!(
+            "`{}` cannot be compared with {}",
+            field, spec.value
+        ));
+    }
+    if !matches!(op, Op::Eq | Op::Ne) && !value.is_number() {
+        return Err(format!("`{}` only supports == and !=", field));
+    }
+
+    Ok(Condition { pointer, op, value })
+}
+
+struct LoadedRules {
+    modified: Option<SystemTime>,
+    rules: Arc<Vec<Rule>>,
+}
+
+/// A rules file that is re-read whenever its modification time changes.
+///
+/// A reload that fails validation is logged and the previous rules are kept.
+pub struct RulesFile {
+    path: PathBuf,
+    loaded: RwLock<LoadedRules>,
+}
+
+impl RulesFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let (modified, rules) = read_rules(path)?;
+        info!(
+            "Loaded {} custom rules from {}",
+            rules.len(),
+            path.display()
+        );
+        Ok(Self {
+            path: path.to_path_buf(),
+            loaded: RwLock::new(LoadedRules {
+                modified,
+                rules: Arc::new(rules),
+            }),
+        })
+    }
+
+    pub fn rules(&self) -> Arc<Vec<Rule>> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        {
+            let loaded = self.loaded.read().unwrap();
+            if modified.is_none() || loaded.modified == modified {
+                return Arc::clone(&loaded.rules);
+            }
+        }
+
+        let mut loaded = self.loaded.write().unwrap();
+        if loaded.modified != modified {
+            // Record the attempt so a broken file is not re-parsed on every call
+            loaded.modified = modified;
+            match read_rules(&self.path) {
+                Ok((_, rules)) => {
+                    info!(
+                        "Reloaded {} custom rules from {}",
+                        rules.len(),
+                        self.path.display()
+                    );
+                    loaded.rules = Arc::new(rules);
+                }
+                Err(e) => error!("Keeping previous rules: {:#}", e),
+            }
+        }
+        Arc::clone(&loaded.rules)
+    }
+}
+
+fn read_rules(path: &Path) -> Result<(Option<SystemTime>, Vec<Rule>)> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read rules from {}: {}", path.display(), e))?;
+    let rules = parse_rules(&source).map_err(|errors| {
+        let details: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        anyhow::anyhow!(
+            "Invalid rules in {}:\n{}",
+            path.display(),
+            details.join("\n")
+        )
+    })?;
+    Ok((modified, rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::frame;
+
+    const SHOULDER_RULE: &str = r#"
+[[rule]]
+name = "shoulder_without_emergency"
+severity = 0.6
+min_frames = 2
+when = [
+    { field = "shoulder_use.using_shoulder", op = "==", value = true },
+    { field = "shoulder_use.emergency_situation", op = "==", value = false },
+]
+"#;
+
+    #[test]
+    fn test_shoulder_rule() {
+        let rules = parse_rules(SHOULDER_RULE).unwrap();
+        let mut frames: Vec<_> = (0..4).map(|idx| frame(idx, idx as f64)).collect();
+        frames[1].shoulder_use.using_shoulder = true;
+        frames[2].shoulder_use.using_shoulder = true;
+
+        let risk = rules[0].evaluate(&frames).unwrap();
+        assert_eq!(
+            risk.factor_type,
+            RiskFactorType::Other("shoulder_without_emergency".to_string())
+        );
+        assert_eq!(risk.evidence_frames, vec![1, 2]);
+
+        frames[2].shoulder_use.emergency_situation = true;
+        assert!(rules[0].evaluate(&frames).is_none());
+    }
+
+    #[test]
+    fn test_numeric_and_enum_conditions() {
+        let rules = parse_rules(
+            r#"
+[[rule]]
+name = "low_score_on_red"
+severity = 0.4
+when = [
+    { field = "signal_compliance.traffic_light.status", op = "==", value = "Red" },
+    { field = "signal_compliance.score", op = "<", value = 10 },
+]
+"#,
+        )
+        .unwrap();
+        let mut frames = vec![frame(0, 0.0)];
+        frames[0].signal_compliance.traffic_light.status = crate::types::SignalColor::Red;
+        frames[0].signal_compliance.score = 5.0;
+
+        assert!(rules[0].evaluate(&frames).is_some());
+    }
+
+    #[test]
+    fn test_errors_have_line_numbers() {
+        let errors = parse_rules(
+            r#"[[rule]]
+name = "bad"
+severity = 1.5
+when = [
+    { field = "shoulder_use.not_a_field", op = "==", value = true },
+    { field = "lane_centering.score", op = "~", value = 1 },
+]
+"#,
+        )
+        .err()
+        .unwrap();
+
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![3, 5, 6]);
+        assert!(errors[1].message.contains("unknown field"));
+    }
+
+    #[test]
+    fn test_syntax_error_line() {
+        let errors = parse_rules("[[rule]]\nname = \"x\"\nseverity = \n")
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+    }
+}
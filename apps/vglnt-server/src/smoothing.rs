@@ -0,0 +1,267 @@
+//! Temporal smoothing of the vision model timeline.
+//!
+//! Scores are median-filtered and categorical fields majority-voted over a
+//! centred window. Violation flags (a failed compliance, a lane departure,
+//! shoulder use) go through hysteresis instead: a violation must persist for
+//! `enter_frames` frames before it is reported, and a reported violation ends
+//! only after `exit_frames` frames without it. By default a violation needs
+//! two consecutive sampled frames, so a single misread frame never reaches
+//! the features, events or risks.
+
+use crate::types::FrameAnalysis;
+use std::ops::Range;
+
+const DEFAULT_WINDOW: usize = 3;
+const DEFAULT_ENTER_FRAMES: usize = 2;
+const DEFAULT_EXIT_FRAMES: usize = 2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Smoother {
+    /// Width of the median/majority window in sampled frames; 1 disables it.
+    pub window: usize,
+    pub enter_frames: usize,
+    pub exit_frames: usize,
+}
+
+impl Default for Smoother {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_WINDOW,
+            enter_frames: DEFAULT_ENTER_FRAMES,
+            exit_frames: DEFAULT_EXIT_FRAMES,
+        }
+    }
+}
+
+impl Smoother {
+    /// Reads `VGLNT_SMOOTHING_WINDOW`, `VGLNT_HYSTERESIS_ENTER` and
+    /// `VGLNT_HYSTERESIS_EXIT`, falling back to the defaults.
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            window: var("VGLNT_SMOOTHING_WINDOW", DEFAULT_WINDOW),
+            enter_frames: var("VGLNT_HYSTERESIS_ENTER", DEFAULT_ENTER_FRAMES),
+            exit_frames: var("VGLNT_HYSTERESIS_EXIT", DEFAULT_EXIT_FRAMES),
+        }
+    }
+
+    /// Returns a smoothed copy of `raw`; frame numbers and timestamps are unchanged.
+    pub fn smooth(&self, raw: &[FrameAnalysis]) -> Vec<FrameAnalysis> {
+        let mut frames = raw.to_vec();
+
+        // Scores
+        self.median(&mut frames, |f| &mut f.lane_centering.score);
+        self.median(&mut frames, |f| &mut f.following_distance.score);
+        self.median(&mut frames, |f| &mut f.signal_compliance.score);
+        self.median(&mut frames, |f| &mut f.merging_lane_change.score);
+        self.median(&mut frames, |f| &mut f.pedestrian_yielding.score);
+        self.median(&mut frames, |f| &mut f.intersection_behavior.score);
+        self.median(&mut frames, |f| &mut f.road_sign_awareness.score);
+        self.median(&mut frames, |f| &mut f.shoulder_use.score);
+
+        // Scene state
+        self.majority(&mut frames, |f| {
+            &mut f.signal_compliance.traffic_light.status
+        });
+        self.majority(&mut frames, |f| &mut f.following_distance.safe_distance);
+        self.majority(&mut frames, |f| &mut f.signal_compliance.stop_sign.present);
+        self.majority(&mut frames, |f| {
+            &mut f.signal_compliance.stop_sign.compliance
+        });
+        self.majority(&mut frames, |f| {
+            &mut f.pedestrian_yielding.pedestrian_present
+        });
+        self.majority(&mut frames, |f| &mut f.merging_lane_change.in_progress);
+        self.majority(&mut frames, |f| {
+            &mut f.road_sign_awareness.speed_limit.compliance
+        });
+
+        // Violations, where `false` means the driver is out of compliance
+        self.debounce_compliance(&mut frames, |f| {
+            &mut f.signal_compliance.traffic_light.compliance
+        });
+        self.debounce_compliance(&mut frames, |f| {
+            &mut f.lane_centering.following_lane_discipline
+        });
+        self.debounce_compliance(&mut frames, |f| &mut f.merging_lane_change.safe_merging);
+        self.debounce_compliance(&mut frames, |f| &mut f.pedestrian_yielding.proper_yielding);
+        self.debounce_compliance(&mut frames, |f| {
+            &mut f.intersection_behavior.stop_line_observance
+        });
+        self.debounce_violation(&mut frames, |f| &mut f.shoulder_use.using_shoulder);
+
+        frames
+    }
+
+    fn median(&self, frames: &mut [FrameAnalysis], field: fn(&mut FrameAnalysis) -> &mut f32) {
+        let raw: Vec<f32> = frames.iter_mut().map(|f| *field(f)).collect();
+        for (idx, frame) in frames.iter_mut().enumerate() {
+            let mut values = raw[self.span(idx, raw.len())].to_vec();
+            values.sort_by(f32::total_cmp);
+            *field(frame) = values[values.len() / 2];
+        }
+    }
+
+    fn majority<T: Clone + PartialEq>(
+        &self,
+        frames: &mut [FrameAnalysis],
+        field: fn(&mut FrameAnalysis) -> &mut T,
+    ) {
+        let raw: Vec<T> = frames.iter_mut().map(|f| field(f).clone()).collect();
+        for (idx, frame) in frames.iter_mut().enumerate() {
+            let values = &raw[self.span(idx, raw.len())];
+            let count = |value: &T| values.iter().filter(|v| *v == value).count();
+            // Ties keep the frame's own value
+            let mut best = &raw[idx];
+            for value in values {
+                if count(value) > count(best) {
+                    best = value;
+                }
+            }
+            *field(frame) = best.clone();
+        }
+    }
+
+    /// Applies hysteresis to a flag whose `false` value marks a violation.
+    fn debounce_compliance(
+        &self,
+        frames: &mut [FrameAnalysis],
+        field: fn(&mut FrameAnalysis) -> &mut bool,
+    ) {
+        let violations: Vec<bool> = frames.iter_mut().map(|f| !*field(f)).collect();
+        let violations = hysteresis(&violations, self.enter_frames, self.exit_frames);
+        for (frame, violation) in frames.iter_mut().zip(violations) {
+            *field(frame) = !violation;
+        }
+    }
+
+    /// Applies hysteresis to a flag whose `true` value marks a violation.
+    fn debounce_violation(
+        &self,
+        frames: &mut [FrameAnalysis],
+        field: fn(&mut FrameAnalysis) -> &mut bool,
+    ) {
+        let violations: Vec<bool> = frames.iter_mut().map(|f| *field(f)).collect();
+        let violations = hysteresis(&violations, self.enter_frames, self.exit_frames);
+        for (frame, violation) in frames.iter_mut().zip(violations) {
+            *field(frame) = violation;
+        }
+    }
+
+    fn span(&self, idx: usize, len: usize) -> Range<usize> {
+        let half = self.window.max(1) / 2;
+        idx.saturating_sub(half)..(idx + half + 1).min(len)
+    }
+}
+
+/// Keeps runs of `active` frames at least `enter` long, then bridges gaps
+/// shorter than `exit` between the runs that remain.
+pub fn hysteresis(active: &[bool], enter: usize, exit: usize) -> Vec<bool> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut start = None;
+    for idx in 0..=active.len() {
+        match (idx < active.len() && active[idx], start) {
+            (true, None) => start = Some(idx),
+            (false, Some(first)) => {
+                if idx - first >= enter.max(1) {
+                    runs.push(first..idx);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    let mut smoothed = vec![false; active.len()];
+    for (idx, run) in runs.iter().enumerate() {
+        let end = match runs.get(idx + 1) {
+            Some(next) if next.start - run.end < exit => next.start,
+            _ => run.end,
+        };
+        for flag in &mut smoothed[run.start..end] {
+            *flag = true;
+        }
+    }
+    smoothed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::timeline;
+    use crate::types::SignalColor;
+
+    #[test]
+    fn test_hysteresis() {
+        let raw = [
+            false, true, false, true, true, false, true, true, false, false,
+        ];
+        assert_eq!(
+            hysteresis(&raw, 2, 2),
+            [false, false, false, true, true, true, true, true, false, false]
+        );
+        assert_eq!(hysteresis(&raw, 1, 1), raw);
+    }
+
+    #[test]
+    fn test_single_frame_flicker_suppressed() {
+        let mut raw = timeline(5);
+        raw[2].signal_compliance.traffic_light.compliance = false;
+        raw[3].signal_compliance.traffic_light.status = SignalColor::Red;
+        raw[1].lane_centering.score = 0.0;
+
+        let strict = Smoother {
+            enter_frames: 2,
+            ..Default::default()
+        };
+        let smoothed = strict.smooth(&raw);
+        assert!(smoothed
+            .iter()
+            .all(|f| f.signal_compliance.traffic_light.compliance));
+        assert!(smoothed
+            .iter()
+            .all(|f| matches!(f.signal_compliance.traffic_light.status, SignalColor::Green)));
+        assert_eq!(smoothed[1].lane_centering.score, 20.0);
+        assert!(!raw[2].signal_compliance.traffic_light.compliance);
+    }
+
+    #[test]
+    fn test_single_frame_violation_suppressed_by_default() {
+        let mut raw = timeline(6);
+        raw[1].signal_compliance.traffic_light.compliance = false;
+        raw[1].pedestrian_yielding.proper_yielding = false;
+        for frame in &mut raw[3..5] {
+            frame.signal_compliance.traffic_light.compliance = false;
+        }
+
+        let smoothed = Smoother::default().smooth(&raw);
+        let flags: Vec<bool> = smoothed
+            .iter()
+            .map(|f| f.signal_compliance.traffic_light.compliance)
+            .collect();
+        assert_eq!(flags, [true, true, true, false, false, true]);
+        assert!(smoothed
+            .iter()
+            .all(|f| f.pedestrian_yielding.proper_yielding));
+    }
+
+    #[test]
+    fn test_sustained_violation_kept() {
+        let mut raw = timeline(6);
+        for frame in &mut raw[2..5] {
+            frame.shoulder_use.using_shoulder = true;
+        }
+
+        let smoothed = Smoother::default().smooth(&raw);
+        let flags: Vec<bool> = smoothed
+            .iter()
+            .map(|f| f.shoulder_use.using_shoulder)
+            .collect();
+        assert_eq!(flags, [false, false, true, true, true, false]);
+    }
+}
@@ -0,0 +1,278 @@
+//! Aggregate `DrivingStats` for a drive.
+//!
+//! Encounters and lane changes come from the frame timeline; a traffic light
+//! encounter is a run of frames with a light in view, whatever its colour. Kinematic stats
+//! need a speed series, taken from telemetry when given and otherwise from
+//! the per-frame `current_speed`; without at least two samples they stay
+//! `None`. Harsh braking and rapid acceleration use measured acceleration
+//! when the telemetry has it, and speed differences otherwise.
+
+use crate::types::{DrivingStats, FrameAnalysis, SignalColor, Telemetry, TelemetrySample};
+
+/// Deceleration beyond this, in m/s², is a harsh braking event.
+const HARSH_BRAKING_MPS2: f64 = 3.0;
+/// Acceleration beyond this, in m/s², is a rapid acceleration event.
+const RAPID_ACCELERATION_MPS2: f64 = 2.5;
+/// A jump in `deviation_from_center` this large, in metres, crossing the
+/// lane centre means the car has moved into the next lane.
+const LANE_CHANGE_DEVIATION_JUMP: f32 = 1.5;
+
+/// One speed reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SpeedSample {
+    /// Seconds from the start of the video.
+    timestamp: f64,
+    /// Speed in km/h.
+    speed: f32,
+}
+
+pub fn driving_stats(
+    frames: &[FrameAnalysis],
+    telemetry: Option<&Telemetry>,
+    total_duration: f64,
+) -> DrivingStats {
+    let samples: Vec<&TelemetrySample> = telemetry
+        .map(|t| t.samples.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|s| (0.0..=total_duration).contains(&s.timestamp))
+        .collect();
+    let telemetry_speeds: Vec<SpeedSample> = samples
+        .iter()
+        .filter_map(|s| {
+            Some(SpeedSample {
+                timestamp: s.timestamp,
+                speed: s.speed?,
+            })
+        })
+        .collect();
+    let accelerations: Vec<f64> = samples
+        .iter()
+        .filter_map(|s| s.acceleration.map(f64::from))
+        .collect();
+
+    let speeds = if telemetry_speeds.is_empty() {
+        frame_speeds(frames)
+    } else {
+        telemetry_speeds
+    };
+    let kinematics = Kinematics::from_samples(&speeds);
+
+    let mut stats = DrivingStats {
+        total_duration,
+        distance_covered: kinematics.as_ref().map(|k| k.distance_km),
+        average_speed: kinematics.as_ref().map(|k| k.average_speed),
+        max_speed: kinematics.as_ref().map(|k| k.max_speed),
+        harsh_braking_count: kinematics.as_ref().map(|k| k.harsh_braking_count),
+        rapid_acceleration_count: kinematics.as_ref().map(|k| k.rapid_acceleration_count),
+        traffic_light_encounters: count_episodes(frames, |f| {
+            !matches!(
+                f.signal_compliance.traffic_light.status,
+                SignalColor::Unknown
+            )
+        }),
+        stop_sign_encounters: count_episodes(frames, |f| f.signal_compliance.stop_sign.present),
+        lane_changes: count_lane_changes(frames),
+    };
+    if !accelerations.is_empty() {
+        let braking: Vec<bool> = accelerations
+            .iter()
+            .map(|a| *a <= -HARSH_BRAKING_MPS2)
+            .collect();
+        let accelerating: Vec<bool> = accelerations
+            .iter()
+            .map(|a| *a >= RAPID_ACCELERATION_MPS2)
+            .collect();
+        stats.harsh_braking_count = Some(count_runs(&braking));
+        stats.rapid_acceleration_count = Some(count_runs(&accelerating));
+    }
+    stats
+}
+
+fn frame_speeds(frames: &[FrameAnalysis]) -> Vec<SpeedSample> {
+    frames
+        .iter()
+        .filter_map(|f| {
+            let speed = f.road_sign_awareness.speed_limit.current_speed?;
+            Some(SpeedSample {
+                timestamp: f.timestamp,
+                speed,
+            })
+        })
+        .collect()
+}
+
+struct Kinematics {
+    distance_km: f32,
+    average_speed: f32,
+    max_speed: f32,
+    harsh_braking_count: u32,
+    rapid_acceleration_count: u32,
+}
+
+impl Kinematics {
+    fn from_samples(samples: &[SpeedSample]) -> Option<Self> {
+        let elapsed = samples.last()?.timestamp - samples.first()?.timestamp;
+        if samples.len() < 2 || elapsed <= 0.0 {
+            return None;
+        }
+
+        let mut distance_km = 0.0;
+        let mut braking = Vec::with_capacity(samples.len());
+        let mut accelerating = Vec::with_capacity(samples.len());
+        for pair in samples.windows(2) {
+            let dt = pair[1].timestamp - pair[0].timestamp;
+            if dt <= 0.0 {
+                continue;
+            }
+            let (v0, v1) = (pair[0].speed as f64, pair[1].speed as f64);
+            distance_km += (v0 + v1) / 2.0 * dt / 3600.0;
+            let acceleration = (v1 - v0) / 3.6 / dt;
+            braking.push(acceleration <= -HARSH_BRAKING_MPS2);
+            accelerating.push(acceleration >= RAPID_ACCELERATION_MPS2);
+        }
+
+        Some(Self {
+            distance_km: distance_km as f32,
+            average_speed: (distance_km / (elapsed / 3600.0)) as f32,
+            max_speed: samples.iter().map(|s| s.speed).fold(0.0, f32::max),
+            harsh_braking_count: count_runs(&braking),
+            rapid_acceleration_count: count_runs(&accelerating),
+        })
+    }
+}
+
+/// Counts runs of consecutive frames matching `present`, so a sign or light
+/// seen over several frames is one encounter.
+fn count_episodes(frames: &[FrameAnalysis], present: impl Fn(&FrameAnalysis) -> bool) -> u32 {
+    let flags: Vec<bool> = frames.iter().map(present).collect();
+    count_runs(&flags)
+}
+
+/// A lane change is a run of frames that either show one under way (a merge
+/// in progress or a signal used) or follow a jump across the lane centre.
+fn count_lane_changes(frames: &[FrameAnalysis]) -> u32 {
+    let flags: Vec<bool> = frames
+        .iter()
+        .enumerate()
+        .map(|(idx, f)| {
+            let merging = &f.merging_lane_change;
+            let crossed = idx > 0 && {
+                let before = frames[idx - 1].lane_centering.deviation_from_center;
+                let after = f.lane_centering.deviation_from_center;
+                before.signum() != after.signum()
+                    && (after - before).abs() >= LANE_CHANGE_DEVIATION_JUMP
+            };
+            merging.in_progress || merging.signal_used || crossed
+        })
+        .collect();
+    count_runs(&flags)
+}
+
+fn count_runs(flags: &[bool]) -> u32 {
+    flags
+        .iter()
+        .enumerate()
+        .filter(|(idx, flag)| **flag && (*idx == 0 || !flags[idx - 1]))
+        .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::timeline;
+    use crate::types::TelemetryFormat;
+
+    fn telemetry(speeds: &[f32], accelerations: &[Option<f32>]) -> Telemetry {
+        Telemetry {
+            format: TelemetryFormat::Csv,
+            samples: speeds
+                .iter()
+                .zip(accelerations)
+                .enumerate()
+                .map(|(idx, (speed, acceleration))| TelemetrySample {
+                    timestamp: idx as f64 * 2.0,
+                    speed: Some(*speed),
+                    acceleration: *acceleration,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_kinematics_unknown_without_speed() {
+        let stats = driving_stats(&timeline(4), None, 4.0);
+        assert_eq!(stats.distance_covered, None);
+        assert_eq!(stats.max_speed, None);
+        assert_eq!(stats.harsh_braking_count, None);
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert!(json["average_speed"].is_null());
+    }
+
+    #[test]
+    fn test_kinematics_from_telemetry() {
+        let speeds = [36.0, 36.0, 72.0, 72.0, 36.0, 36.0];
+        let telemetry = telemetry(&speeds, &[None; 6]);
+
+        let stats = driving_stats(&timeline(2), Some(&telemetry), 10.0);
+        assert_eq!(stats.max_speed, Some(72.0));
+        assert!((stats.distance_covered.unwrap() - 0.14).abs() < 1e-5);
+        assert!((stats.average_speed.unwrap() - 50.4).abs() < 1e-3);
+        assert_eq!(stats.harsh_braking_count, Some(1));
+        assert_eq!(stats.rapid_acceleration_count, Some(1));
+    }
+
+    #[test]
+    fn test_measured_acceleration_preferred() {
+        let speeds = [36.0, 36.0, 72.0, 72.0, 36.0, 36.0];
+        let accelerations = [
+            Some(0.0),
+            Some(-3.5),
+            Some(0.0),
+            Some(-4.0),
+            Some(-3.2),
+            Some(0.0),
+        ];
+        let telemetry = telemetry(&speeds, &accelerations);
+
+        let stats = driving_stats(&timeline(2), Some(&telemetry), 10.0);
+        assert_eq!(stats.harsh_braking_count, Some(2));
+        assert_eq!(stats.rapid_acceleration_count, Some(0));
+    }
+
+    #[test]
+    fn test_encounters_deduplicated() {
+        let mut frames = timeline(8);
+        for idx in [1, 2, 3, 6] {
+            frames[idx].signal_compliance.traffic_light.status = SignalColor::Unknown;
+        }
+        frames[4].signal_compliance.stop_sign.present = true;
+        frames[5].signal_compliance.stop_sign.present = true;
+        frames[6].merging_lane_change.in_progress = true;
+        // Not a lane change: the model reports no merge under way
+        frames[2].merging_lane_change.safe_merging = false;
+
+        let stats = driving_stats(&frames, None, 8.0);
+        assert_eq!(stats.traffic_light_encounters, 3);
+        assert_eq!(stats.stop_sign_encounters, 1);
+        assert_eq!(stats.lane_changes, 1);
+    }
+
+    #[test]
+    fn test_traffic_light_encounters_need_a_light() {
+        let mut frames = timeline(6);
+        for frame in &mut frames {
+            frame.signal_compliance.traffic_light.status = SignalColor::Unknown;
+        }
+        let encounters =
+            |frames: &[FrameAnalysis]| driving_stats(frames, None, 6.0).traffic_light_encounters;
+        assert_eq!(encounters(&frames), 0);
+
+        // One light changing colour is still one encounter
+        frames[2].signal_compliance.traffic_light.status = SignalColor::Red;
+        frames[3].signal_compliance.traffic_light.status = SignalColor::Green;
+        assert_eq!(encounters(&frames), 1);
+    }
+}
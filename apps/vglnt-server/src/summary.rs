@@ -0,0 +1,262 @@
+//! Maps the model score, critical events and risk factors to a `RiskLevel`.
+//!
+//! The score sets a base level from the configured cut-offs; escalation rules
+//! can only raise it. Every input that set or raised the level is recorded as
+//! a `RiskDriver` so the result can be audited.
+
+use crate::types::{
+    AnalysisSummary, CriticalEvent, DrivingStats, ImprovementArea, LSTMOutput, RiskDriver,
+    RiskLevel,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Cut-offs and escalation rules, loadable from TOML; omitted keys keep their defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskLevelConfig {
+    /// Minimum overall score for `Low`.
+    pub low: f32,
+    /// Minimum overall score for `Medium`.
+    pub medium: f32,
+    /// Minimum overall score for `High`; anything below is `Critical`.
+    pub high: f32,
+    /// Events at or above this severity escalate to `critical_event_level`.
+    pub critical_event_severity: f32,
+    pub critical_event_level: RiskLevel,
+    /// Any other event escalates to `event_level`.
+    pub event_level: RiskLevel,
+    /// Risk factors at or above this severity escalate to `risk_factor_level`.
+    pub risk_factor_severity: f32,
+    pub risk_factor_level: RiskLevel,
+}
+
+impl Default for RiskLevelConfig {
+    fn default() -> Self {
+        Self {
+            low: 80.0,
+            medium: 60.0,
+            high: 40.0,
+            critical_event_severity: 0.8,
+            critical_event_level: RiskLevel::High,
+            event_level: RiskLevel::Medium,
+            risk_factor_severity: 0.8,
+            risk_factor_level: RiskLevel::Medium,
+        }
+    }
+}
+
+impl RiskLevelConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read risk levels from {}", path.display()))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("Invalid risk levels in {}", path.display()))?;
+        if !(config.low >= config.medium && config.medium >= config.high) {
+            anyhow::bail!(
+                "Risk level cut-offs in {} must satisfy low >= medium >= high",
+                path.display()
+            );
+        }
+        Ok(config)
+    }
+
+    fn level_for_score(&self, score: f32) -> (RiskLevel, String) {
+        let (level, detail) = if score >= self.low {
+            (
+                RiskLevel::Low,
+                format!("at or above the Low cut-off of {:.1}", self.low),
+            )
+        } else if score >= self.medium {
+            (
+                RiskLevel::Medium,
+                format!("below the Low cut-off of {:.1}", self.low),
+            )
+        } else if score >= self.high {
+            (
+                RiskLevel::High,
+                format!("below the Medium cut-off of {:.1}", self.medium),
+            )
+        } else {
+            (
+                RiskLevel::Critical,
+                format!("below the High cut-off of {:.1}", self.high),
+            )
+        };
+        (level, format!("overall score {:.1} is {}", score, detail))
+    }
+}
+
+pub struct SummaryBuilder {
+    config: RiskLevelConfig,
+}
+
+impl SummaryBuilder {
+    pub fn new(config: RiskLevelConfig) -> Self {
+        Self { config }
+    }
+
+    /// Reads cut-offs from the TOML file named by `VGLNT_RISK_LEVELS_FILE`, if set.
+    pub fn from_env() -> Result<Self> {
+        let config = match std::env::var("VGLNT_RISK_LEVELS_FILE") {
+            Ok(path) => RiskLevelConfig::load(Path::new(&path))?,
+            Err(_) => RiskLevelConfig::default(),
+        };
+        Ok(Self::new(config))
+    }
+
+    pub fn build(
+        &self,
+        lstm_output: &LSTMOutput,
+        critical_events: Vec<CriticalEvent>,
+        improvement_areas: Vec<ImprovementArea>,
+        stats: DrivingStats,
+    ) -> AnalysisSummary {
+        let overall_score = lstm_output.overall_safety_score;
+        let (risk_level, risk_drivers) = self.risk_level(lstm_output, &critical_events);
+
+        AnalysisSummary {
+            overall_score,
+            risk_level,
+            risk_drivers,
+            critical_events,
+            improvement_areas,
+            stats,
+        }
+    }
+
+    /// The final level and the drivers that set or raised it.
+    pub fn risk_level(
+        &self,
+        lstm_output: &LSTMOutput,
+        critical_events: &[CriticalEvent],
+    ) -> (RiskLevel, Vec<RiskDriver>) {
+        let config = &self.config;
+        let (mut level, detail) = config.level_for_score(lstm_output.overall_safety_score);
+        let mut drivers = vec![RiskDriver {
+            input: "overall_score".to_string(),
+            detail,
+            level,
+        }];
+
+        let mut escalate = |input: &str, detail: String, floor: RiskLevel| {
+            if floor > level {
+                level = floor;
+                drivers.push(RiskDriver {
+                    input: input.to_string(),
+                    detail,
+                    level: floor,
+                });
+            }
+        };
+
+        for event in critical_events {
+            let floor = if event.severity >= config.critical_event_severity {
+                config.critical_event_level
+            } else {
+                config.event_level
+            };
+            escalate(
+                "critical_event",
+                format!(
+                    "{} at {:.1}s with severity {:.2}",
+                    event.event_type, event.timestamp, event.severity
+                ),
+                floor,
+            );
+        }
+        for risk in &lstm_output.risk_factors {
+            if risk.severity >= config.risk_factor_severity {
+                escalate(
+                    "risk_factor",
+                    format!("{:?} with severity {:.2}", risk.factor_type, risk.severity),
+                    config.risk_factor_level,
+                );
+            }
+        }
+
+        (level, drivers)
+    }
+}
+
+impl Default for SummaryBuilder {
+    fn default() -> Self {
+        Self::new(RiskLevelConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{RiskFactor, RiskFactorType, RiskSource};
+    use std::collections::HashMap;
+
+    fn output(score: f32, risks: Vec<RiskFactor>) -> LSTMOutput {
+        LSTMOutput {
+            overall_safety_score: score,
+            risk_factors: risks,
+            temporal_patterns: Vec::new(),
+            behavioral_metrics: None,
+        }
+    }
+
+    fn event(severity: f32) -> CriticalEvent {
+        CriticalEvent {
+            event_type: "red_light_run".to_string(),
+            timestamp: 12.0,
+            end_timestamp: 13.0,
+            severity,
+            frames: vec![360, 390],
+            context: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_score_cut_offs() {
+        let builder = SummaryBuilder::default();
+        let level = |score| builder.risk_level(&output(score, Vec::new()), &[]).0;
+        assert_eq!(level(85.0), RiskLevel::Low);
+        assert_eq!(level(80.0), RiskLevel::Low);
+        assert_eq!(level(65.0), RiskLevel::Medium);
+        assert_eq!(level(45.0), RiskLevel::High);
+        assert_eq!(level(10.0), RiskLevel::Critical);
+    }
+
+    #[test]
+    fn test_critical_event_escalates() {
+        let builder = SummaryBuilder::default();
+        let (level, drivers) = builder.risk_level(&output(92.0, Vec::new()), &[event(0.9)]);
+        assert_eq!(level, RiskLevel::High);
+        assert_eq!(drivers.len(), 2);
+        assert_eq!(drivers[1].input, "critical_event");
+
+        let (level, _) = builder.risk_level(&output(92.0, Vec::new()), &[event(0.5)]);
+        assert_eq!(level, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn test_escalation_never_lowers() {
+        let risk = RiskFactor {
+            factor_type: RiskFactorType::SignalCompliance,
+            severity: 0.9,
+            frequency: 0.2,
+            temporal_correlation: 0.0,
+            sources: vec![RiskSource::Model],
+            evidence_frames: Vec::new(),
+        };
+        let (level, drivers) =
+            SummaryBuilder::default().risk_level(&output(20.0, vec![risk]), &[event(0.9)]);
+        assert_eq!(level, RiskLevel::Critical);
+        assert_eq!(drivers.len(), 1);
+    }
+
+    #[test]
+    fn test_config_from_toml() {
+        let config: RiskLevelConfig =
+            toml::from_str("low = 90.0\ncritical_event_level = \"Critical\"\n").unwrap();
+        assert_eq!(config.low, 90.0);
+        assert_eq!(config.medium, 60.0);
+        assert_eq!(config.critical_event_level, RiskLevel::Critical);
+    }
+}
@@ -0,0 +1,373 @@
+//! Parsing of dashcam telemetry sidecar files.
+//!
+//! NMEA (`$--RMC` sentences), GPX track points and CSV logs are converted to
+//! a `Telemetry` series on the video clock: the first sample of the log is
+//! taken to coincide with `offset` seconds into the video.
+
+use crate::types::{FrameAnalysis, Telemetry, TelemetryFormat, TelemetrySample};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+pub(crate) const KNOTS_TO_KMH: f32 = 1.852;
+pub(crate) const MPS_TO_KMH: f32 = 3.6;
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+/// Speeds are not interpolated across gaps in the log longer than this, in seconds.
+const MAX_INTERPOLATION_GAP: f64 = 5.0;
+
+/// Formats an uploaded sidecar file can be in; the others are embedded in
+/// the video and found by `video::mp4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SidecarFormat {
+    Nmea,
+    Gpx,
+    Csv,
+}
+
+impl SidecarFormat {
+    /// Picks the format from the file extension, falling back to the contents.
+    fn detect(filename: &str, contents: &str) -> Self {
+        let extension = filename
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("nmea") => SidecarFormat::Nmea,
+            Some("gpx") => SidecarFormat::Gpx,
+            Some("csv") => SidecarFormat::Csv,
+            _ if contents.trim_start().starts_with('$') => SidecarFormat::Nmea,
+            _ if contents.contains("<gpx") => SidecarFormat::Gpx,
+            _ => SidecarFormat::Csv,
+        }
+    }
+}
+
+impl From<SidecarFormat> for TelemetryFormat {
+    fn from(format: SidecarFormat) -> Self {
+        match format {
+            SidecarFormat::Nmea => TelemetryFormat::Nmea,
+            SidecarFormat::Gpx => TelemetryFormat::Gpx,
+            SidecarFormat::Csv => TelemetryFormat::Csv,
+        }
+    }
+}
+
+/// Parses a sidecar file and aligns it to the video clock.
+pub fn parse(filename: &str, contents: &str, offset: f64) -> Result<Telemetry> {
+    let format = SidecarFormat::detect(filename, contents);
+    let mut samples = match format {
+        SidecarFormat::Nmea => parse_nmea(contents),
+        SidecarFormat::Gpx => parse_gpx(contents)?,
+        SidecarFormat::Csv => parse_csv(contents)?,
+    };
+    if samples.is_empty() {
+        anyhow::bail!("No telemetry samples found in {}", filename);
+    }
+
+    rebase(&mut samples, offset);
+    Ok(Telemetry::new(format.into(), samples))
+}
+
+/// Shifts log timestamps so the first sample falls `offset` seconds into the video.
+pub(crate) fn rebase(samples: &mut [TelemetrySample], offset: f64) {
+    let start = samples
+        .iter()
+        .map(|s| s.timestamp)
+        .fold(f64::INFINITY, f64::min);
+    for sample in samples {
+        sample.timestamp = sample.timestamp - start + offset;
+    }
+}
+
+impl Telemetry {
+    /// Orders samples already on the video clock and fills in missing speeds.
+    pub(crate) fn new(format: TelemetryFormat, mut samples: Vec<TelemetrySample>) -> Self {
+        samples.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        fill_speed_from_positions(&mut samples);
+        Self { format, samples }
+    }
+
+    /// Speed at `timestamp`, linearly interpolated between neighbouring samples.
+    pub fn speed_at(&self, timestamp: f64) -> Option<f32> {
+        let speeds: Vec<(f64, f32)> = self
+            .samples
+            .iter()
+            .filter_map(|s| Some((s.timestamp, s.speed?)))
+            .collect();
+        let after = speeds.partition_point(|(t, _)| *t < timestamp);
+
+        match (
+            after.checked_sub(1).map(|idx| speeds[idx]),
+            speeds.get(after),
+        ) {
+            (_, Some(&(t, speed))) if t == timestamp => Some(speed),
+            (Some((t0, v0)), Some(&(t1, v1))) if t1 - t0 <= MAX_INTERPOLATION_GAP => {
+                let fraction = ((timestamp - t0) / (t1 - t0)) as f32;
+                Some(v0 + (v1 - v0) * fraction)
+            }
+            _ => None,
+        }
+    }
+
+    /// Replaces the vision model's speed estimate with the measured one where available.
+    pub fn annotate(&self, frames: &mut [FrameAnalysis]) {
+        for frame in frames {
+            if let Some(speed) = self.speed_at(frame.timestamp) {
+                frame.road_sign_awareness.speed_limit.current_speed = Some(speed);
+            }
+        }
+    }
+}
+
+fn parse_nmea(contents: &str) -> Vec<TelemetrySample> {
+    contents
+        .lines()
+        .filter_map(|line| parse_rmc(line.trim()))
+        .collect()
+}
+
+/// `$GPRMC,hhmmss.ss,A,ddmm.mmmm,N,dddmm.mmmm,E,knots,course,ddmmyy,...*CS`
+pub(crate) fn parse_rmc(sentence: &str) -> Option<TelemetrySample> {
+    let body = sentence.strip_prefix('$')?;
+    let body = match body.split_once('*') {
+        Some((body, checksum)) => {
+            let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+            (body.bytes().fold(0, |acc, b| acc ^ b) == expected).then_some(body)?
+        }
+        None => body,
+    };
+
+    let fields: Vec<&str> = body.split(',').collect();
+    if fields.len() < 10 || !fields[0].ends_with("RMC") || fields[2] != "A" {
+        return None;
+    }
+
+    let time = fields[1];
+    let seconds_of_day = time.get(0..2)?.parse::<f64>().ok()? * 3600.0
+        + time.get(2..4)?.parse::<f64>().ok()? * 60.0
+        + time.get(4..)?.parse::<f64>().ok()?;
+    let date = fields[9];
+    let date = NaiveDate::from_ymd_opt(
+        2000 + date.get(4..6)?.parse::<i32>().ok()?,
+        date.get(2..4)?.parse().ok()?,
+        date.get(0..2)?.parse().ok()?,
+    )?;
+
+    Some(TelemetrySample {
+        timestamp: date.num_days_from_ce() as f64 * 86_400.0 + seconds_of_day,
+        latitude: nmea_coordinate(fields[3], fields[4]),
+        longitude: nmea_coordinate(fields[5], fields[6]),
+        speed: fields[7]
+            .parse::<f32>()
+            .ok()
+            .map(|knots| knots * KNOTS_TO_KMH),
+        acceleration: None,
+    })
+}
+
+/// Converts `dddmm.mmmm` plus hemisphere to signed decimal degrees.
+fn nmea_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    nmea_degrees(value.parse().ok()?, hemisphere.chars().next()?)
+}
+
+pub(crate) fn nmea_degrees(value: f64, hemisphere: char) -> Option<f64> {
+    let degrees = (value / 100.0).trunc();
+    let decimal = degrees + (value - degrees * 100.0) / 60.0;
+    match hemisphere {
+        'N' | 'E' => Some(decimal),
+        'S' | 'W' => Some(-decimal),
+        _ => None,
+    }
+}
+
+/// Reads `<trkpt lat lon>` points with their `<time>` and, when present, a
+/// `<speed>` in m/s (GPX 1.0 or a track point extension).
+fn parse_gpx(contents: &str) -> Result<Vec<TelemetrySample>> {
+    let mut reader = Reader::from_str(contents);
+    reader.trim_text(true);
+
+    let mut samples = Vec::new();
+    let mut point: Option<TelemetrySample> = None;
+    let mut has_time = false;
+    let mut element = Vec::new();
+
+    loop {
+        match reader.read_event().context("Invalid GPX")? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"trkpt" => {
+                let mut sample = TelemetrySample::default();
+                for attribute in e.attributes() {
+                    let attribute = attribute.context("Invalid GPX attribute")?;
+                    let value: Option<f64> = attribute.unescape_value()?.parse().ok();
+                    match attribute.key.local_name().as_ref() {
+                        b"lat" => sample.latitude = value,
+                        b"lon" => sample.longitude = value,
+                        _ => {}
+                    }
+                }
+                point = Some(sample);
+                has_time = false;
+            }
+            Event::Start(e) => element = e.local_name().as_ref().to_vec(),
+            Event::Text(text) => {
+                let Some(sample) = point.as_mut() else {
+                    continue;
+                };
+                let text = text.unescape()?;
+                match element.as_slice() {
+                    b"time" => {
+                        let time = DateTime::parse_from_rfc3339(text.trim())
+                            .with_context(|| format!("Invalid GPX time {}", text))?;
+                        sample.timestamp = time.timestamp_millis() as f64 / 1000.0;
+                        has_time = true;
+                    }
+                    b"speed" => {
+                        sample.speed = text.trim().parse::<f32>().ok().map(|v| v * MPS_TO_KMH)
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"trkpt" => {
+                if let Some(sample) = point.take() {
+                    if has_time {
+                        samples.push(sample);
+                    }
+                }
+            }
+            Event::End(_) => element.clear(),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Reads a CSV log with a header row. Recognised columns (case-insensitive):
+/// `time`/`timestamp` in seconds, `speed`/`speed_kmh`, `speed_mps`,
+/// `lat`/`latitude`, `lon`/`lng`/`longitude`, and `accel_x`/`ax` in m/s².
+fn parse_csv(contents: &str) -> Result<Vec<TelemetrySample>> {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<String> = lines
+        .next()
+        .context("Empty telemetry CSV")?
+        .split(',')
+        .map(|column| column.trim().to_ascii_lowercase())
+        .collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+
+    let time = column(&["time", "timestamp", "t"])
+        .context("Telemetry CSV needs a time or timestamp column")?;
+    let speed_kmh = column(&["speed", "speed_kmh"]);
+    let speed_mps = column(&["speed_mps"]);
+    let latitude = column(&["lat", "latitude"]);
+    let longitude = column(&["lon", "lng", "longitude"]);
+    let acceleration = column(&["accel_x", "ax"]);
+
+    let mut samples = Vec::new();
+    for (idx, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let value = |column: Option<usize>| -> Option<f64> {
+            fields.get(column?).and_then(|v| v.parse().ok())
+        };
+        let timestamp = value(Some(time))
+            .with_context(|| format!("Telemetry CSV row {}: invalid time", idx + 2))?;
+
+        samples.push(TelemetrySample {
+            timestamp,
+            latitude: value(latitude),
+            longitude: value(longitude),
+            speed: value(speed_kmh)
+                .map(|v| v as f32)
+                .or_else(|| value(speed_mps).map(|v| v as f32 * MPS_TO_KMH)),
+            acceleration: value(acceleration).map(|v| v as f32),
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Derives speed from consecutive positions for samples that lack one.
+fn fill_speed_from_positions(samples: &mut [TelemetrySample]) {
+    for idx in 1..samples.len() {
+        let (before, after) = (samples[idx - 1], samples[idx]);
+        if after.speed.is_some() {
+            continue;
+        }
+        let (Some(lat0), Some(lon0), Some(lat1), Some(lon1)) = (
+            before.latitude,
+            before.longitude,
+            after.latitude,
+            after.longitude,
+        ) else {
+            continue;
+        };
+        let dt = after.timestamp - before.timestamp;
+        if dt > 0.0 {
+            let meters = haversine(lat0, lon0, lat1, lon1);
+            samples[idx].speed = Some((meters / dt) as f32 * MPS_TO_KMH);
+        }
+    }
+}
+
+fn haversine(lat0: f64, lon0: f64, lat1: f64, lon1: f64) -> f64 {
+    let (phi0, phi1) = (lat0.to_radians(), lat1.to_radians());
+    let d_phi = phi1 - phi0;
+    let d_lambda = (lon1 - lon0).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi0.cos() * phi1.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::frame;
+
+    #[test]
+    fn test_nmea_rmc() {
+        let log = "\
+$GPRMC,123519.00,A,4807.038,N,01131.000,E,10.0,084.4,230394,003.1,W*71
+$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47
+$GPRMC,123520.00,A,4807.038,N,01131.000,E,20.0,084.4,230394,003.1,W*78
+$GPRMC,123521.00,A,4807.038,N,01131.000,E,20.0,084.4,230394,003.1,W*00
+";
+        let telemetry = parse("drive.nmea", log, 0.0).unwrap();
+        assert_eq!(telemetry.format, TelemetryFormat::Nmea);
+        assert_eq!(
+            telemetry.samples.len(),
+            2,
+            "bad checksum and GGA are skipped"
+        );
+        assert_eq!(telemetry.samples[1].timestamp, 1.0);
+        assert!((telemetry.samples[0].latitude.unwrap() - 48.1173).abs() < 1e-4);
+        assert!((telemetry.samples[1].speed.unwrap() - 37.04).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_gpx_speed_from_positions() {
+        let gpx = r#"<?xml version="1.0"?>
+<gpx version="1.1"><trk><trkseg>
+  <trkpt lat="51.5000" lon="-0.1000"><time>2024-03-01T09:00:00Z</time></trkpt>
+  <trkpt lat="51.5001" lon="-0.1000"><time>2024-03-01T09:00:01Z</time></trkpt>
+</trkseg></trk></gpx>"#;
+        let telemetry = parse("drive.gpx", gpx, 2.0).unwrap();
+        assert_eq!(telemetry.format, TelemetryFormat::Gpx);
+        assert_eq!(telemetry.samples[0].timestamp, 2.0);
+        assert_eq!(telemetry.samples[1].timestamp, 3.0);
+        // 0.0001 degrees of latitude is about 11.1 m
+        assert!((telemetry.samples[1].speed.unwrap() - 40.03).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_csv_and_alignment() {
+        let csv = "timestamp,speed_mps,accel_x\n10.0,10,0.1\n11.0,12,2.0\n12.0,8,-4.0\n";
+        let telemetry = parse("imu.csv", csv, 0.0).unwrap();
+        assert_eq!(telemetry.samples[2].acceleration, Some(-4.0));
+        assert!((telemetry.speed_at(0.5).unwrap() - 39.6).abs() < 1e-3);
+        assert_eq!(telemetry.speed_at(13.0), None);
+
+        let mut frames = vec![frame(0, 0.0), frame(30, 1.0)];
+        telemetry.annotate(&mut frames);
+        let speed = frames[1].road_sign_awareness.speed_limit.current_speed;
+        assert!((speed.unwrap() - 43.2).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::dataset::{self, Sample};
+use crate::lstm::{FeatureScaler, LSTMModel};
+use crate::smoothing::Smoother;
+use crate::types::FrameAnalysis;
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+use tch::nn::OptimizerConfig;
+use tch::{nn, Device, Reduction, Tensor};
+use tracing::info;
+
+#[derive(Debug, Args)]
+pub struct TrainArgs {
+    /// Directory of `<video>/frame_*.json` annotations from prepare.py
+    #[arg(long, default_value = "../../data/annotations")]
+    pub annotations: PathBuf,
+    /// CSV of `video,label` rows with drive labels on the 0–5 scale
+    #[arg(long)]
+    pub labels: PathBuf,
+    /// Checkpoint path; the scaler is written next to it as `.scaler.json`
+    #[arg(long, default_value = "../../models/vglnt_lstm.ot")]
+    pub output: PathBuf,
+    #[arg(long, default_value_t = 100)]
+    pub epochs: usize,
+    #[arg(long, default_value_t = 32)]
+    pub batch_size: usize,
+    #[arg(long, default_value_t = 0.0005)]
+    pub learning_rate: f64,
+    /// Epochs without validation improvement before stopping
+    #[arg(long, default_value_t = 10)]
+    pub patience: usize,
+    /// Fraction of videos held out for validation
+    #[arg(long, default_value_t = 0.15)]
+    pub val_split: f32,
+    #[arg(long, default_value_t = 200)]
+    pub max_frames: usize,
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+}
+
+pub fn run(args: &TrainArgs) -> Result<()> {
+    let samples = dataset::load_samples(
+        &args.annotations,
+        &args.labels,
+        args.max_frames,
+        &Smoother::from_env(),
+    )?;
+    if samples.len() < 2 {
+        anyhow::bail!("Need at least two labelled videos, found {}", samples.len());
+    }
+    let (train, val) = dataset::split(samples, args.val_split, args.seed);
+    info!(
+        "Training on {} videos, validating on {}",
+        train.len(),
+        val.len()
+    );
+
+    let mut model = LSTMModel::with_device(Device::Cpu)?;
+    // Only the drive score is fitted, so the scaler leaves `heads_trained` unset
+    model.set_scaler(FeatureScaler::fit(
+        train.iter().flat_map(|sample| sample.frames.iter()),
+    ));
+    let mut optimizer = nn::Adam::default().build(model.var_store(), args.learning_rate)?;
+
+    let mut order: Vec<usize> = (0..train.len()).collect();
+    let mut seed = args.seed.max(1);
+    let mut best_loss = f64::INFINITY;
+    let mut epochs_since_best = 0;
+
+    for epoch in 1..=args.epochs {
+        dataset::shuffle(&mut order, &mut seed);
+        let mut train_loss = 0.0;
+        for batch in order.chunks(args.batch_size.max(1)) {
+            let batch: Vec<&Sample> = batch.iter().map(|idx| &train[*idx]).collect();
+            let loss = batch_loss(&model, &batch, true)?;
+            optimizer.backward_step(&loss);
+            train_loss += loss.double_value(&[]) * batch.len() as f64;
+        }
+        train_loss /= train.len() as f64;
+
+        // Without a validation split, early stopping watches the training loss
+        let val_loss = if val.is_empty() {
+            train_loss
+        } else {
+            tch::no_grad(|| mean_loss(&model, &val, args.batch_size))?
+        };
+        info!(
+            "Epoch {}: train loss {:.4}, val loss {:.4}",
+            epoch, train_loss, val_loss
+        );
+
+        if val_loss < best_loss {
+            best_loss = val_loss;
+            epochs_since_best = 0;
+            model.save_weights(&args.output)?;
+        } else {
+            epochs_since_best += 1;
+            if epochs_since_best >= args.patience {
+                info!("No improvement for {} epochs, stopping", args.patience);
+                break;
+            }
+        }
+    }
+
+    info!(
+        "Best val loss {:.4}, checkpoint written to {}",
+        best_loss,
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn batch_loss(model: &LSTMModel, batch: &[&Sample], train: bool) -> Result<Tensor> {
+    let sequences: Vec<&[FrameAnalysis]> = batch.iter().map(|s| s.frames.as_slice()).collect();
+    let labels: Vec<f32> = batch.iter().map(|s| s.label).collect();
+
+    let predictions = model.predict_batch(&sequences, train)?;
+    let targets = Tensor::of_slice(&labels).to_device(predictions.device());
+    Ok(predictions.mse_loss(&targets, Reduction::Mean))
+}
+
+fn mean_loss(model: &LSTMModel, samples: &[Sample], batch_size: usize) -> Result<f64> {
+    let mut total = 0.0;
+    for batch in samples.chunks(batch_size.max(1)) {
+        let batch: Vec<&Sample> = batch.iter().collect();
+        total += batch_loss(model, &batch, false)?.double_value(&[]) * batch.len() as f64;
+    }
+    Ok(total / samples.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::fixtures::write_video;
+    use crate::lstm::SequenceBackend;
+    use crate::types::fixtures::timeline;
+
+    fn args(dir: &std::path::Path) -> TrainArgs {
+        TrainArgs {
+            annotations: dir.join("annotations"),
+            labels: dir.join("labels.csv"),
+            output: dir.join("model.ot"),
+            epochs: 2,
+            batch_size: 32,
+            learning_rate: 0.0005,
+            patience: 10,
+            val_split: 0.5,
+            max_frames: 200,
+            seed: 42,
+        }
+    }
+
+    #[test]
+    fn test_mean_loss_weights_every_sample_equally() {
+        let model = LSTMModel::with_device(Device::Cpu).unwrap();
+        let samples: Vec<Sample> = (0..3)
+            .map(|idx| Sample {
+                video: format!("video_{}", idx),
+                frames: timeline(4),
+                label: idx as f32,
+            })
+            .collect();
+
+        let whole = tch::no_grad(|| mean_loss(&model, &samples, 3)).unwrap();
+        let chunked = tch::no_grad(|| mean_loss(&model, &samples, 2)).unwrap();
+        assert!((whole - chunked).abs() < 1e-5, "{} != {}", whole, chunked);
+    }
+
+    #[test]
+    fn test_run_writes_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = args(dir.path());
+        write_video(&args.annotations, "calm", &[0, 1, 2]);
+        std::fs::write(&args.labels, "video,label\ncalm,5\n").unwrap();
+        assert!(run(&args).is_err());
+
+        write_video(&args.annotations, "rushed", &[0, 1]);
+        std::fs::write(&args.labels, "video,label\ncalm,5\nrushed,1\n").unwrap();
+        run(&args).unwrap();
+        assert!(args.output.with_extension("scaler.json").exists());
+
+        // Only the drive score was trained, so the heads must not be reported
+        let mut model = LSTMModel::new().unwrap();
+        model.load_weights(args.output.to_str().unwrap()).unwrap();
+        assert!(model.weights_loaded());
+        assert!(!model.heads_loaded());
+    }
+}
@@ -17,6 +17,9 @@ pub enum AnalysisStatus {
     },
     Failed {
         error: String,
+        /// The `AppError` code of the failure, e.g. `model_unavailable`.
+        #[serde(default)]
+        code: Option<String>,
         timestamp: SystemTime,
     },
 }
@@ -34,7 +37,13 @@ pub struct AnalysisMetadata {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DrivingAnalysis {
     pub metadata: AnalysisMetadata,
+    /// The smoothed timeline every downstream stage consumes.
     pub frame_analyses: Vec<FrameAnalysis>,
+    /// The timeline exactly as returned by the vision model.
+    #[serde(default)]
+    pub raw_frame_analyses: Vec<FrameAnalysis>,
+    #[serde(default)]
+    pub telemetry: Option<Telemetry>,
     pub lstm_output: LSTMOutput,
     pub summary: AnalysisSummary,
 }
@@ -80,6 +89,10 @@ pub struct SignalCompliance {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MergingLaneChange {
+    /// A merge or lane change is under way; `safe_merging` only means
+    /// anything while it is.
+    #[serde(default)]
+    pub in_progress: bool,
     pub safe_merging: bool,
     pub signal_used: bool,
     pub blind_spot_check: bool,
@@ -128,13 +141,17 @@ pub struct LSTMOutput {
     pub overall_safety_score: f32,
     pub risk_factors: Vec<RiskFactor>,
     pub temporal_patterns: Vec<TemporalPattern>,
-    pub behavioral_metrics: BehavioralMetrics,
+    /// Absent when the model has no trained per-timestep heads.
+    pub behavioral_metrics: Option<BehavioralMetrics>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisSummary {
     pub overall_score: f32,
     pub risk_level: RiskLevel,
+    /// The inputs that set or raised `risk_level`, in the order applied.
+    #[serde(default)]
+    pub risk_drivers: Vec<RiskDriver>,
     pub critical_events: Vec<CriticalEvent>,
     pub improvement_areas: Vec<ImprovementArea>,
     pub stats: DrivingStats,
@@ -142,7 +159,7 @@ pub struct AnalysisSummary {
 
 // Supporting Types
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SafetyStatus {
     Safe,
     Marginal,
@@ -155,6 +172,9 @@ pub struct TrafficLightStatus {
     pub status: SignalColor,
     pub compliance: bool,
     pub distance: f32,
+    /// The vision model's sub-score, fed to the sequence model as is.
+    #[serde(default)]
+    pub score: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -162,6 +182,8 @@ pub struct StopSignStatus {
     pub present: bool,
     pub compliance: Option<bool>,
     pub stop_duration: Option<f32>,
+    #[serde(default)]
+    pub score: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -170,12 +192,16 @@ pub struct SpeedLimitStatus {
     pub limit: Option<u32>,
     pub current_speed: Option<f32>,
     pub compliance: Option<bool>,
+    #[serde(default)]
+    pub score: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct YieldSignStatus {
     pub visible: bool,
     pub compliance: Option<bool>,
+    #[serde(default)]
+    pub score: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -192,6 +218,10 @@ pub struct RiskFactor {
     pub severity: f32,
     pub frequency: f32,
     pub temporal_correlation: f32,
+    #[serde(default)]
+    pub sources: Vec<RiskSource>,
+    #[serde(default)]
+    pub evidence_frames: Vec<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -213,11 +243,25 @@ pub struct BehavioralMetrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CriticalEvent {
     pub event_type: String,
+    /// Start of the event, in seconds.
     pub timestamp: f64,
+    #[serde(default)]
+    pub end_timestamp: f64,
+    /// Peak severity over the frames involved.
     pub severity: f32,
+    #[serde(default)]
+    pub frames: Vec<u32>,
     pub context: HashMap<String, String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskDriver {
+    pub input: String,
+    pub detail: String,
+    /// The level this input requires on its own.
+    pub level: RiskLevel,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImprovementArea {
     pub area: String,
@@ -229,11 +273,14 @@ pub struct ImprovementArea {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DrivingStats {
     pub total_duration: f64,
-    pub distance_covered: f32,
-    pub average_speed: f32,
-    pub max_speed: f32,
-    pub harsh_braking_count: u32,
-    pub rapid_acceleration_count: u32,
+    /// Kilometres; this and the other kinematic stats are `None` without speed data.
+    pub distance_covered: Option<f32>,
+    /// km/h
+    pub average_speed: Option<f32>,
+    /// km/h
+    pub max_speed: Option<f32>,
+    pub harsh_braking_count: Option<u32>,
+    pub rapid_acceleration_count: Option<u32>,
     pub traffic_light_encounters: u32,
     pub stop_sign_encounters: u32,
     pub lane_changes: u32,
@@ -241,15 +288,16 @@ pub struct DrivingStats {
 
 // Enums
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SignalColor {
     Red,
     Yellow,
     Green,
+    /// No traffic light in view, or its colour could not be made out.
     Unknown,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -257,7 +305,7 @@ pub enum RiskLevel {
     Critical,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum RiskFactorType {
     FollowingDistance,
     SpeedControl,
@@ -269,6 +317,250 @@ pub enum RiskFactorType {
     Other(String),
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RiskSource {
+    Model,
+    Rule(String),
+}
+
+// Vision Model Types
+
+/// One frame as returned by the vision model, in the schema of the LLM
+/// prompt and of the `frame_*.json` annotations written by `prepare.py`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameData {
+    pub lane_centering: FrameLaneCentering,
+    pub following_distance: FrameFollowingDistance,
+    pub signal_compliance: FrameSignalCompliance,
+    pub merging_lane_change: FrameMergingLaneChange,
+    pub pedestrian_yielding: FramePedestrianYielding,
+    pub intersection_behavior: FrameIntersectionBehavior,
+    pub road_sign_awareness: FrameRoadSignAwareness,
+    pub shoulder_use: FrameShoulderUse,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameLaneCentering {
+    pub following_lane_discipline: bool,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameFollowingDistance {
+    pub safe_distance: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameSignalCompliance {
+    pub traffic_light: FrameTrafficLight,
+    pub stop_sign: FrameStopSign,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameTrafficLight {
+    pub status: String,
+    pub compliance: bool,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameStopSign {
+    pub present: bool,
+    /// `true`/`false`, or the string `"N/A"` when no stop sign applies.
+    pub compliance: serde_json::Value,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameMergingLaneChange {
+    /// Missing from annotations made before the schema had it.
+    #[serde(default)]
+    pub in_progress: bool,
+    pub safe_merging: bool,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FramePedestrianYielding {
+    pub pedestrian_present: bool,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameIntersectionBehavior {
+    pub stop_line_observance: bool,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameRoadSignAwareness {
+    pub speed_limit_sign: FrameSpeedLimitSign,
+    pub yield_sign: FrameYieldSign,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameSpeedLimitSign {
+    pub visible: bool,
+    pub observing_limit: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameYieldSign {
+    pub visible: bool,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameShoulderUse {
+    pub using_shoulder: bool,
+    pub score: f32,
+}
+
+impl FrameData {
+    /// Converts the vision model output into the timeline representation.
+    ///
+    /// Measurements the vision model does not report (distances, speeds,
+    /// stop durations) are left at zero or `None`, and confidences at 1.0.
+    /// Sub-scores are kept and also summed into their category score.
+    pub fn into_analysis(self, frame_number: u32, timestamp: f64) -> FrameAnalysis {
+        let signals = self.signal_compliance;
+        let signs = self.road_sign_awareness;
+
+        FrameAnalysis {
+            frame_number,
+            timestamp,
+            lane_centering: LaneCentering {
+                following_lane_discipline: self.lane_centering.following_lane_discipline,
+                deviation_from_center: 0.0,
+                score: self.lane_centering.score,
+                confidence: 1.0,
+            },
+            following_distance: FollowingDistance {
+                safe_distance: match self.following_distance.safe_distance.as_str() {
+                    "safe" => SafetyStatus::Safe,
+                    "approximate" => SafetyStatus::Marginal,
+                    "unsafe" => SafetyStatus::Unsafe,
+                    _ => SafetyStatus::Unknown,
+                },
+                distance_meters: 0.0,
+                time_to_collision: 0.0,
+                score: self.following_distance.score,
+                confidence: 1.0,
+            },
+            signal_compliance: SignalCompliance {
+                traffic_light: TrafficLightStatus {
+                    status: match signals.traffic_light.status.as_str() {
+                        "red" => SignalColor::Red,
+                        "yellow" => SignalColor::Yellow,
+                        "green" => SignalColor::Green,
+                        _ => SignalColor::Unknown,
+                    },
+                    compliance: signals.traffic_light.compliance,
+                    distance: 0.0,
+                    score: signals.traffic_light.score,
+                },
+                stop_sign: StopSignStatus {
+                    present: signals.stop_sign.present,
+                    compliance: signals.stop_sign.compliance.as_bool(),
+                    stop_duration: None,
+                    score: signals.stop_sign.score,
+                },
+                score: signals.traffic_light.score + signals.stop_sign.score,
+                confidence: 1.0,
+            },
+            merging_lane_change: MergingLaneChange {
+                in_progress: self.merging_lane_change.in_progress,
+                safe_merging: self.merging_lane_change.safe_merging,
+                signal_used: false,
+                blind_spot_check: false,
+                speed_adjustment: 0.0,
+                score: self.merging_lane_change.score,
+                confidence: 1.0,
+            },
+            pedestrian_yielding: PedestrianYielding {
+                pedestrian_present: self.pedestrian_yielding.pedestrian_present,
+                proper_yielding: self.pedestrian_yielding.score > 0.0,
+                distance_to_pedestrian: None,
+                score: self.pedestrian_yielding.score,
+                confidence: 1.0,
+            },
+            intersection_behavior: IntersectionBehavior {
+                stop_line_observance: self.intersection_behavior.stop_line_observance,
+                complete_stop: false,
+                right_of_way_compliance: self.intersection_behavior.stop_line_observance,
+                score: self.intersection_behavior.score,
+                confidence: 1.0,
+            },
+            road_sign_awareness: RoadSignAwareness {
+                speed_limit: SpeedLimitStatus {
+                    visible: signs.speed_limit_sign.visible,
+                    limit: None,
+                    current_speed: None,
+                    compliance: match signs.speed_limit_sign.observing_limit.as_str() {
+                        "observing" | "yes" => Some(true),
+                        "exceeding" | "no" => Some(false),
+                        _ => None,
+                    },
+                    score: signs.speed_limit_sign.score,
+                },
+                yield_sign: YieldSignStatus {
+                    visible: signs.yield_sign.visible,
+                    compliance: None,
+                    score: signs.yield_sign.score,
+                },
+                other_signs: Vec::new(),
+                score: signs.speed_limit_sign.score + signs.yield_sign.score,
+                confidence: 1.0,
+            },
+            shoulder_use: ShoulderUse {
+                using_shoulder: self.shoulder_use.using_shoulder,
+                emergency_situation: false,
+                score: self.shoulder_use.score,
+                confidence: 1.0,
+            },
+        }
+    }
+}
+
+// Telemetry Types
+
+/// A GPS/IMU series aligned to the video clock.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Telemetry {
+    pub format: TelemetryFormat,
+    pub samples: Vec<TelemetrySample>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TelemetrySample {
+    /// Seconds from the start of the video.
+    pub timestamp: f64,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// km/h
+    pub speed: Option<f32>,
+    /// Longitudinal acceleration in m/s², negative when braking.
+    pub acceleration: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TelemetryFormat {
+    #[default]
+    Nmea,
+    Gpx,
+    Csv,
+    /// `gps ` boxes written by Novatek-based dashcams.
+    Novatek,
+    /// GoPro GPMF metadata track.
+    Gpmf,
+    /// NMEA sentences carried in an MP4 subtitle track.
+    Subtitle,
+}
+
 // API Response Types
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -296,8 +588,93 @@ pub struct AnalysisListResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AnalysisSummaryItem {
     pub id: Uuid,
+    pub tenant: String,
     pub filename: String,
-    pub status: AnalysisStatus,
+    /// One of `queued`, `processing`, `complete` or `failed`.
+    pub status: String,
     pub upload_time: SystemTime,
+    /// Set once the analysis is complete.
     pub overall_score: Option<f32>,
 }
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+
+    /// A compliant frame with every category at full marks.
+    pub fn frame(frame_number: u32, timestamp: f64) -> FrameAnalysis {
+        frame_data().into_analysis(frame_number, timestamp)
+    }
+
+    /// The model's answer for a compliant frame, as in a `frame_*.json` annotation.
+    pub fn frame_data() -> FrameData {
+        serde_json::from_value(serde_json::json!({
+            "lane_centering": {"following_lane_discipline": true, "score": 20},
+            "following_distance": {"safe_distance": "safe", "score": 15},
+            "signal_compliance": {
+                "traffic_light": {"status": "green", "compliance": true, "score": 15},
+                "stop_sign": {"present": false, "compliance": "N/A", "score": 5}
+            },
+            "merging_lane_change": {"safe_merging": true, "score": 10},
+            "pedestrian_yielding": {"pedestrian_present": false, "score": 10},
+            "intersection_behavior": {"stop_line_observance": true, "score": 10},
+            "road_sign_awareness": {
+                "speed_limit_sign": {"visible": false, "observing_limit": "unknown", "score": 15},
+                "yield_sign": {"visible": false, "score": 5}
+            },
+            "shoulder_use": {"using_shoulder": false, "score": 5}
+        }))
+        .unwrap()
+    }
+
+    /// `len` compliant frames, one a second at 30 fps.
+    pub fn timeline(len: u32) -> Vec<FrameAnalysis> {
+        (0..len).map(|idx| frame(idx * 30, idx as f64)).collect()
+    }
+
+    /// A completed analysis of a short compliant drive.
+    pub fn analysis(id: Uuid, overall_score: f32) -> DrivingAnalysis {
+        DrivingAnalysis {
+            metadata: AnalysisMetadata {
+                id,
+                filename: "drive.mp4".to_string(),
+                upload_time: SystemTime::UNIX_EPOCH,
+                video_duration: 2.0,
+                frame_count: 60,
+                fps: 30.0,
+            },
+            frame_analyses: vec![frame(0, 0.0), frame(30, 1.0)],
+            raw_frame_analyses: Vec::new(),
+            telemetry: None,
+            lstm_output: LSTMOutput {
+                overall_safety_score: overall_score,
+                risk_factors: Vec::new(),
+                temporal_patterns: Vec::new(),
+                behavioral_metrics: Some(BehavioralMetrics {
+                    aggression_index: 0.0,
+                    attention_score: 1.0,
+                    consistency_rating: 1.0,
+                    anticipation_level: 1.0,
+                }),
+            },
+            summary: AnalysisSummary {
+                overall_score,
+                risk_level: RiskLevel::Low,
+                risk_drivers: Vec::new(),
+                critical_events: Vec::new(),
+                improvement_areas: Vec::new(),
+                stats: DrivingStats {
+                    total_duration: 2.0,
+                    distance_covered: None,
+                    average_speed: None,
+                    max_speed: None,
+                    harsh_braking_count: None,
+                    rapid_acceleration_count: None,
+                    traffic_light_encounters: 1,
+                    stop_sign_encounters: 0,
+                    lane_changes: 0,
+                },
+            },
+        }
+    }
+}
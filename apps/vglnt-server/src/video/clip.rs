@@ -0,0 +1,296 @@
+//! Evidence clips around critical events and single-frame thumbnails, cut
+//! from the retained source video and cached on disk.
+
+use super::store::remove_cached;
+use super::{open_video, open_writer};
+use crate::types::CriticalEvent;
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use opencv::core::{Mat, Vector};
+use opencv::imgcodecs;
+use opencv::prelude::*;
+use opencv::videoio;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use uuid::Uuid;
+
+const DEFAULT_PRE_ROLL: f64 = 5.0;
+const DEFAULT_POST_ROLL: f64 = 5.0;
+/// Longest pre- or post-roll a request may ask for, in seconds.
+pub const MAX_ROLL: f64 = 60.0;
+
+/// Seconds of footage kept before the event starts and after it ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipOptions {
+    pub pre_roll: f64,
+    pub post_roll: f64,
+}
+
+impl ClipOptions {
+    /// Rolls rounded to the tenth of a second clips are cached by.
+    pub fn rounded(self) -> Self {
+        let round = |seconds: f64| (seconds * 10.0).round() / 10.0;
+        Self {
+            pre_roll: round(self.pre_roll),
+            post_roll: round(self.post_roll),
+        }
+    }
+}
+
+impl Default for ClipOptions {
+    fn default() -> Self {
+        Self {
+            pre_roll: DEFAULT_PRE_ROLL,
+            post_roll: DEFAULT_POST_ROLL,
+        }
+    }
+}
+
+pub struct Clipper {
+    dir: PathBuf,
+    defaults: ClipOptions,
+    /// A lock per output being written, so concurrent requests for the same
+    /// clip or thumbnail wait for one another instead of racing.
+    in_flight: DashMap<PathBuf, Arc<Mutex<()>>>,
+}
+
+impl Clipper {
+    pub fn new(dir: PathBuf, defaults: ClipOptions) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create clip directory {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            defaults,
+            in_flight: DashMap::new(),
+        })
+    }
+
+    /// Reads `VGLNT_CLIP_DIR`, `VGLNT_CLIP_PRE_ROLL` and `VGLNT_CLIP_POST_ROLL`.
+    pub fn from_env() -> Result<Self> {
+        let dir = std::env::var("VGLNT_CLIP_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("vglnt").join("clips"));
+        let seconds = |name: &str, default: f64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        let defaults = ClipOptions {
+            pre_roll: seconds("VGLNT_CLIP_PRE_ROLL", DEFAULT_PRE_ROLL),
+            post_roll: seconds("VGLNT_CLIP_POST_ROLL", DEFAULT_POST_ROLL),
+        };
+        Self::new(dir, defaults)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn defaults(&self) -> ClipOptions {
+        self.defaults
+    }
+
+    /// Deletes every cached clip and thumbnail of an analysis.
+    pub fn remove(&self, analysis_id: Uuid) -> std::io::Result<()> {
+        remove_cached(&self.dir, analysis_id)
+    }
+
+    /// Cuts the footage around `event` into an MP4, with the rolls rounded
+    /// to a tenth of a second.
+    ///
+    /// Blocking; run it off the async runtime.
+    pub fn clip(
+        &self,
+        source: &Path,
+        analysis_id: Uuid,
+        event_idx: usize,
+        event: &CriticalEvent,
+        options: ClipOptions,
+    ) -> Result<PathBuf> {
+        let options = options.rounded();
+        let output = self.dir.join(format!(
+            "{}-event{}-{:.1}-{:.1}.mp4",
+            analysis_id, event_idx, options.pre_roll, options.post_roll
+        ));
+        self.cached(output, "partial.mp4", |partial| {
+            let source_str = source.to_str().context("Invalid source video path")?;
+            let (mut capture, fps) = open_video(source_str)?;
+            let frame_count = capture.get(videoio::CAP_PROP_FRAME_COUNT)?.max(0.0) as u32;
+            let frames = frame_range(event, options, fps, frame_count);
+            capture.set(videoio::CAP_PROP_POS_FRAMES, frames.start as f64)?;
+
+            let (mut writer, _) = open_writer(partial, &capture, fps)?;
+            let mut frame = Mat::default();
+            for _ in frames {
+                if !capture.read(&mut frame)? || frame.empty() {
+                    break;
+                }
+                writer.write(&frame)?;
+            }
+            writer.release()?;
+            Ok(())
+        })
+    }
+
+    /// Saves one frame of the source video as a JPEG.
+    ///
+    /// Blocking; run it off the async runtime.
+    pub fn thumbnail(
+        &self,
+        source: &Path,
+        analysis_id: Uuid,
+        frame_number: u32,
+    ) -> Result<PathBuf> {
+        let output = self
+            .dir
+            .join(format!("{}-frame{}.jpg", analysis_id, frame_number));
+        self.cached(output, "partial.jpg", |partial| {
+            let source_str = source.to_str().context("Invalid source video path")?;
+            let (mut capture, _) = open_video(source_str)?;
+            capture.set(videoio::CAP_PROP_POS_FRAMES, frame_number as f64)?;
+            let mut frame = Mat::default();
+            if !capture.read(&mut frame)? || frame.empty() {
+                anyhow::bail!("Frame {} is past the end of the video", frame_number);
+            }
+
+            let partial_str = partial.to_str().context("Invalid thumbnail path")?;
+            if !imgcodecs::imwrite(partial_str, &frame, &Vector::new())? {
+                anyhow::bail!("Could not write thumbnail {}", partial.display());
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns `output`, first having `write` produce it at a partial path
+    /// with `extension` unless it is cached. Only one writer runs per output;
+    /// a failed one leaves no partial file behind.
+    fn cached(
+        &self,
+        output: PathBuf,
+        extension: &str,
+        write: impl FnOnce(&Path) -> Result<()>,
+    ) -> Result<PathBuf> {
+        let lock = Arc::clone(self.in_flight.entry(output.clone()).or_default().value());
+        let result = {
+            let _writing = lock.lock().unwrap_or_else(PoisonError::into_inner);
+            if output.exists() {
+                Ok(())
+            } else {
+                let partial = output.with_extension(extension);
+                let written = write(&partial).and_then(|()| {
+                    std::fs::rename(&partial, &output)
+                        .with_context(|| format!("Failed to finish {}", output.display()))
+                });
+                if written.is_err() {
+                    let _ = std::fs::remove_file(&partial);
+                }
+                written
+            }
+        };
+        // Entries are cloned under the map's lock, so a count of two, the
+        // map's and this one, means nobody else is waiting on it
+        self.in_flight
+            .remove_if(&output, |_, lock| Arc::strong_count(lock) == 2);
+        result.map(|()| output)
+    }
+}
+
+/// Source frames covering the event plus its rolls, clamped to the video.
+fn frame_range(
+    event: &CriticalEvent,
+    options: ClipOptions,
+    fps: f64,
+    frame_count: u32,
+) -> Range<u32> {
+    let start = ((event.timestamp - options.pre_roll).max(0.0) * fps).floor() as u32;
+    let end = ((event.end_timestamp + options.post_roll) * fps).ceil() as u32;
+    // Some containers do not report a frame count; read until the stream ends
+    let end = if frame_count > 0 {
+        end.min(frame_count)
+    } else {
+        end
+    };
+    start..end.max(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_clip_frame_range() {
+        let event = CriticalEvent {
+            event_type: "red_light_run".to_string(),
+            timestamp: 10.0,
+            end_timestamp: 12.0,
+            severity: 0.9,
+            frames: vec![300, 360],
+            context: HashMap::new(),
+        };
+        let options = ClipOptions::default();
+        assert_eq!(frame_range(&event, options, 30.0, 900), 150..510);
+        assert_eq!(frame_range(&event, options, 30.0, 400), 150..400);
+        assert_eq!(frame_range(&event, options, 30.0, 0), 150..510);
+
+        let early = ClipOptions {
+            pre_roll: 20.0,
+            post_roll: 0.0,
+        };
+        assert_eq!(frame_range(&event, early, 30.0, 900), 0..360);
+    }
+
+    #[test]
+    fn test_rolls_rounded_to_cache_key() {
+        let options = ClipOptions {
+            pre_roll: 5.04,
+            post_roll: 2.96,
+        };
+        assert_eq!(
+            options.rounded(),
+            ClipOptions {
+                pre_roll: 5.0,
+                post_roll: 3.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_one_writer_per_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let clipper =
+            Arc::new(Clipper::new(dir.path().to_path_buf(), ClipOptions::default()).unwrap());
+        let output = dir.path().join("clip.mp4");
+        let writes = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let (clipper, output, writes) =
+                    (Arc::clone(&clipper), output.clone(), Arc::clone(&writes));
+                std::thread::spawn(move || {
+                    clipper.cached(output, "partial.mp4", |partial| {
+                        writes.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(50));
+                        Ok(std::fs::write(partial, b"clip")?)
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap().unwrap(), output);
+        }
+        assert_eq!(writes.load(Ordering::SeqCst), 1);
+        assert!(clipper.in_flight.is_empty());
+
+        let failed = clipper.cached(dir.path().join("bad.mp4"), "partial.mp4", |partial| {
+            std::fs::write(partial, b"half")?;
+            anyhow::bail!("Decoder gave up")
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
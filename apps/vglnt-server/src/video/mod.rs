@@ -0,0 +1,277 @@
+use crate::coaching::Coach;
+use crate::events::EventDetector;
+use crate::llm::LLMClient;
+use crate::lstm::{self, SequenceBackend};
+use crate::metrics::METRICS;
+use crate::risk::{self, RiskEngine};
+use crate::smoothing::Smoother;
+use crate::stats;
+use crate::summary::SummaryBuilder;
+use crate::types::{AnalysisMetadata, DrivingAnalysis, Telemetry};
+use anyhow::{Context, Result};
+use opencv::core::{Mat, Size, Vector};
+use opencv::prelude::*;
+use opencv::{imgcodecs, videoio};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+use tracing::{error, info, info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+pub mod clip;
+mod mp4;
+pub mod render;
+pub mod store;
+
+/// Frames handed to the vision model per second of footage.
+const DEFAULT_SAMPLES_PER_SECOND: f64 = 1.0;
+
+pub struct VideoAnalyzer {
+    llm: LLMClient,
+    sequence_model: Box<dyn SequenceBackend>,
+    risk_engine: RiskEngine,
+    event_detector: EventDetector,
+    smoother: Smoother,
+    coach: Coach,
+    summary_builder: SummaryBuilder,
+    samples_per_second: f64,
+}
+
+/// Sampled JPEG frames plus the stream properties needed for metadata.
+struct DecodedVideo {
+    frames: Vec<(Vec<u8>, u32)>,
+    fps: f64,
+    frame_count: u32,
+}
+
+impl VideoAnalyzer {
+    pub fn new() -> Result<Self> {
+        let sequence_model = lstm::backend_from_env()?;
+        info!("Using {} sequence model backend", sequence_model.name());
+
+        let samples_per_second = std::env::var("VGLNT_SAMPLES_PER_SECOND")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SAMPLES_PER_SECOND);
+
+        Ok(Self {
+            llm: LLMClient::new()?,
+            sequence_model,
+            risk_engine: RiskEngine::from_env()?,
+            event_detector: EventDetector::default(),
+            smoother: Smoother::from_env(),
+            coach: Coach::from_env()?,
+            summary_builder: SummaryBuilder::from_env()?,
+            samples_per_second,
+        })
+    }
+
+    /// Analyses a video. `frame_sent` is called as each frame goes to the
+    /// vision model, whether or not the model can analyse it, with the number
+    /// of frames sent before it and the number sampled from the video.
+    pub async fn process_video(
+        &self,
+        analysis_id: Uuid,
+        filename: &str,
+        path: &str,
+        telemetry: Option<Telemetry>,
+        frame_sent: impl Fn(u32, u32),
+    ) -> Result<DrivingAnalysis> {
+        let path_owned = path.to_string();
+        let samples_per_second = self.samples_per_second;
+        let span = info_span!("decode_frames", samples_per_second);
+        let video = tokio::task::spawn_blocking(move || {
+            span.in_scope(|| decode_frames(&path_owned, samples_per_second))
+        })
+        .await??;
+        info!(
+            frames = video.frames.len(),
+            fps = video.fps,
+            "Decoded {}",
+            filename
+        );
+        let telemetry = match telemetry {
+            Some(telemetry) => Some(telemetry),
+            None => self.embedded_telemetry(path).await,
+        };
+
+        let mut raw_frame_analyses = Vec::with_capacity(video.frames.len());
+        let mut last_error = None;
+        let total_frames = video.frames.len() as u32;
+        for (idx, (jpeg, frame_number)) in video.frames.iter().enumerate() {
+            let span = info_span!("analyze_frame", frame_number = *frame_number);
+            frame_sent(idx as u32, total_frames);
+            match self
+                .llm
+                .analyze_frame(jpeg, *frame_number)
+                .instrument(span)
+                .await
+            {
+                Ok(frame_data) => {
+                    let timestamp = *frame_number as f64 / video.fps;
+                    raw_frame_analyses.push(frame_data.into_analysis(*frame_number, timestamp));
+                }
+                Err(e) => {
+                    error!("Failed to analyze frame {}: {}", frame_number, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        if raw_frame_analyses.is_empty() {
+            // Keep the model error as the cause so callers can tell an outage from bad input
+            return Err(match last_error {
+                Some(e) => e.context("No frames could be analyzed"),
+                None => anyhow::anyhow!("No frames could be analyzed"),
+            });
+        }
+        let mut frame_analyses = self.smoother.smooth(&raw_frame_analyses);
+        if let Some(telemetry) = &telemetry {
+            telemetry.annotate(&mut frame_analyses);
+        }
+
+        let started = Instant::now();
+        let span = info_span!("sequence_model", backend = self.sequence_model.name());
+        let mut lstm_output =
+            span.in_scope(|| self.sequence_model.process_sequence(&frame_analyses))?;
+        METRICS
+            .lstm_inference_seconds
+            .with_label_values(&[self.sequence_model.name()])
+            .observe(started.elapsed().as_secs_f64());
+        let rule_risks = self.risk_engine.evaluate(&frame_analyses);
+        lstm_output.risk_factors = risk::merge(lstm_output.risk_factors, rule_risks);
+        let video_duration = video.frame_count as f64 / video.fps;
+        let critical_events = self.event_detector.detect(&frame_analyses);
+        let improvement_areas = self
+            .coach
+            .improvement_areas(&frame_analyses, &lstm_output.risk_factors);
+        let improvement_areas = self
+            .coach
+            .enrich(&self.llm, improvement_areas)
+            .instrument(info_span!("coaching"))
+            .await;
+        let summary = self.summary_builder.build(
+            &lstm_output,
+            critical_events,
+            improvement_areas,
+            stats::driving_stats(&frame_analyses, telemetry.as_ref(), video_duration),
+        );
+
+        Ok(DrivingAnalysis {
+            metadata: AnalysisMetadata {
+                id: analysis_id,
+                filename: filename.to_string(),
+                upload_time: SystemTime::now(),
+                video_duration,
+                frame_count: video.frame_count,
+                fps: video.fps as f32,
+            },
+            frame_analyses,
+            raw_frame_analyses,
+            telemetry,
+            lstm_output,
+            summary,
+        })
+    }
+
+    pub fn llm(&self) -> &LLMClient {
+        &self.llm
+    }
+
+    pub fn sequence_model(&self) -> &dyn SequenceBackend {
+        self.sequence_model.as_ref()
+    }
+
+    /// GPS recorded into the video file itself, used when no sidecar was uploaded.
+    /// A malformed container only loses the telemetry, not the analysis.
+    async fn embedded_telemetry(&self, path: &str) -> Option<Telemetry> {
+        let path_owned = PathBuf::from(path);
+        let span = Span::current();
+        let extracted = tokio::task::spawn_blocking(move || {
+            span.in_scope(|| mp4::extract_telemetry(&path_owned))
+        })
+        .await;
+        match extracted {
+            Ok(Ok(Some(telemetry))) => {
+                info!(
+                    "Found {} embedded {:?} telemetry samples in {}",
+                    telemetry.samples.len(),
+                    telemetry.format,
+                    path
+                );
+                Some(telemetry)
+            }
+            Ok(Ok(None)) => None,
+            Ok(Err(e)) => {
+                warn!("Failed to read embedded telemetry from {}: {:#}", path, e);
+                None
+            }
+            Err(e) => {
+                warn!("Embedded telemetry extraction panicked for {}: {}", path, e);
+                None
+            }
+        }
+    }
+}
+
+/// Opens a video for decoding, with its frame rate (30 when unreported).
+pub(crate) fn open_video(path: &str) -> Result<(videoio::VideoCapture, f64)> {
+    let capture = videoio::VideoCapture::from_file(path, videoio::CAP_ANY)
+        .with_context(|| format!("Failed to open video {}", path))?;
+    if !capture.is_opened()? {
+        anyhow::bail!("Could not open video file: {}", path);
+    }
+
+    let fps = match capture.get(videoio::CAP_PROP_FPS)? {
+        fps if fps > 0.0 => fps,
+        _ => 30.0,
+    };
+    Ok((capture, fps))
+}
+
+/// Opens an MP4 writer matching the frame rate and size of `capture`.
+pub(crate) fn open_writer(
+    path: &Path,
+    capture: &videoio::VideoCapture,
+    fps: f64,
+) -> Result<(videoio::VideoWriter, Size)> {
+    let size = Size::new(
+        capture.get(videoio::CAP_PROP_FRAME_WIDTH)? as i32,
+        capture.get(videoio::CAP_PROP_FRAME_HEIGHT)? as i32,
+    );
+    let path_str = path.to_str().context("Invalid output video path")?;
+    let fourcc = videoio::VideoWriter::fourcc('m', 'p', '4', 'v')?;
+    let writer = videoio::VideoWriter::new(path_str, fourcc, fps, size, true)?;
+    if !writer.is_opened()? {
+        anyhow::bail!("Could not open video writer: {}", path.display());
+    }
+    Ok((writer, size))
+}
+
+fn decode_frames(path: &str, samples_per_second: f64) -> Result<DecodedVideo> {
+    let (mut capture, fps) = open_video(path)?;
+    let interval = (fps / samples_per_second).round().max(1.0) as u32;
+
+    let mut frames = Vec::new();
+    let mut frame = Mat::default();
+    let mut frame_number = 0u32;
+    loop {
+        let started = Instant::now();
+        if !capture.read(&mut frame)? || frame.empty() {
+            break;
+        }
+        METRICS
+            .frame_decode_seconds
+            .observe(started.elapsed().as_secs_f64());
+        if frame_number % interval == 0 {
+            let mut jpeg = Vector::<u8>::new();
+            imgcodecs::imencode(".jpg", &frame, &mut jpeg, &Vector::new())?;
+            frames.push((jpeg.to_vec(), frame_number));
+        }
+        frame_number += 1;
+    }
+
+    Ok(DecodedVideo {
+        frames,
+        fps,
+        frame_count: frame_number,
+    })
+}
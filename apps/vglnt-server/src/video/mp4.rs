@@ -0,0 +1,812 @@
+//! GPS embedded in dashcam MP4 files.
+//!
+//! Three layouts are recognised, tried in this order:
+//! - Novatek: a `moov/gps ` index of `free` boxes tagged `GPS `, one fix per
+//!   second. Files without the index are scanned for the tagged boxes.
+//! - GoPro: a `meta` track of `gpmd` samples holding GPMF `GPS5` streams.
+//! - Subtitle tracks (`sbtl`/`text`) whose samples carry NMEA `RMC` sentences.
+//!
+//! Every size and count comes from the upload, so each is checked against
+//! the data it describes and capped before it drives an allocation.
+
+use crate::telemetry::{self, KNOTS_TO_KMH, MPS_TO_KMH};
+use crate::types::{Telemetry, TelemetryFormat, TelemetrySample};
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const NOVATEK_MAGIC: &[u8; 8] = b"freeGPS ";
+/// Bytes of a Novatek GPS box up to and including the speed field.
+const NOVATEK_RECORD_LEN: usize = 56;
+const SCAN_CHUNK: usize = 1 << 20;
+/// Largest box payload or sample read into memory. GPS indexes, sample
+/// tables and samples are far smaller; anything bigger is corrupt.
+const MAX_READ: u64 = 16 << 20;
+/// Most samples read from one track, over 11 hours at 25 per second.
+const MAX_SAMPLES: usize = 1 << 20;
+/// Deepest GPMF nesting followed.
+const MAX_GPMF_DEPTH: usize = 8;
+
+/// Returns the embedded GPS track of the video at `path`, if it has one.
+pub fn extract_telemetry(path: &Path) -> Result<Option<Telemetry>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    read_telemetry(&mut BufReader::new(file))
+}
+
+fn read_telemetry<R: Read + Seek>(reader: &mut R) -> Result<Option<Telemetry>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let top = read_boxes(reader, 0, len)?;
+    let Some(moov) = find(&top, b"moov") else {
+        return Ok(None);
+    };
+    let moov_children = read_boxes(reader, moov.start, moov.end)?;
+
+    if let Some(index) = find(&moov_children, b"gps ") {
+        let samples = novatek_indexed(reader, index)?;
+        if !samples.is_empty() {
+            return Ok(Some(novatek_telemetry(samples)));
+        }
+    }
+
+    for trak in moov_children.iter().filter(|b| &b.kind == b"trak") {
+        let Some(track) = read_track(reader, trak)? else {
+            continue;
+        };
+        let samples = match (&track.handler, &track.format) {
+            (_, b"gpmd") => gpmf_samples(reader, &track)?,
+            (b"sbtl" | b"text", _) => subtitle_samples(reader, &track)?,
+            _ => continue,
+        };
+        if !samples.is_empty() {
+            let format = if &track.format == b"gpmd" {
+                TelemetryFormat::Gpmf
+            } else {
+                TelemetryFormat::Subtitle
+            };
+            return Ok(Some(Telemetry::new(format, samples)));
+        }
+    }
+
+    let samples = novatek_scan(reader, len)?;
+    Ok((!samples.is_empty()).then(|| novatek_telemetry(samples)))
+}
+
+/// A box's payload range within the file.
+#[derive(Debug, Clone, Copy)]
+struct Mp4Box {
+    kind: [u8; 4],
+    start: u64,
+    end: u64,
+}
+
+/// Lists the boxes between `start` and `end`, stopping at a truncated box.
+fn read_boxes<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> Result<Vec<Mp4Box>> {
+    let mut boxes = Vec::new();
+    let mut pos = start;
+
+    while pos + 8 <= end {
+        reader.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (header_len, size) = match u32::from_be_bytes(header[..4].try_into()?) {
+            0 => (8, end - pos),
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large)?;
+                (16, u64::from_be_bytes(large))
+            }
+            size => (8, size as u64),
+        };
+        let Some(box_end) = pos.checked_add(size).filter(|box_end| *box_end <= end) else {
+            break;
+        };
+        if size < header_len {
+            break;
+        }
+        boxes.push(Mp4Box {
+            kind,
+            start: pos + header_len,
+            end: box_end,
+        });
+        pos = box_end;
+    }
+
+    Ok(boxes)
+}
+
+fn find<'a>(boxes: &'a [Mp4Box], kind: &[u8; 4]) -> Option<&'a Mp4Box> {
+    boxes.iter().find(|b| &b.kind == kind)
+}
+
+fn read_range<R: Read + Seek>(reader: &mut R, start: u64, len: u64) -> Result<Vec<u8>> {
+    anyhow::ensure!(len <= MAX_READ, "MP4 box of {} bytes is too large", len);
+    reader.seek(SeekFrom::Start(start))?;
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn read_payload<R: Read + Seek>(reader: &mut R, mp4_box: &Mp4Box) -> Result<Vec<u8>> {
+    read_range(reader, mp4_box.start, mp4_box.end - mp4_box.start)
+}
+
+fn be_u16(data: &[u8], at: usize) -> Result<u16> {
+    let bytes = data.get(at..at + 2).context("Truncated MP4 box")?;
+    Ok(u16::from_be_bytes(bytes.try_into()?))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Result<u32> {
+    let bytes = data.get(at..at + 4).context("Truncated MP4 box")?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Result<u64> {
+    let bytes = data.get(at..at + 8).context("Truncated MP4 box")?;
+    Ok(u64::from_be_bytes(bytes.try_into()?))
+}
+
+/// The entry count of a sample table whose count is at `at`, capped by the
+/// entries of `entry_len` bytes that follow it in the payload.
+fn entry_count(table: &[u8], at: usize, entry_len: usize) -> Result<usize> {
+    let count = be_u32(table, at)? as usize;
+    Ok(count.min(table.len().saturating_sub(at + 4) / entry_len))
+}
+
+// Novatek
+
+fn novatek_indexed<R: Read + Seek>(reader: &mut R, index: &Mp4Box) -> Result<Vec<TelemetrySample>> {
+    let data = read_payload(reader, index)?;
+    let mut samples = Vec::new();
+    // Version and encoded date precede the (offset, size) pairs
+    let mut at = 8;
+    while at + 8 <= data.len() {
+        let (offset, size) = (be_u32(&data, at)? as u64, be_u32(&data, at + 4)? as u64);
+        at += 8;
+        if size < NOVATEK_RECORD_LEN as u64 {
+            continue;
+        }
+        let Ok(record) = read_range(reader, offset, NOVATEK_RECORD_LEN as u64) else {
+            continue;
+        };
+        samples.extend(novatek_record(&record));
+    }
+    Ok(samples)
+}
+
+/// Finds `free` boxes tagged `GPS ` anywhere in the file.
+fn novatek_scan<R: Read + Seek>(reader: &mut R, len: u64) -> Result<Vec<TelemetrySample>> {
+    let mut samples = Vec::new();
+    let mut chunk = vec![0u8; SCAN_CHUNK];
+    let mut pos = 0u64;
+
+    while pos < len {
+        reader.seek(SeekFrom::Start(pos))?;
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        for at in 0..read.saturating_sub(NOVATEK_MAGIC.len() - 1) {
+            if &chunk[at..at + NOVATEK_MAGIC.len()] != NOVATEK_MAGIC || at < 4 {
+                continue;
+            }
+            let box_start = pos + at as u64 - 4;
+            if let Ok(record) = read_range(reader, box_start, NOVATEK_RECORD_LEN as u64) {
+                samples.extend(novatek_record(&record));
+            }
+        }
+        if pos + read as u64 >= len {
+            break;
+        }
+        // Overlap so a tag split across chunks is still found
+        pos += read.saturating_sub(NOVATEK_MAGIC.len() + 4).max(1) as u64;
+    }
+
+    samples.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    samples.dedup_by(|a, b| a.timestamp == b.timestamp);
+    Ok(samples)
+}
+
+/// Decodes one Novatek GPS box: little-endian time and date fields, a fix
+/// flag, hemispheres, then NMEA-style coordinates and speed in knots.
+fn novatek_record(record: &[u8]) -> Option<TelemetrySample> {
+    if record.len() < NOVATEK_RECORD_LEN || &record[4..12] != NOVATEK_MAGIC {
+        return None;
+    }
+    let le_u32 = |at: usize| u32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+    let le_f32 = |at: usize| f32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+
+    if record[40] != b'A' {
+        return None;
+    }
+    let (hour, minute, second) = (le_u32(16), le_u32(20), le_u32(24));
+    let year = le_u32(28);
+    let year = if year < 100 { year + 2000 } else { year };
+    let date = NaiveDate::from_ymd_opt(year as i32, le_u32(32), le_u32(36))?;
+
+    Some(TelemetrySample {
+        timestamp: date.num_days_from_ce() as f64 * 86_400.0
+            + hour as f64 * 3600.0
+            + minute as f64 * 60.0
+            + second as f64,
+        latitude: telemetry::nmea_degrees(le_f32(44) as f64, record[41] as char),
+        longitude: telemetry::nmea_degrees(le_f32(48) as f64, record[42] as char),
+        speed: Some(le_f32(52) * KNOTS_TO_KMH),
+        acceleration: None,
+    })
+}
+
+/// Novatek records carry wall-clock time and start with the recording.
+fn novatek_telemetry(mut samples: Vec<TelemetrySample>) -> Telemetry {
+    telemetry::rebase(&mut samples, 0.0);
+    Telemetry::new(TelemetryFormat::Novatek, samples)
+}
+
+// Tracks
+
+struct Track {
+    handler: [u8; 4],
+    format: [u8; 4],
+    /// File offset, size, start and duration in seconds of each sample.
+    samples: Vec<TrackSample>,
+}
+
+struct TrackSample {
+    offset: u64,
+    size: u64,
+    start: f64,
+    duration: f64,
+}
+
+fn read_track<R: Read + Seek>(reader: &mut R, trak: &Mp4Box) -> Result<Option<Track>> {
+    let children = read_boxes(reader, trak.start, trak.end)?;
+    let Some(mdia) = find(&children, b"mdia") else {
+        return Ok(None);
+    };
+    let mdia = read_boxes(reader, mdia.start, mdia.end)?;
+    let (Some(mdhd), Some(hdlr), Some(minf)) = (
+        find(&mdia, b"mdhd"),
+        find(&mdia, b"hdlr"),
+        find(&mdia, b"minf"),
+    ) else {
+        return Ok(None);
+    };
+    let minf = read_boxes(reader, minf.start, minf.end)?;
+    let Some(stbl) = find(&minf, b"stbl") else {
+        return Ok(None);
+    };
+    let stbl = read_boxes(reader, stbl.start, stbl.end)?;
+
+    let mdhd = read_payload(reader, mdhd)?;
+    let timescale = match mdhd.first() {
+        Some(1) => be_u32(&mdhd, 20)?,
+        _ => be_u32(&mdhd, 12)?,
+    };
+    let hdlr = read_payload(reader, hdlr)?;
+    let handler: [u8; 4] = hdlr.get(8..12).context("Truncated hdlr box")?.try_into()?;
+
+    let mut table = |kind: &[u8; 4]| -> Result<Option<Vec<u8>>> {
+        find(&stbl, kind)
+            .map(|b| read_payload(reader, b))
+            .transpose()
+    };
+    let Some(stsd) = table(b"stsd")? else {
+        return Ok(None);
+    };
+    let format: [u8; 4] = stsd.get(12..16).context("Truncated stsd box")?.try_into()?;
+    // Video and audio sample tables can be megabytes; skip them unread
+    if !is_telemetry(&handler, &format) {
+        return Ok(None);
+    }
+    let (Some(stts), Some(stsc), Some(stsz)) = (table(b"stts")?, table(b"stsc")?, table(b"stsz")?)
+    else {
+        return Ok(None);
+    };
+    let offsets = match (table(b"stco")?, table(b"co64")?) {
+        (Some(stco), _) => (0..entry_count(&stco, 4, 4)?)
+            .map(|idx| be_u32(&stco, 8 + idx * 4).map(u64::from))
+            .collect::<Result<Vec<_>>>()?,
+        (None, Some(co64)) => (0..entry_count(&co64, 4, 8)?)
+            .map(|idx| be_u64(&co64, 8 + idx * 8))
+            .collect::<Result<Vec<_>>>()?,
+        (None, None) => return Ok(None),
+    };
+
+    // Sample sizes
+    let fixed_size = be_u32(&stsz, 4)?;
+    let sample_count = match fixed_size {
+        0 => entry_count(&stsz, 8, 4)?,
+        _ => be_u32(&stsz, 8)? as usize,
+    }
+    .min(MAX_SAMPLES);
+    let sizes = (0..sample_count)
+        .map(|idx| match fixed_size {
+            0 => be_u32(&stsz, 12 + idx * 4).map(u64::from),
+            size => Ok(size as u64),
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    // Sample file offsets, chunk by chunk
+    let chunk_runs = (0..entry_count(&stsc, 4, 12)?)
+        .map(|idx| Ok((be_u32(&stsc, 8 + idx * 12)?, be_u32(&stsc, 12 + idx * 12)?)))
+        .collect::<Result<Vec<(u32, u32)>>>()?;
+    let mut sample_offsets = Vec::with_capacity(sample_count);
+    for (chunk_idx, chunk_offset) in offsets.iter().enumerate() {
+        let chunk = chunk_idx as u32 + 1;
+        let per_chunk = chunk_runs
+            .iter()
+            .rev()
+            .find(|(first, _)| *first <= chunk)
+            .map_or(0, |(_, count)| *count);
+        let mut offset = *chunk_offset;
+        for _ in 0..per_chunk {
+            let Some(size) = sizes.get(sample_offsets.len()) else {
+                break;
+            };
+            sample_offsets.push(offset);
+            offset = offset.saturating_add(*size);
+        }
+    }
+
+    // Sample times
+    let mut durations = Vec::with_capacity(sample_count);
+    for idx in 0..entry_count(&stts, 4, 8)? {
+        let (count, delta) = (be_u32(&stts, 8 + idx * 8)?, be_u32(&stts, 12 + idx * 8)?);
+        let count = (count as usize).min(sample_count - durations.len());
+        durations.resize(
+            durations.len() + count,
+            delta as f64 / timescale.max(1) as f64,
+        );
+    }
+
+    let mut start = 0.0;
+    let samples = sample_offsets
+        .into_iter()
+        .zip(sizes)
+        .zip(durations)
+        .map(|((offset, size), duration)| {
+            let sample = TrackSample {
+                offset,
+                size,
+                start,
+                duration,
+            };
+            start += duration;
+            sample
+        })
+        .collect();
+
+    Ok(Some(Track {
+        handler,
+        format,
+        samples,
+    }))
+}
+
+/// Whether a track can hold GPS: GPMF metadata or a subtitle track.
+fn is_telemetry(handler: &[u8; 4], format: &[u8; 4]) -> bool {
+    format == b"gpmd" || matches!(handler, b"sbtl" | b"text")
+}
+
+// GoPro GPMF
+
+fn gpmf_samples<R: Read + Seek>(reader: &mut R, track: &Track) -> Result<Vec<TelemetrySample>> {
+    let mut samples = Vec::new();
+    for sample in &track.samples {
+        let data = read_range(reader, sample.offset, sample.size)?;
+        let mut fixes = Vec::new();
+        walk_gpmf(&data, &mut GpmfStream::default(), &mut fixes, 0);
+
+        let step = sample.duration / fixes.len().max(1) as f64;
+        for (idx, [latitude, longitude, _altitude, speed_2d, _speed_3d]) in
+            fixes.into_iter().enumerate()
+        {
+            samples.push(TelemetrySample {
+                timestamp: sample.start + step * idx as f64,
+                latitude: Some(latitude),
+                longitude: Some(longitude),
+                speed: Some(speed_2d as f32 * MPS_TO_KMH),
+                acceleration: None,
+            });
+        }
+    }
+    Ok(samples)
+}
+
+/// Per-stream state that applies to the `GPS5` entries following it.
+#[derive(Default)]
+struct GpmfStream {
+    scale: Vec<f64>,
+    fix: Option<u32>,
+}
+
+/// Walks GPMF key-length-value entries, collecting scaled `GPS5` fixes
+/// (latitude, longitude, altitude, 2D speed, 3D speed).
+fn walk_gpmf(data: &[u8], stream: &mut GpmfStream, fixes: &mut Vec<[f64; 5]>, depth: usize) {
+    if depth > MAX_GPMF_DEPTH {
+        return;
+    }
+    let mut at = 0;
+    while at + 8 <= data.len() {
+        let key = &data[at..at + 4];
+        let kind = data[at + 4];
+        let size = data[at + 5] as usize;
+        let repeat = u16::from_be_bytes([data[at + 6], data[at + 7]]) as usize;
+        let len = size * repeat;
+        let Some(payload) = data.get(at + 8..at + 8 + len) else {
+            return;
+        };
+        at += 8 + ((len + 3) & !3);
+
+        match (key, kind) {
+            (_, 0) => walk_gpmf(payload, &mut GpmfStream::default(), fixes, depth + 1),
+            (b"SCAL", _) => stream.scale = gpmf_values(payload, kind),
+            (b"GPSF", _) => stream.fix = gpmf_values(payload, kind).first().map(|v| *v as u32),
+            (b"GPS5", b'l') if stream.fix != Some(0) => {
+                let values = gpmf_values(payload, kind);
+                for fix in values.chunks_exact(5) {
+                    let mut scaled = [0.0; 5];
+                    for (idx, value) in fix.iter().enumerate() {
+                        let scale = match stream.scale.as_slice() {
+                            [single] => *single,
+                            scales => scales.get(idx).copied().unwrap_or(1.0),
+                        };
+                        scaled[idx] = value / if scale == 0.0 { 1.0 } else { scale };
+                    }
+                    fixes.push(scaled);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn gpmf_values(payload: &[u8], kind: u8) -> Vec<f64> {
+    match kind {
+        b'l' => payload
+            .chunks_exact(4)
+            .map(|b| i32::from_be_bytes(b.try_into().unwrap()) as f64)
+            .collect(),
+        b'L' => payload
+            .chunks_exact(4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as f64)
+            .collect(),
+        b's' => payload
+            .chunks_exact(2)
+            .map(|b| i16::from_be_bytes(b.try_into().unwrap()) as f64)
+            .collect(),
+        b'S' => payload
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes(b.try_into().unwrap()) as f64)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Subtitles
+
+/// Reads `tx3g`-style samples (a 16-bit length then text) and keeps the first
+/// valid `RMC` sentence of each, timed by the sample rather than the sentence.
+fn subtitle_samples<R: Read + Seek>(reader: &mut R, track: &Track) -> Result<Vec<TelemetrySample>> {
+    let mut samples = Vec::new();
+    for sample in &track.samples {
+        let data = read_range(reader, sample.offset, sample.size)?;
+        let Ok(len) = be_u16(&data, 0) else {
+            continue;
+        };
+        let Some(text) = data.get(2..2 + len as usize) else {
+            continue;
+        };
+        let text = String::from_utf8_lossy(text);
+        if let Some(fix) = text
+            .lines()
+            .find_map(|line| telemetry::parse_rmc(line.trim()))
+        {
+            samples.push(TelemetrySample {
+                timestamp: sample.start,
+                ..fix
+            });
+        }
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn ftyp() -> Vec<u8> {
+        mp4_box(b"ftyp", b"isom\0\0\0\0isom")
+    }
+
+    fn novatek_box(second: u32, speed_knots: f32) -> Vec<u8> {
+        let mut payload = b"GPS ".to_vec();
+        payload.extend_from_slice(&[0; 4]);
+        for field in [9, 30, second, 24, 3, 1] {
+            payload.extend_from_slice(&u32::to_le_bytes(field));
+        }
+        payload.extend_from_slice(b"ANW\0");
+        for field in [5130.0f32, 7.5, speed_knots, 90.0] {
+            payload.extend_from_slice(&field.to_le_bytes());
+        }
+        mp4_box(b"free", &payload)
+    }
+
+    #[test]
+    fn test_novatek_index() {
+        let mut file = ftyp();
+        let mut index = vec![0u8; 8];
+        for (second, speed) in [(0, 10.0), (1, 20.0)] {
+            let record = novatek_box(second, speed);
+            index.extend_from_slice(&(file.len() as u32).to_be_bytes());
+            index.extend_from_slice(&(record.len() as u32).to_be_bytes());
+            file.extend(record);
+        }
+        file.extend(mp4_box(b"moov", &mp4_box(b"gps ", &index)));
+
+        let telemetry = read_telemetry(&mut Cursor::new(file)).unwrap().unwrap();
+        assert_eq!(telemetry.format, TelemetryFormat::Novatek);
+        assert_eq!(telemetry.samples.len(), 2);
+        assert_eq!(telemetry.samples[1].timestamp, 1.0);
+        assert_eq!(telemetry.samples[0].latitude, Some(51.5));
+        assert_eq!(telemetry.samples[0].longitude, Some(-0.125));
+        assert!((telemetry.samples[1].speed.unwrap() - 37.04).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_novatek_scan_without_index() {
+        let mut file = ftyp();
+        file.extend(novatek_box(5, 10.0));
+        file.extend(mp4_box(b"moov", &[]));
+
+        let telemetry = read_telemetry(&mut Cursor::new(file)).unwrap().unwrap();
+        assert_eq!(telemetry.samples.len(), 1);
+        assert_eq!(telemetry.samples[0].timestamp, 0.0);
+    }
+
+    fn klv(key: &[u8; 4], kind: u8, size: u8, values: &[u8]) -> Vec<u8> {
+        let repeat = if size == 0 {
+            0
+        } else {
+            values.len() / size as usize
+        };
+        let mut data = key.to_vec();
+        data.push(kind);
+        data.push(size);
+        data.extend_from_slice(&(repeat as u16).to_be_bytes());
+        data.extend_from_slice(values);
+        data.resize((data.len() + 3) & !3, 0);
+        data
+    }
+
+    fn nested(key: &[u8; 4], children: &[u8]) -> Vec<u8> {
+        let mut data = key.to_vec();
+        data.extend_from_slice(&[0, 4]);
+        data.extend_from_slice(&((children.len() / 4) as u16).to_be_bytes());
+        data.extend_from_slice(children);
+        data
+    }
+
+    fn be_i32s(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    fn full_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut payload = vec![0u8; 4];
+        payload.extend_from_slice(body);
+        mp4_box(kind, &payload)
+    }
+
+    /// An MP4 with one track whose sample table is `stsd` plus `tables`.
+    fn track_file(handler: &[u8; 4], format: &[u8; 4], mdat: &[u8], tables: &[u8]) -> Vec<u8> {
+        let mut file = ftyp();
+        file.extend(mp4_box(b"mdat", mdat));
+
+        let mut mdhd = vec![0u8; 8];
+        mdhd.extend_from_slice(&1000u32.to_be_bytes());
+        mdhd.extend_from_slice(&1000u32.to_be_bytes());
+        let mut hdlr = vec![0u8; 4];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0; 12]);
+        let mut stsd = 1u32.to_be_bytes().to_vec();
+        stsd.extend(mp4_box(format, &[0; 8]));
+
+        let mut stbl = full_box(b"stsd", &stsd);
+        stbl.extend_from_slice(tables);
+        let mut mdia = full_box(b"mdhd", &mdhd);
+        mdia.extend(full_box(b"hdlr", &hdlr));
+        mdia.extend(mp4_box(b"minf", &mp4_box(b"stbl", &stbl)));
+        let trak = mp4_box(b"trak", &mp4_box(b"mdia", &mdia));
+        file.extend(mp4_box(b"moov", &trak));
+        file
+    }
+
+    /// Offset of the `mdat` payload in a `track_file`.
+    const MDAT_OFFSET: i32 = 28;
+
+    /// A track of one-second samples stored back to back in one chunk.
+    fn sample_track(handler: &[u8; 4], format: &[u8; 4], samples: &[Vec<u8>]) -> Vec<u8> {
+        let count = samples.len() as i32;
+        let mut sizes = vec![0, count];
+        sizes.extend(samples.iter().map(|sample| sample.len() as i32));
+
+        let mut tables = full_box(b"stts", &be_i32s(&[1, count, 1000]));
+        tables.extend(full_box(b"stsc", &be_i32s(&[1, 1, count, 1])));
+        tables.extend(full_box(b"stsz", &be_i32s(&sizes)));
+        tables.extend(full_box(b"stco", &be_i32s(&[1, MDAT_OFFSET])));
+        track_file(handler, format, &samples.concat(), &tables)
+    }
+
+    fn gopro_file() -> Vec<u8> {
+        let mut strm = klv(
+            b"SCAL",
+            b'l',
+            4,
+            &be_i32s(&[10_000_000, 10_000_000, 1000, 1000, 100]),
+        );
+        strm.extend(klv(b"GPSF", b'L', 4, &be_i32s(&[3])));
+        strm.extend(klv(
+            b"GPS5",
+            b'l',
+            20,
+            &be_i32s(&[
+                515_000_000,
+                -1_000_000,
+                10_000,
+                10_000,
+                1000,
+                515_000_100,
+                -1_000_000,
+                10_000,
+                12_000,
+                1200,
+            ]),
+        ));
+        let gpmf = nested(b"DEVC", &nested(b"STRM", &strm));
+        sample_track(b"meta", b"gpmd", &[gpmf])
+    }
+
+    #[test]
+    fn test_gopro_gpmf_track() {
+        let telemetry = read_telemetry(&mut Cursor::new(gopro_file()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(telemetry.format, TelemetryFormat::Gpmf);
+        assert_eq!(telemetry.samples.len(), 2);
+        assert_eq!(telemetry.samples[1].timestamp, 0.5);
+        assert_eq!(telemetry.samples[0].latitude, Some(51.5));
+        assert_eq!(telemetry.samples[0].longitude, Some(-0.1));
+        assert!((telemetry.samples[1].speed.unwrap() - 43.2).abs() < 1e-3);
+    }
+
+    fn tx3g(text: &str) -> Vec<u8> {
+        let mut sample = (text.len() as u16).to_be_bytes().to_vec();
+        sample.extend_from_slice(text.as_bytes());
+        sample
+    }
+
+    fn subtitle_file() -> Vec<u8> {
+        let samples = [
+            tx3g("$GPRMC,123519.00,A,4807.038,N,01131.000,E,10.0,084.4,230394,003.1,W*71"),
+            tx3g("REC 12:35:20"),
+            tx3g("2024/03/01\n$GPRMC,123521.00,A,4807.038,N,01131.000,E,20.0,084.4,230394,,"),
+        ];
+        sample_track(b"sbtl", b"tx3g", &samples)
+    }
+
+    #[test]
+    fn test_subtitle_track() {
+        let telemetry = read_telemetry(&mut Cursor::new(subtitle_file()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(telemetry.format, TelemetryFormat::Subtitle);
+        assert_eq!(
+            telemetry.samples.len(),
+            2,
+            "the caption without RMC is skipped"
+        );
+        // Timed by the sample, not the sentence
+        assert_eq!(telemetry.samples[0].timestamp, 0.0);
+        assert_eq!(telemetry.samples[1].timestamp, 2.0);
+        assert!((telemetry.samples[0].latitude.unwrap() - 48.1173).abs() < 1e-4);
+        assert!((telemetry.samples[1].speed.unwrap() - 37.04).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_oversized_counts() {
+        // Every table claims u32::MAX entries but holds at most one
+        let mut tables = full_box(b"stts", &be_i32s(&[-1, -1, 1000]));
+        tables.extend(full_box(b"stsc", &be_i32s(&[-1, 1, -1, 1])));
+        tables.extend(full_box(b"stsz", &be_i32s(&[0, -1, 8])));
+        tables.extend(full_box(b"stco", &be_i32s(&[-1, MDAT_OFFSET])));
+        let file = track_file(b"meta", b"gpmd", &[0; 8], &tables);
+        assert!(read_telemetry(&mut Cursor::new(file)).unwrap().is_none());
+
+        // A fixed sample size lets the count go unchecked by the table
+        let mut tables = full_box(b"stts", &be_i32s(&[1, -1, 1]));
+        tables.extend(full_box(b"stsc", &be_i32s(&[1, 1, -1, 1])));
+        tables.extend(full_box(b"stsz", &be_i32s(&[1, -1])));
+        tables.extend(full_box(b"stco", &be_i32s(&[1, MDAT_OFFSET])));
+        let file = track_file(b"meta", b"gpmd", &[0; 8], &tables);
+        // Runs off the end of the file rather than allocating 4 billion samples
+        assert!(!matches!(
+            read_telemetry(&mut Cursor::new(file)),
+            Ok(Some(_))
+        ));
+
+        // Samples larger than the read cap
+        let mut tables = full_box(b"stts", &be_i32s(&[1, 1, 1000]));
+        tables.extend(full_box(b"stsc", &be_i32s(&[1, 1, 1, 1])));
+        tables.extend(full_box(b"stsz", &be_i32s(&[0, 1, -1])));
+        tables.extend(full_box(b"stco", &be_i32s(&[1, MDAT_OFFSET])));
+        let file = track_file(b"meta", b"gpmd", &[0; 8], &tables);
+        assert!(read_telemetry(&mut Cursor::new(file)).is_err());
+
+        // A 64-bit box size that overflows the end offset
+        let mut file = ftyp();
+        file.extend_from_slice(&1u32.to_be_bytes());
+        file.extend_from_slice(b"moov");
+        file.extend_from_slice(&(u64::MAX - 4).to_be_bytes());
+        assert!(read_telemetry(&mut Cursor::new(file)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_gpmf_depth_limit() {
+        let mut data = klv(b"GPS5", b'l', 20, &be_i32s(&[1, 2, 3, 4, 5]));
+        for depth in 0..=MAX_GPMF_DEPTH + 1 {
+            let mut fixes = Vec::new();
+            walk_gpmf(&data, &mut GpmfStream::default(), &mut fixes, 0);
+            assert_eq!(
+                fixes.len(),
+                usize::from(depth <= MAX_GPMF_DEPTH),
+                "depth {}",
+                depth
+            );
+            data = nested(b"DEVC", &data);
+        }
+    }
+
+    /// Random byte corruptions of valid files must fail or yield nothing,
+    /// never panic or allocate without bound.
+    #[test]
+    fn test_corrupt_files() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for original in [gopro_file(), subtitle_file()] {
+            for _ in 0..500 {
+                let mut file = original.clone();
+                for _ in 0..1 + next() % 4 {
+                    let at = next() as usize % file.len();
+                    file[at] = match next() % 3 {
+                        0 => 0xff,
+                        1 => 0,
+                        _ => next() as u8,
+                    };
+                }
+                let _ = read_telemetry(&mut Cursor::new(file));
+            }
+        }
+    }
+
+    #[test]
+    fn test_plain_video_has_no_telemetry() {
+        let mut file = ftyp();
+        file.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &[0; 100])));
+        assert!(read_telemetry(&mut Cursor::new(file)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,456 @@
+//! Burns an analysis into a copy of its source video for review.
+//!
+//! Each output frame shows the most recent analysed frame at or before it, so
+//! overlays hold between samples. Renders are written to a partial file and
+//! renamed when complete, so a cached render is always whole.
+
+use crate::coaching::CATEGORIES;
+use crate::types::{CriticalEvent, DrivingAnalysis, FrameAnalysis, RiskLevel, SignalColor};
+use anyhow::{Context, Result};
+use opencv::core::{Mat, Point, Rect, Scalar};
+use opencv::imgproc;
+use opencv::prelude::*;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Events stay highlighted this long past their last flagged frame, in seconds,
+/// since frames are only sampled about once a second.
+const EVENT_HOLD: f64 = 1.0;
+/// Frame height the overlay layout is designed for; other sizes scale from it.
+const REFERENCE_HEIGHT: f64 = 720.0;
+
+const OVERLAYS: [&str; 4] = ["scores", "signals", "risk", "events"];
+
+/// Which overlays to draw; all of them by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderOptions {
+    /// Per-category scores of the current frame.
+    pub scores: bool,
+    /// Traffic light, stop sign, speed limit and speed.
+    pub signals: bool,
+    /// Overall score and risk level bar.
+    pub risk: bool,
+    /// Border and label while a critical event is in progress.
+    pub events: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            scores: true,
+            signals: true,
+            risk: true,
+            events: true,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Parses a comma-separated overlay list such as `scores,risk`.
+    pub fn parse(overlays: &str) -> Result<Self> {
+        let mut options = Self {
+            scores: false,
+            signals: false,
+            risk: false,
+            events: false,
+        };
+        for name in overlays.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match name {
+                "scores" => options.scores = true,
+                "signals" => options.signals = true,
+                "risk" => options.risk = true,
+                "events" => options.events = true,
+                other => anyhow::bail!(
+                    "Unknown overlay '{}', expected one of {}",
+                    other,
+                    OVERLAYS.join(", ")
+                ),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Stable name for the cached render, e.g. `scores-risk`.
+    fn key(&self) -> String {
+        let enabled = [self.scores, self.signals, self.risk, self.events];
+        let names: Vec<&str> = OVERLAYS
+            .iter()
+            .zip(enabled)
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| *name)
+            .collect();
+        if names.is_empty() {
+            "plain".to_string()
+        } else {
+            names.join("-")
+        }
+    }
+}
+
+/// Progress of a render job, keyed by analysis and options.
+#[derive(Debug, Clone)]
+pub enum RenderStatus {
+    Rendering,
+    Ready(PathBuf),
+    Failed(String),
+}
+
+pub struct Renderer {
+    dir: PathBuf,
+}
+
+impl Renderer {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create render directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Reads `VGLNT_RENDER_DIR`, defaulting to a directory under the system temp dir.
+    pub fn from_env() -> Result<Self> {
+        let dir = std::env::var("VGLNT_RENDER_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("vglnt").join("renders"));
+        Self::new(dir)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn output_path(&self, analysis_id: Uuid, options: RenderOptions) -> PathBuf {
+        self.dir
+            .join(format!("{}-{}.mp4", analysis_id, options.key()))
+    }
+
+    /// Deletes every cached render of an analysis.
+    pub fn remove(&self, analysis_id: Uuid) -> std::io::Result<()> {
+        super::store::remove_cached(&self.dir, analysis_id)
+    }
+
+    /// Renders `analysis` over `source`, reusing a cached render if present.
+    /// A failed render leaves no partial file behind.
+    ///
+    /// Blocking; run it off the async runtime.
+    pub fn render(
+        &self,
+        source: &Path,
+        analysis: &DrivingAnalysis,
+        options: RenderOptions,
+    ) -> Result<PathBuf> {
+        let output = self.output_path(analysis.metadata.id, options);
+        if output.exists() {
+            return Ok(output);
+        }
+        let partial = output.with_extension("partial.mp4");
+        let rendered = write_render(source, analysis, options, &partial).and_then(|()| {
+            std::fs::rename(&partial, &output)
+                .with_context(|| format!("Failed to finish render {}", output.display()))
+        });
+        if rendered.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        rendered.map(|()| output)
+    }
+}
+
+/// Writes every frame of `source` with the overlays drawn to `partial`.
+fn write_render(
+    source: &Path,
+    analysis: &DrivingAnalysis,
+    options: RenderOptions,
+    partial: &Path,
+) -> Result<()> {
+    let source_str = source.to_str().context("Invalid source video path")?;
+    let (mut capture, fps) = super::open_video(source_str)?;
+    let (mut writer, size) = super::open_writer(partial, &capture, fps)?;
+
+    let overlay = Overlay {
+        analysis,
+        options,
+        scale: (size.height as f64 / REFERENCE_HEIGHT).max(0.5),
+    };
+    let mut frame = Mat::default();
+    let mut frame_number = 0u32;
+    while capture.read(&mut frame)? {
+        if frame.empty() {
+            break;
+        }
+        overlay.draw(&mut frame, frame_number, frame_number as f64 / fps)?;
+        writer.write(&frame)?;
+        frame_number += 1;
+    }
+    writer.release()?;
+    Ok(())
+}
+
+/// The analysed frame shown at `frame_number`: the latest one at or before it.
+fn frame_at(frames: &[FrameAnalysis], frame_number: u32) -> Option<&FrameAnalysis> {
+    let idx = frames.partition_point(|f| f.frame_number <= frame_number);
+    idx.checked_sub(1).map(|idx| &frames[idx])
+}
+
+fn events_at(events: &[CriticalEvent], timestamp: f64) -> Vec<&CriticalEvent> {
+    events
+        .iter()
+        .filter(|e| timestamp >= e.timestamp && timestamp < e.end_timestamp + EVENT_HOLD)
+        .collect()
+}
+
+// BGR
+fn white() -> Scalar {
+    Scalar::new(255.0, 255.0, 255.0, 0.0)
+}
+
+fn panel_colour() -> Scalar {
+    Scalar::new(30.0, 30.0, 30.0, 0.0)
+}
+
+fn grade_colour(fraction: f32) -> Scalar {
+    if fraction >= 0.8 {
+        Scalar::new(80.0, 200.0, 80.0, 0.0)
+    } else if fraction >= 0.5 {
+        Scalar::new(0.0, 200.0, 255.0, 0.0)
+    } else {
+        Scalar::new(60.0, 60.0, 230.0, 0.0)
+    }
+}
+
+fn risk_colour(level: RiskLevel) -> Scalar {
+    match level {
+        RiskLevel::Low => grade_colour(1.0),
+        RiskLevel::Medium => grade_colour(0.6),
+        RiskLevel::High => Scalar::new(0.0, 120.0, 255.0, 0.0),
+        RiskLevel::Critical => grade_colour(0.0),
+    }
+}
+
+fn signal_colour(colour: &SignalColor) -> Scalar {
+    match colour {
+        SignalColor::Red => Scalar::new(0.0, 0.0, 255.0, 0.0),
+        SignalColor::Yellow => Scalar::new(0.0, 220.0, 255.0, 0.0),
+        SignalColor::Green => Scalar::new(0.0, 220.0, 0.0, 0.0),
+        SignalColor::Unknown => Scalar::new(128.0, 128.0, 128.0, 0.0),
+    }
+}
+
+struct Overlay<'a> {
+    analysis: &'a DrivingAnalysis,
+    options: RenderOptions,
+    /// Frame height relative to `REFERENCE_HEIGHT`.
+    scale: f64,
+}
+
+impl Overlay<'_> {
+    fn draw(&self, frame: &mut Mat, frame_number: u32, timestamp: f64) -> Result<()> {
+        let current = frame_at(&self.analysis.frame_analyses, frame_number);
+        if let Some(current) = current {
+            if self.options.scores {
+                self.draw_scores(frame, current)?;
+            }
+            if self.options.signals {
+                self.draw_signals(frame, current)?;
+            }
+        }
+        if self.options.risk {
+            self.draw_risk(frame)?;
+        }
+        if self.options.events {
+            let events = events_at(&self.analysis.summary.critical_events, timestamp);
+            self.draw_events(frame, &events)?;
+        }
+        Ok(())
+    }
+
+    fn px(&self, value: f64) -> i32 {
+        (value * self.scale).round() as i32
+    }
+
+    fn text(&self, frame: &mut Mat, text: &str, origin: Point, colour: Scalar) -> Result<()> {
+        imgproc::put_text(
+            frame,
+            text,
+            origin,
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            0.5 * self.scale,
+            colour,
+            self.px(1.0).max(1),
+            imgproc::LINE_AA,
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn fill(&self, frame: &mut Mat, rect: Rect, colour: Scalar) -> Result<()> {
+        imgproc::rectangle(frame, rect, colour, imgproc::FILLED, imgproc::LINE_8, 0)?;
+        Ok(())
+    }
+
+    fn draw_scores(&self, frame: &mut Mat, current: &FrameAnalysis) -> Result<()> {
+        let line = self.px(22.0);
+        let margin = self.px(10.0);
+        let height = line * CATEGORIES.len() as i32 + margin;
+        self.fill(
+            frame,
+            Rect::new(margin, margin, self.px(260.0), height),
+            panel_colour(),
+        )?;
+
+        for (idx, category) in CATEGORIES.iter().enumerate() {
+            let score = (category.score)(current);
+            let label = format!(
+                "{:<22}{:>5.1}/{:.0}",
+                category.name.replace('_', " "),
+                score,
+                category.max_score
+            );
+            let origin = Point::new(2 * margin, margin + line * (idx as i32 + 1));
+            self.text(
+                frame,
+                &label,
+                origin,
+                grade_colour(score / category.max_score),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_signals(&self, frame: &mut Mat, current: &FrameAnalysis) -> Result<()> {
+        let light = &current.signal_compliance.traffic_light;
+        let speed = &current.road_sign_awareness.speed_limit;
+        let mut lines = vec![format!("Light: {:?}", light.status)];
+        if current.signal_compliance.stop_sign.present {
+            lines.push("Stop sign".to_string());
+        }
+        if let Some(limit) = speed.limit {
+            lines.push(format!("Limit: {} km/h", limit));
+        }
+        if let Some(current_speed) = speed.current_speed {
+            lines.push(format!("Speed: {:.0} km/h", current_speed));
+        }
+
+        let line = self.px(22.0);
+        let margin = self.px(10.0);
+        let width = self.px(180.0);
+        let left = frame.cols() - width - margin;
+        self.fill(
+            frame,
+            Rect::new(left, margin, width, line * lines.len() as i32 + margin),
+            panel_colour(),
+        )?;
+        imgproc::circle(
+            frame,
+            Point::new(left + width - 2 * margin, margin + line / 2 + margin / 2),
+            self.px(7.0),
+            signal_colour(&light.status),
+            imgproc::FILLED,
+            imgproc::LINE_AA,
+            0,
+        )?;
+        for (idx, text) in lines.iter().enumerate() {
+            let origin = Point::new(left + margin, margin + line * (idx as i32 + 1));
+            self.text(frame, text, origin, white())?;
+        }
+        Ok(())
+    }
+
+    fn draw_risk(&self, frame: &mut Mat) -> Result<()> {
+        let summary = &self.analysis.summary;
+        let height = self.px(24.0);
+        let top = frame.rows() - height;
+        let width = frame.cols();
+        let filled = (width as f32 * (summary.overall_score / 100.0).clamp(0.0, 1.0)) as i32;
+
+        self.fill(frame, Rect::new(0, top, width, height), panel_colour())?;
+        self.fill(
+            frame,
+            Rect::new(0, top, filled, height),
+            risk_colour(summary.risk_level),
+        )?;
+        let label = format!(
+            "Score {:.1}  {:?} risk",
+            summary.overall_score, summary.risk_level
+        );
+        let origin = Point::new(self.px(10.0), top + height - self.px(7.0));
+        self.text(frame, &label, origin, white())
+    }
+
+    fn draw_events(&self, frame: &mut Mat, events: &[&CriticalEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let red = signal_colour(&SignalColor::Red);
+        let border = self.px(6.0).max(2);
+        imgproc::rectangle(
+            frame,
+            Rect::new(0, 0, frame.cols(), frame.rows()),
+            red,
+            border,
+            imgproc::LINE_8,
+            0,
+        )?;
+
+        let line = self.px(26.0);
+        let top = frame.rows() / 3;
+        for (idx, event) in events.iter().enumerate() {
+            let label = format!(
+                "{} ({:.2})",
+                event.event_type.replace('_', " ").to_uppercase(),
+                event.severity
+            );
+            let origin = Point::new(frame.cols() / 2 - self.px(120.0), top + line * idx as i32);
+            self.fill(
+                frame,
+                Rect::new(
+                    origin.x - self.px(8.0),
+                    origin.y - line + self.px(6.0),
+                    self.px(256.0),
+                    line,
+                ),
+                red,
+            )?;
+            self.text(frame, &label, origin, white())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::fixtures::timeline;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_overlays() {
+        let options = RenderOptions::parse("scores, risk").unwrap();
+        assert!(options.scores && options.risk);
+        assert!(!options.signals && !options.events);
+        assert_eq!(options.key(), "scores-risk");
+
+        assert_eq!(RenderOptions::parse("").unwrap().key(), "plain");
+        assert_eq!(RenderOptions::default().key(), "scores-signals-risk-events");
+        assert!(RenderOptions::parse("scores,speedo").is_err());
+    }
+
+    #[test]
+    fn test_overlay_holds_between_samples() {
+        let frames = timeline(3);
+        assert_eq!(frame_at(&frames, 0).unwrap().frame_number, 0);
+        assert_eq!(frame_at(&frames, 45).unwrap().frame_number, 30);
+        assert_eq!(frame_at(&frames, 900).unwrap().frame_number, 60);
+
+        let event = CriticalEvent {
+            event_type: "tailgating".to_string(),
+            timestamp: 1.0,
+            end_timestamp: 2.0,
+            severity: 0.6,
+            frames: vec![30, 60],
+            context: HashMap::new(),
+        };
+        let events = [event];
+        assert!(events_at(&events, 0.5).is_empty());
+        assert_eq!(events_at(&events, 2.5).len(), 1);
+        assert!(events_at(&events, 3.0).is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Uploaded videos, kept after analysis so they can be rendered and clipped.
+pub struct VideoStore {
+    dir: PathBuf,
+}
+
+impl VideoStore {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create video directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Reads `VGLNT_VIDEO_DIR`, defaulting to a directory under the system temp dir.
+    pub fn from_env() -> Result<Self> {
+        let dir = std::env::var("VGLNT_VIDEO_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("vglnt").join("videos"));
+        Self::new(dir)
+    }
+
+    /// Where uploads are staged, so keeping one is a rename on the same filesystem.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, analysis_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.mp4", analysis_id))
+    }
+
+    pub fn remove(&self, analysis_id: Uuid) -> std::io::Result<()> {
+        match std::fs::remove_file(self.path(analysis_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Deletes the videos of analyses `keep` rejects, and uploads left
+    /// half-staged, returning how many files went. Only safe while no upload
+    /// is in progress, i.e. before the server starts.
+    pub fn retain(&self, keep: impl Fn(Uuid) -> bool) -> std::io::Result<usize> {
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let orphaned = match name.strip_suffix(".mp4").map(Uuid::parse_str) {
+                Some(Ok(analysis_id)) => !keep(analysis_id),
+                _ => name.starts_with(".tmp"),
+            };
+            if orphaned {
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Deletes the files in `dir` derived from an analysis, named `<id>-...`.
+pub(crate) fn remove_cached(dir: &Path, analysis_id: Uuid) -> std::io::Result<()> {
+    let prefix = format!("{}-", analysis_id);
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retain() {
+        let dir = tempfile::tempdir().unwrap();
+        let videos = VideoStore::new(dir.path().to_path_buf()).unwrap();
+        let (kept, orphaned) = (Uuid::new_v4(), Uuid::new_v4());
+        for path in [
+            videos.path(kept),
+            videos.path(orphaned),
+            dir.path().join(".tmpAbC123"),
+            dir.path().join("notes.txt"),
+        ] {
+            std::fs::write(path, b"").unwrap();
+        }
+
+        assert_eq!(videos.retain(|id| id == kept).unwrap(), 2);
+        assert!(videos.path(kept).exists());
+        assert!(!videos.path(orphaned).exists());
+        assert!(!dir.path().join(".tmpAbC123").exists());
+        assert!(dir.path().join("notes.txt").exists());
+    }
+}